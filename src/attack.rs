@@ -0,0 +1,391 @@
+use std::collections::VecDeque;
+
+use bevy::math::Vec2;
+use bevy::prelude::{Commands, Entity, NextState, Query, Res, ResMut, Resource, Transform, With};
+use bevy::sprite::Sprite;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::board::garbage_row;
+use crate::common_component::{GameData, GameOutcome, GameState, GarbageMeter, LineClear, BOARD_COLS, BOARD_ROWS, DOT_SIZE};
+use crate::garbage::{raise_garbage, BoardDotQuery, LiftedBlockQuery};
+use crate::player::MainBoard;
+
+/// Lines of garbage sent per clear. The defaults follow the Guideline versus table.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct AttackTable {
+    /// Indexed by lines cleared (0-4) without a T-spin
+    pub lines: [u32; 5],
+    /// Indexed by lines cleared (0-3) with a T-spin
+    pub t_spin: [u32; 4],
+    /// Added on top of the clear when it leaves the board empty
+    pub perfect_clear: u32,
+    /// Added to a Tetris or T-spin clear that follows another one
+    pub back_to_back: u32,
+    /// Indexed by combo count (0 for the first clear of a chain); the last entry repeats
+    pub combo: Vec<u32>,
+    /// Most garbage rows inserted after a single lock; the rest waits for the next one
+    pub insert_cap: u32,
+}
+
+impl Default for AttackTable {
+    fn default() -> Self {
+        Self {
+            lines: [0, 0, 1, 2, 4],
+            t_spin: [0, 2, 4, 6],
+            perfect_clear: 10,
+            back_to_back: 1,
+            combo: vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+            insert_cap: 8,
+        }
+    }
+}
+
+impl AttackTable {
+    /// Lines sent by `clear`, given the back-to-back and combo state it was made in
+    pub fn attack(&self, clear: &LineClear, back_to_back: bool, combo: u32) -> u32 {
+        let lines = clear.lines as usize;
+        let base = if clear.t_spin {
+            self.t_spin.get(lines).or(self.t_spin.last())
+        } else {
+            self.lines.get(lines).or(self.lines.last())
+        };
+        let mut attack = base.copied().unwrap_or(0);
+        if back_to_back && is_difficult(clear) {
+            attack += self.back_to_back;
+        }
+        attack += self.combo.get(combo as usize).or(self.combo.last()).copied().unwrap_or(0);
+        if clear.perfect_clear {
+            attack += self.perfect_clear;
+        }
+        attack
+    }
+}
+
+/// Named garbage rules; the host of an online game picks one and the guest plays by it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RuleSet {
+    /// The Guideline versus table (`AttackTable::default`)
+    #[default]
+    Guideline,
+    /// Line clears only: no T-spin, back-to-back, combo or perfect clear bonus
+    Classic,
+}
+
+impl RuleSet {
+    pub const ALL: [RuleSet; 2] = [RuleSet::Guideline, RuleSet::Classic];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuleSet::Guideline => "Guideline",
+            RuleSet::Classic => "Classic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RuleSet> {
+        Self::ALL.into_iter().find(|rules| rules.name() == name)
+    }
+
+    pub fn table(&self) -> AttackTable {
+        match self {
+            RuleSet::Guideline => AttackTable::default(),
+            RuleSet::Classic => AttackTable {
+                t_spin: [0, 0, 1, 2],
+                perfect_clear: 0,
+                back_to_back: 0,
+                combo: vec![0],
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Tetrises and T-spin clears keep a back-to-back chain going; other clears break it
+fn is_difficult(clear: &LineClear) -> bool {
+    clear.lines >= 4 || (clear.t_spin && clear.lines > 0)
+}
+
+/// Garbage rows that arrive together share one hole column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GarbagePacket {
+    pub lines: u32,
+    pub hole: usize,
+}
+
+/// Attack bookkeeping of one board
+#[derive(Debug, Clone)]
+pub struct AttackState {
+    /// Clears in a row minus one, `None` once a piece locks without clearing
+    pub combo: Option<u32>,
+    /// Whether the last clear was a Tetris or a T-spin clear
+    pub back_to_back: bool,
+    /// Garbage waiting to be inserted, oldest first
+    pub incoming: VecDeque<GarbagePacket>,
+    /// Attack left over after cancelling, waiting to be sent to an opponent
+    pub outgoing: u32,
+    pub lines_sent: u32,
+    /// Picks the hole column of the garbage this board receives; seeded, so a replayed game gets the same holes
+    pub holes: StdRng,
+}
+
+impl Default for AttackState {
+    fn default() -> Self {
+        Self::with_hole_seed(rand::random())
+    }
+}
+
+impl AttackState {
+    /// Two boards with the same seed get the same holes in the garbage they receive
+    pub fn with_hole_seed(seed: u64) -> Self {
+        Self {
+            combo: None,
+            back_to_back: false,
+            incoming: VecDeque::new(),
+            outgoing: 0,
+            lines_sent: 0,
+            holes: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn receive(&mut self, packet: GarbagePacket) {
+        if packet.lines > 0 {
+            self.incoming.push_back(packet);
+        }
+    }
+
+    /// Queues a packet of `lines` garbage rows with a hole of this board's picking
+    pub fn receive_lines(&mut self, lines: u32) {
+        let hole = self.holes.gen_range(0..BOARD_COLS);
+        self.receive(GarbagePacket { lines, hole });
+    }
+
+    pub fn incoming_lines(&self) -> u32 {
+        self.incoming.iter().map(|packet| packet.lines).sum()
+    }
+
+    /// Attack first cancels incoming garbage, oldest first; returns what is left to send
+    fn cancel(&mut self, mut attack: u32) -> u32 {
+        while attack > 0 {
+            let Some(packet) = self.incoming.front_mut() else {
+                break;
+            };
+            let cancelled = attack.min(packet.lines);
+            packet.lines -= cancelled;
+            attack -= cancelled;
+            if packet.lines == 0 {
+                self.incoming.pop_front();
+            }
+        }
+        attack
+    }
+
+    /// Settles a locked piece. A clear attacks (cancelling incoming garbage first);
+    /// a lock without a clear lets up to `insert_cap` incoming rows in, which are returned.
+    pub fn on_lock(&mut self, table: &AttackTable, clear: Option<&LineClear>) -> Vec<GarbagePacket> {
+        let Some(clear) = clear.filter(|clear| clear.lines > 0) else {
+            self.combo = None;
+            return self.take_incoming(table.insert_cap);
+        };
+
+        let combo = self.combo.map_or(0, |combo| combo + 1);
+        let difficult = is_difficult(clear);
+        let attack = table.attack(clear, self.back_to_back && difficult, combo);
+        self.combo = Some(combo);
+        self.back_to_back = difficult;
+        self.lines_sent += attack;
+        let left = self.cancel(attack);
+        self.outgoing += left;
+        Vec::new()
+    }
+
+    fn take_incoming(&mut self, cap: u32) -> Vec<GarbagePacket> {
+        let mut room = cap;
+        let mut inserted = Vec::new();
+        while room > 0 {
+            let Some(packet) = self.incoming.front_mut() else {
+                break;
+            };
+            let lines = packet.lines.min(room);
+            inserted.push(GarbagePacket { lines, hole: packet.hole });
+            packet.lines -= lines;
+            room -= lines;
+            if packet.lines == 0 {
+                self.incoming.pop_front();
+            }
+        }
+        inserted
+    }
+}
+
+/// The board rows for a batch of inserted garbage, bottom row last
+pub(crate) fn garbage_rows(packets: &[GarbagePacket]) -> Vec<[i8; BOARD_COLS]> {
+    packets
+        .iter()
+        .flat_map(|packet| std::iter::repeat_n(garbage_row(packet.hole), packet.lines as usize))
+        .collect()
+}
+
+/// Hands each side's outgoing attack to the other side's incoming queue
+pub(crate) fn exchange(a: &mut AttackState, b: &mut AttackState) {
+    let from_a = std::mem::take(&mut a.outgoing);
+    if from_a > 0 {
+        b.receive_lines(from_a);
+    }
+    let from_b = std::mem::take(&mut b.outgoing);
+    if from_b > 0 {
+        a.receive_lines(from_b);
+    }
+}
+
+/// Settles attack and incoming garbage once per locked piece, after line clears are detected
+pub(crate) fn attack_system(
+    mut commands: Commands,
+    mut boards: Query<(Entity, &mut GameData)>,
+    table: Res<AttackTable>,
+    mut board_dots: BoardDotQuery,
+    mut active_block: LiftedBlockQuery,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (board, mut game_data) in boards.iter_mut() {
+        if !std::mem::take(&mut game_data.piece_locked) {
+            continue;
+        }
+        let clear = game_data.last_clear;
+        let rows = garbage_rows(&game_data.attack.on_lock(&table, clear.as_ref()));
+        if !raise_garbage(&mut commands, board, &mut game_data, &mut board_dots, &mut active_block, &rows) {
+            game_data.outcome = GameOutcome::TopOut;
+            next_state.set(GameState::GameOver);
+        }
+    }
+}
+
+const METER_WIDTH: f32 = 6.0;
+const METER_X: f32 = -(BOARD_COLS as f32 * DOT_SIZE / 2.0) - METER_WIDTH / 2.0 - 1.0;
+const BOARD_BOTTOM_Y: f32 = -(BOARD_ROWS as f32 * DOT_SIZE / 2.0);
+
+/// Grows the bar left of the main board with its incoming garbage, one cell per row
+pub(crate) fn update_garbage_meter(
+    game_data: Query<&GameData, With<MainBoard>>,
+    mut meter: Query<(&mut Sprite, &mut Transform), With<GarbageMeter>>,
+) {
+    let (Ok(game_data), Ok((mut sprite, mut transform))) = (game_data.get_single(), meter.get_single_mut()) else {
+        return;
+    };
+    let height = game_data.attack.incoming_lines().min(BOARD_ROWS as u32) as f32 * DOT_SIZE;
+    let size = Some(Vec2::new(METER_WIDTH, height));
+    if sprite.custom_size != size {
+        sprite.custom_size = size;
+        transform.translation.x = METER_X;
+        transform.translation.y = BOARD_BOTTOM_Y + height / 2.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(lines: u32, t_spin: bool) -> LineClear {
+        LineClear {
+            lines,
+            t_spin,
+            perfect_clear: false,
+        }
+    }
+
+    #[test]
+    fn attack_follows_the_table() {
+        let table = AttackTable::default();
+        assert_eq!(table.attack(&clear(1, false), false, 0), 0);
+        assert_eq!(table.attack(&clear(4, false), false, 0), 4);
+        assert_eq!(table.attack(&clear(2, true), false, 0), 4);
+        assert_eq!(table.attack(&clear(4, false), true, 0), 5);
+        assert_eq!(table.attack(&clear(2, false), false, 20), 1 + 5);
+        let perfect = LineClear {
+            perfect_clear: true,
+            ..clear(4, false)
+        };
+        assert_eq!(table.attack(&perfect, false, 0), 14);
+    }
+
+    #[test]
+    fn back_to_back_needs_two_difficult_clears_in_a_row() {
+        let table = AttackTable::default();
+        let mut state = AttackState::default();
+        state.on_lock(&table, Some(&clear(4, false)));
+        assert_eq!(state.lines_sent, 4);
+        // A lock without a clear ends the combo but keeps back-to-back
+        state.on_lock(&table, None);
+        state.on_lock(&table, Some(&clear(2, true)));
+        assert_eq!(state.lines_sent, 4 + 5);
+        state.on_lock(&table, None);
+        state.on_lock(&table, Some(&clear(1, false)));
+        state.on_lock(&table, None);
+        state.on_lock(&table, Some(&clear(4, false)));
+        assert_eq!(state.lines_sent, 4 + 5 + 4);
+    }
+
+    #[test]
+    fn combos_build_on_consecutive_clears() {
+        let table = AttackTable::default();
+        let mut state = AttackState::default();
+        for _ in 0..4 {
+            state.on_lock(&table, Some(&clear(1, false)));
+        }
+        assert_eq!(state.combo, Some(3));
+        // Combo bonuses 0, 0, 1, 1
+        assert_eq!(state.lines_sent, 2);
+        state.on_lock(&table, None);
+        assert_eq!(state.combo, None);
+    }
+
+    #[test]
+    fn attack_cancels_incoming_first() {
+        let table = AttackTable::default();
+        let mut state = AttackState::default();
+        state.receive(GarbagePacket { lines: 3, hole: 0 });
+        state.receive(GarbagePacket { lines: 2, hole: 5 });
+        state.on_lock(&table, Some(&clear(4, false)));
+        assert_eq!(state.incoming_lines(), 1);
+        assert_eq!(state.outgoing, 0);
+        state.on_lock(&table, Some(&clear(4, false)));
+        assert_eq!(state.incoming_lines(), 0);
+        // 4 + back-to-back 1 + combo 0, minus the 1 line still incoming
+        assert_eq!(state.outgoing, 4);
+    }
+
+    #[test]
+    fn garbage_waits_for_a_lock_without_a_clear_and_is_capped() {
+        let table = AttackTable::default();
+        let mut state = AttackState::default();
+        state.receive(GarbagePacket { lines: 6, hole: 1 });
+        state.receive(GarbagePacket { lines: 4, hole: 7 });
+        assert!(state.on_lock(&table, Some(&clear(1, false))).is_empty());
+        let inserted = state.on_lock(&table, None);
+        assert_eq!(inserted, vec![GarbagePacket { lines: 6, hole: 1 }, GarbagePacket { lines: 2, hole: 7 }]);
+        assert_eq!(state.incoming_lines(), 2);
+    }
+
+    #[test]
+    fn boards_with_the_same_hole_seed_get_the_same_garbage() {
+        let mut first = AttackState::with_hole_seed(7);
+        let mut second = AttackState::with_hole_seed(7);
+        for lines in 1..10 {
+            first.receive_lines(lines);
+            second.receive_lines(lines);
+        }
+        assert_eq!(first.incoming, second.incoming);
+    }
+
+    #[test]
+    fn classic_rules_only_count_lines() {
+        let table = RuleSet::Classic.table();
+        let t_spin_double = LineClear {
+            lines: 2,
+            t_spin: true,
+            perfect_clear: true,
+        };
+        assert_eq!(table.attack(&t_spin_double, true, 5), 1);
+        assert_eq!(RuleSet::from_name("Classic"), Some(RuleSet::Classic));
+        assert_eq!(RuleSet::from_name("Tetris"), None);
+    }
+}
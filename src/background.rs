@@ -6,16 +6,17 @@ use bevy::prelude::{
     BuildChildren, Camera2d, ChildBuild, Commands, Gizmos, Mesh, Mesh2d, Query, Rectangle, ResMut,
     Text, Transform, With,
 };
-use bevy::sprite::{ColorMaterial, MeshMaterial2d};
+use bevy::sprite::{ColorMaterial, MeshMaterial2d, Sprite};
 use bevy::text::{TextColor, TextFont};
-use bevy::ui::{AlignItems, Display, JustifyContent, Node, PositionType, UiRect, Val};
+use bevy::ui::{AlignItems, Display, FlexDirection, JustifyContent, Node, PositionType, UiRect, Val};
 use bevy::utils::default;
 use bevy::window::{PrimaryWindow, Window};
 
-use crate::common_component::{LevelText, LinesText, ScoreText};
+use crate::common_component::{FinesseText, GarbageMeter, LevelText, LinesText, ScoreText, SplitsText, TimerText};
 
-const MAIN_COLOR: Color = Color::srgb(62.0 / 255.0, 209.0 / 255.0, 185.0 / 255.0);
-const INNER_WINDOW_COLOR: Color = Color::srgb(0.0, 0.0, 0.0);
+pub(crate) const MAIN_COLOR: Color = Color::srgb(62.0 / 255.0, 209.0 / 255.0, 185.0 / 255.0);
+pub(crate) const INNER_WINDOW_COLOR: Color = Color::srgb(0.0, 0.0, 0.0);
+pub(crate) const GARBAGE_METER_COLOR: Color = Color::srgb(0.9, 0.15, 0.15);
 
 const BORDER_WIDTH: f32 = 8.0;
 const SINGLE_GRID_SIZE: f32 = 25.0;
@@ -31,7 +32,7 @@ const SIDE_BOX_WIDTH: f32 = 80.0;
 const SIDE_INNER_BOX_WIDTH: f32 = SIDE_BOX_WIDTH - 2.0 * BORDER_WIDTH;
 const HOLD_BOX_HEIGHT: f32 = 80.0;
 const NEXT_BOX_HEIGHT: f32 = 300.0;
-const SCORE_BOX_HEIGHT: f32 = 120.0;
+const SCORE_BOX_HEIGHT: f32 = 140.0;
 
 pub fn setup_background(
     mut commands: Commands,
@@ -41,6 +42,7 @@ pub fn setup_background(
 ) {
     spawn_camera(&mut commands);
     spawn_background(&mut commands, &mut meshes, &mut materials, window_query);
+    spawn_garbage_meter(&mut commands);
 }
 
 pub fn setup_background_grid(mut gizmos: Gizmos) {
@@ -56,6 +58,19 @@ fn spawn_camera(commands: &mut Commands) {
     commands.spawn(Camera2d);
 }
 
+/// Incoming garbage bar in the border between the hold panel and the board; sized by `update_garbage_meter`
+fn spawn_garbage_meter(commands: &mut Commands) {
+    commands.spawn((
+        Sprite {
+            color: GARBAGE_METER_COLOR,
+            custom_size: Some(Vec2::ZERO),
+            ..default()
+        },
+        Transform::from_xyz(0.0, 0.0, 1.0),
+        GarbageMeter,
+    ));
+}
+
 fn spawn_background(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
@@ -106,6 +121,15 @@ fn spawn_background(
         window.height() / 2.0 - CENTER_BOX_HEIGHT / 2.0 + BORDER_WIDTH,
         window.width() / 2.0 - CENTER_BOX_WIDTH / 2.0 - SIDE_BOX_WIDTH,
     );
+    spawn_splits_display(
+        commands,
+        window.height() / 2.0 - CENTER_BOX_HEIGHT / 2.0
+            + BORDER_WIDTH
+            + TEXT_BOX_HEIGHT
+            + HOLD_BOX_HEIGHT
+            + BORDER_WIDTH,
+        window.width() / 2.0 - CENTER_BOX_WIDTH / 2.0 - SIDE_BOX_WIDTH,
+    );
 
     // right
     commands
@@ -245,5 +269,59 @@ fn spawn_score_display(commands: &mut Commands, top: f32, left: f32) {
                     ..default()
                 },
             ));
+            parent.spawn((
+                Text::new("0:00.000"),
+                TextColor(WHITE_SMOKE.into()),
+                TextFont {
+                    font_size: 12.0,
+                    ..default()
+                },
+                TimerText,
+                Node {
+                    margin: UiRect::top(Val::Px(5.0)),
+                    ..default()
+                },
+            ));
+        });
+}
+
+/// Sprint splits and the finesse counter, stacked in the left panel below the hold box
+fn spawn_splits_display(commands: &mut Commands, top: f32, left: f32) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Px(SIDE_BOX_WIDTH),
+                position_type: PositionType::Absolute,
+                top: Val::Px(top),
+                left: Val::Px(left),
+                display: Display::Flex,
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextColor(BLACK.into()),
+                TextFont {
+                    font_size: 10.0,
+                    ..default()
+                },
+                SplitsText,
+            ));
+            parent.spawn((
+                Text::new(""),
+                TextColor(BLACK.into()),
+                TextFont {
+                    font_size: 10.0,
+                    ..default()
+                },
+                Node {
+                    margin: UiRect::top(Val::Px(8.0)),
+                    ..default()
+                },
+                FinesseText,
+            ));
         });
 }
@@ -0,0 +1,134 @@
+//! A small stand-in for an external bot speaking the Tetris Bot Protocol, for testing the game's side of it.
+//! It drops the piece to play straight down wherever it ends up lowest, never holding, spinning or tucking.
+
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+use bevy_tetris::board::{fits, Board, Placement, BLOCK_CELL, EMPTY_CELL};
+use bevy_tetris::common_component::{BOARD_COLS, BOARD_ROWS};
+use bevy_tetris::line_clear::collapse_full_rows;
+use bevy_tetris::tbp::Move;
+use bevy_tetris::tetromino::{Block, State};
+use serde_json::{json, Value};
+
+struct Game {
+    board: Board,
+    queue: VecDeque<char>,
+    hold: Option<char>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self {
+            board: [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS],
+            queue: VecDeque::new(),
+            hold: None,
+        }
+    }
+}
+
+/// Every rotation and column the piece can be dropped straight down into
+fn drops(board: &Board, piece: &Block) -> Vec<Placement> {
+    let mut found = Vec::new();
+    for state in [State::Zero, State::One, State::Two, State::Three] {
+        let mut block = piece.clone();
+        block.set_state(state);
+        for column in -3..BOARD_COLS as i8 {
+            let mut row = -4;
+            if !fits(board, &block, column, row, true) {
+                continue;
+            }
+            while fits(board, &block, column, row + 1, true) {
+                row += 1;
+            }
+            found.push(Placement {
+                block: block.clone(),
+                column,
+                row,
+            });
+        }
+    }
+    found
+}
+
+/// Every straight drop of the piece to play, lowest first
+fn suggestion(game: &Game) -> Value {
+    let Some(piece) = game.queue.front().and_then(|&letter| Block::from_letter(letter)) else {
+        return json!({"type": "suggestion", "moves": []});
+    };
+    let mut drops: Vec<_> = drops(&game.board, &piece).iter().map(Move::from_placement).collect();
+    // Rows count down, so the lowest drop has the largest top row
+    drops.sort_by_key(|candidate| {
+        let cells = candidate.cells().unwrap_or_default();
+        let top = cells.iter().map(|&(_, row)| row).min().unwrap_or_default();
+        Reverse((top, cells.iter().map(|&(_, row)| row as i32).sum::<i32>()))
+    });
+    let moves: Vec<Value> = drops.iter().map(|candidate| json!(candidate)).collect();
+    json!({"type": "suggestion", "moves": moves})
+}
+
+fn start(message: &Value) -> Game {
+    let mut game = Game::default();
+    // The protocol's rows start at the bottom and go on above the game's 20
+    for (y, row) in message["board"].as_array().into_iter().flatten().take(BOARD_ROWS).enumerate() {
+        for (x, cell) in row.as_array().into_iter().flatten().take(BOARD_COLS).enumerate() {
+            if !cell.is_null() {
+                game.board[BOARD_ROWS - 1 - y][x] = BLOCK_CELL;
+            }
+        }
+    }
+    let piece = |value: &Value| value.as_str().and_then(|letter| letter.chars().next());
+    game.queue = message["queue"].as_array().into_iter().flatten().filter_map(piece).collect();
+    game.hold = piece(&message["hold"]);
+    game
+}
+
+fn play(game: &mut Game, played: &Value) {
+    let Ok(played) = serde_json::from_value::<Move>(played.clone()) else {
+        return;
+    };
+    let current = game.queue.pop_front();
+    if current != Some(played.location.piece) {
+        if game.hold.is_none() {
+            game.queue.pop_front();
+        }
+        game.hold = current;
+    }
+    for (x, row) in played.cells().into_iter().flatten() {
+        if (0..BOARD_ROWS as i8).contains(&row) {
+            game.board[row as usize][x as usize] = BLOCK_CELL;
+        }
+    }
+    collapse_full_rows(&mut game.board);
+}
+
+fn send(out: &mut impl Write, message: &Value) -> io::Result<()> {
+    writeln!(out, "{message}")?;
+    out.flush()
+}
+
+fn main() -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    send(
+        &mut out,
+        &json!({"type": "info", "name": "stand-in", "version": "1.0", "author": "bevy-tetris", "features": []}),
+    )?;
+    let mut game = Game::default();
+    for line in io::stdin().lock().lines() {
+        let Ok(message) = serde_json::from_str::<Value>(&line?) else {
+            continue;
+        };
+        match message["type"].as_str() {
+            Some("rules") => send(&mut out, &json!({"type": "ready"}))?,
+            Some("start") => game = start(&message),
+            Some("stop") => game = Game::default(),
+            Some("suggest") => send(&mut out, &suggestion(&game))?,
+            Some("play") => play(&mut game, &message["move"]),
+            Some("new_piece") => game.queue.extend(message["piece"].as_str().and_then(|letter| letter.chars().next())),
+            Some("quit") => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
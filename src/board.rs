@@ -1,8 +1,19 @@
-use bevy::prelude::Component;
+use bevy::color::Color;
+use bevy::math::Vec2;
+use bevy::prelude::{BuildChildren, Commands, Component, Entity, Transform};
+use bevy::sprite::Sprite;
+use bevy::utils::default;
 
 use crate::common_component::{BOARD_COLS, BOARD_ROWS, DOT_SIZE};
 use crate::tetromino;
 
+/// Values stored in `GameData::board_matrix`
+pub const EMPTY_CELL: i8 = 0;
+pub const BLOCK_CELL: i8 = 1;
+pub const GARBAGE_CELL: i8 = 2;
+
+pub(crate) const GARBAGE_COLOR: Color = Color::srgb(0.45, 0.45, 0.45);
+
 #[derive(Component, Debug)]
 pub(crate) struct BoardDot {
     pub board_x: i8,
@@ -56,7 +67,7 @@ pub(crate) fn board_check_block_position(
             return false;
         }
 
-        if board[board_y as usize][board_x as usize] == 1 {
+        if board[board_y as usize][board_x as usize] != EMPTY_CELL {
             return false;
         }
     }
@@ -64,8 +75,132 @@ pub(crate) fn board_check_block_position(
     true
 }
 
+/// Lowest y the block reaches when dropped straight down from `y`
+pub(crate) fn landing_y(board: &[[i8; BOARD_COLS]; BOARD_ROWS], x: f32, y: f32, block: &tetromino::Block) -> f32 {
+    let mut landing_y = y;
+    while board_check_block_position(board, x, landing_y - DOT_SIZE, block) {
+        landing_y -= DOT_SIZE;
+    }
+    landing_y
+}
+
+/// 3-corner rule: a T piece counts as a T-spin when at least three of the four cells
+/// diagonal to its center are filled (walls and floor count as filled). The caller also
+/// has to check that the last successful move was a rotation.
+pub(crate) fn is_t_spin_position(board: &[[i8; BOARD_COLS]; BOARD_ROWS], x: f32, y: f32, block: &tetromino::Block) -> bool {
+    if !matches!(block, tetromino::Block::T { .. }) {
+        return false;
+    }
+    // Every T rotation state turns around the dot at (1, 1)
+    let filled_corners = [(0, 0), (2, 0), (0, 2), (2, 2)]
+        .iter()
+        .filter(|(dot_x, dot_y)| {
+            let (board_x, board_y) = get_dot_position_in_board(x, y, *dot_x, *dot_y);
+            !(0..BOARD_COLS as i8).contains(&board_x)
+                || !(0..BOARD_ROWS as i8).contains(&board_y)
+                || board[board_y as usize][board_x as usize] != EMPTY_CELL
+        })
+        .count();
+    filled_corners >= 3
+}
+
 pub(crate) fn place_dot_on_board(board_x: i8, board_y: i8, board: &mut [[i8; BOARD_COLS]; BOARD_ROWS]) {
-    board[board_y as usize][board_x as usize] = 1;
+    board[board_y as usize][board_x as usize] = BLOCK_CELL;
+}
+
+/// Position of the center of a board cell, relative to its board (inverse of `get_object_position_in_board`)
+pub(crate) fn board_cell_position(board_x: i8, board_y: i8) -> (f32, f32) {
+    (
+        (board_x as f32 - 4.5) * DOT_SIZE,
+        (9.5 - board_y as f32) * DOT_SIZE,
+    )
+}
+
+/// Spawns the sprite for a settled cell on `board` that did not come from a piece (e.g. garbage)
+pub(crate) fn spawn_board_dot(commands: &mut Commands, board: Entity, board_x: i8, board_y: i8, color: Color) {
+    let (x, y) = board_cell_position(board_x, board_y);
+    commands
+        .spawn((
+            Sprite {
+                color,
+                custom_size: Some(Vec2::new(DOT_SIZE, DOT_SIZE)),
+                ..default()
+            },
+            Transform::from_xyz(x, y, 1.0),
+            BoardDot { board_x, board_y },
+        ))
+        .set_parent(board);
+}
+
+/// A garbage row: every cell filled except the hole column
+pub(crate) fn garbage_row(hole: usize) -> [i8; BOARD_COLS] {
+    let mut row = [GARBAGE_CELL; BOARD_COLS];
+    row[hole] = EMPTY_CELL;
+    row
+}
+
+pub(crate) fn count_garbage_cells(board: &[[i8; BOARD_COLS]; BOARD_ROWS]) -> usize {
+    board.iter().flatten().filter(|&&cell| cell == GARBAGE_CELL).count()
+}
+
+/// The cells of a board, as in `GameData::board_matrix`, for working out moves away from the ECS
+pub type Board = [[i8; BOARD_COLS]; BOARD_ROWS];
+
+/// Where a piece ends up: its rotation state and the board cell of its origin
+#[derive(Clone)]
+pub struct Placement {
+    pub block: tetromino::Block,
+    pub column: i8,
+    pub row: i8,
+}
+
+/// Whether the block fits with its origin at (`column`, `row`). With `allow_above` rows above the board count
+/// as empty, as for a piece dropped in from above; otherwise every cell has to be on the board.
+pub fn fits(board: &Board, block: &tetromino::Block, column: i8, row: i8, allow_above: bool) -> bool {
+    block.dots_by_state().iter().all(|dot| {
+        let (x, y) = (column + dot.x, row + dot.y);
+        (0..BOARD_COLS as i8).contains(&x)
+            && y < BOARD_ROWS as i8
+            && if y < 0 { allow_above } else { board[y as usize][x as usize] == EMPTY_CELL }
+    })
+}
+
+/// Every rotation and column `piece` can be dropped straight down into
+#[cfg(test)]
+pub(crate) fn placements(board: &Board, piece: &tetromino::Block) -> Vec<Placement> {
+    let mut found = Vec::new();
+    for state in [tetromino::State::Zero, tetromino::State::One, tetromino::State::Two, tetromino::State::Three] {
+        let mut block = piece.clone();
+        block.set_state(state);
+        for column in -3..BOARD_COLS as i8 {
+            let mut row = -4;
+            if !fits(board, &block, column, row, true) {
+                continue;
+            }
+            while fits(board, &block, column, row + 1, true) {
+                row += 1;
+            }
+            found.push(Placement {
+                block: block.clone(),
+                column,
+                row,
+            });
+        }
+    }
+    found
+}
+
+/// The board with the placement locked in, `None` if part of it would stick out of the top
+pub fn place(board: &Board, placement: &Placement) -> Option<Board> {
+    let mut board = *board;
+    for dot in placement.block.dots_by_state() {
+        let (x, y) = (placement.column + dot.x, placement.row + dot.y);
+        if y < 0 {
+            return None;
+        }
+        board[y as usize][x as usize] = BLOCK_CELL;
+    }
+    Some(board)
 }
 
 #[cfg(test)]
@@ -133,10 +268,64 @@ mod tests {
         assert!(!board_check_block_position(&board, -37.5, -237.5, &block));
     }
 
+    #[test]
+    fn cell_position_round_trips() {
+        for (board_x, board_y) in [(0, 0), (9, 19), (4, 9), (7, 3)] {
+            let (x, y) = board_cell_position(board_x, board_y);
+            assert_eq!(get_object_position_in_board(x, y), (board_x, board_y));
+        }
+    }
+
+    #[test]
+    fn garbage_collides_like_blocks() {
+        let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        board[19] = garbage_row(0);
+        let block = Block::new_o();
+        assert!(!board_check_block_position(&board, -37.5, -237.5, &block));
+        assert_eq!(count_garbage_cells(&board), BOARD_COLS - 1);
+    }
+
+    #[test]
+    fn landing_y_stops_on_the_stack() {
+        let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        let block = Block::new_o();
+        // O-piece dots sit on rows 0 and 1 below the block origin, so it rests with origin at row 18
+        assert_eq!(landing_y(&board, -37.5, 237.5, &block), -212.5);
+        board[19] = [1; BOARD_COLS];
+        assert_eq!(landing_y(&board, -37.5, 237.5, &block), -187.5);
+    }
+
+    #[test]
+    fn t_spin_needs_three_corners() {
+        let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        let mut block = Block::new_t();
+        block.set_state(crate::tetromino::State::Two);
+        // Pointing down: flat side on row 18, stem in row 19 (origin at column 3, row 17)
+        let (x, y) = (-37.5, -187.5);
+        board[19][3] = 1;
+        board[19][5] = 1;
+        assert!(!is_t_spin_position(&board, x, y, &block));
+        board[17][3] = 1;
+        assert!(is_t_spin_position(&board, x, y, &block));
+        assert!(!is_t_spin_position(&board, x, y, &Block::new_s()));
+    }
+
     #[test]
     fn place_dot_sets_cell() {
         let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
         place_dot_on_board(3, 5, &mut board);
         assert_eq!(board[5][3], 1);
     }
+
+    #[test]
+    fn placements_rest_on_the_floor() {
+        let board = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+        let found = placements(&board, &Block::new_o());
+        // The O fits in 9 column pairs in each of its 4 identical states
+        assert_eq!(found.len(), 36);
+        for placement in found {
+            let board = place(&board, &placement).expect("fits on an empty board");
+            assert_eq!(board[BOARD_ROWS - 1].iter().filter(|&&x| x != EMPTY_CELL).count(), 2);
+        }
+    }
 }
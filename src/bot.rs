@@ -0,0 +1,603 @@
+use bevy::prelude::{default, Commands, Component, DetectChangesMut, Entity, Parent, Query, Res, Transform, With};
+use bevy::time::{Time, Timer, TimerMode};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::board::{get_object_position_in_board, place, Board, Placement, EMPTY_CELL};
+use crate::common_component::{ActiveBlock, DropType, GameData, BOARD_COLS, BOARD_ROWS};
+use crate::game_mode::{CpuDifficulty, ModeSettings};
+use crate::line_clear::collapse_full_rows;
+use crate::move_gen::{path_rows, reachable_placements, Input, Reachable};
+use crate::player::{MainBoard, PieceInput};
+use crate::spawn_block_system::Randomizer7Bag;
+use crate::tbp::{choose, Snapshot, TbpBot};
+use crate::tetromino;
+
+/// Seconds between two of the bot's key presses
+const BOT_INPUT_SECS: f32 = 0.05;
+
+/// How much each board feature counts in the bot's evaluation of a placement. Landing height,
+/// eroded cells, transitions, holes and wells are Dellacherie's features with the El-Tetris weights;
+/// aggregate height and bumpiness nudge it towards a flat, low stack, and T-spins get a bonus.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Weights {
+    pub landing_height: f32,
+    pub eroded_cells: f32,
+    pub row_transitions: f32,
+    pub column_transitions: f32,
+    pub holes: f32,
+    pub wells: f32,
+    pub aggregate_height: f32,
+    pub bumpiness: f32,
+    /// Bonus for a placement that is a T-spin, which scores and attacks more
+    pub t_spin: f32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            landing_height: -4.500,
+            eroded_cells: 3.418,
+            row_transitions: -3.218,
+            column_transitions: -9.349,
+            holes: -7.899,
+            wells: -3.386,
+            aggregate_height: -0.1,
+            bumpiness: -0.2,
+            t_spin: 4.0,
+        }
+    }
+}
+
+/// How strong a bot plays: how fast it may lock pieces, how long it looks at a new piece before its first
+/// press, and how often it settles for a placement that is not its best
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BotTier {
+    pub pieces_per_second: f32,
+    pub reaction_secs: f32,
+    /// Chance of picking one of the other reachable placements instead of the best one
+    pub mistake_chance: f32,
+}
+
+impl BotTier {
+    /// The ladder of levels offered for Versus and for the 2 Players bot
+    pub(crate) fn preset(difficulty: CpuDifficulty) -> Self {
+        let (pieces_per_second, reaction_secs, mistake_chance) = match difficulty {
+            CpuDifficulty::Beginner => (0.5, 1.0, 0.35),
+            CpuDifficulty::Easy => (0.7, 0.6, 0.2),
+            CpuDifficulty::Medium => (1.1, 0.35, 0.08),
+            CpuDifficulty::Hard => (2.5, 0.15, 0.02),
+            CpuDifficulty::Expert => (4.0, 0.05, 0.0),
+        };
+        Self {
+            pieces_per_second,
+            reaction_secs,
+            mistake_chance,
+        }
+    }
+
+    /// Shortest time between two pieces
+    pub(crate) fn piece_secs(&self) -> f32 {
+        1.0 / self.pieces_per_second
+    }
+}
+
+/// Filled-or-not changes along every row, the walls counting as filled
+fn row_transitions(board: &Board) -> u32 {
+    board
+        .iter()
+        .map(|row| {
+            let mut filled = true;
+            let mut transitions = 0;
+            for &cell in row.iter() {
+                if (cell != EMPTY_CELL) != filled {
+                    transitions += 1;
+                    filled = !filled;
+                }
+            }
+            transitions + u32::from(!filled)
+        })
+        .sum()
+}
+
+/// Filled-or-not changes down every column, from empty above the board to the filled floor
+fn column_transitions(board: &Board) -> u32 {
+    (0..BOARD_COLS)
+        .map(|x| {
+            let mut filled = false;
+            let mut transitions = 0;
+            for row in board.iter() {
+                if (row[x] != EMPTY_CELL) != filled {
+                    transitions += 1;
+                    filled = !filled;
+                }
+            }
+            transitions + u32::from(!filled)
+        })
+        .sum()
+}
+
+/// Empty cells with a filled cell somewhere above them
+fn holes(board: &Board) -> u32 {
+    (0..BOARD_COLS)
+        .map(|x| {
+            let top = (0..BOARD_ROWS).find(|&y| board[y][x] != EMPTY_CELL).unwrap_or(BOARD_ROWS);
+            (top..BOARD_ROWS).filter(|&y| board[y][x] == EMPTY_CELL).count() as u32
+        })
+        .sum()
+}
+
+/// Cumulative well depth: a well cell is empty with both neighbours filled (walls count), and the
+/// n-th cell down a well adds n
+fn wells(board: &Board) -> u32 {
+    let filled = |x: i32, y: usize| x < 0 || x >= BOARD_COLS as i32 || board[y][x as usize] != EMPTY_CELL;
+    (0..BOARD_COLS as i32)
+        .map(|x| {
+            let mut total = 0;
+            let mut depth = 0;
+            for y in 0..BOARD_ROWS {
+                if !filled(x, y) && filled(x - 1, y) && filled(x + 1, y) {
+                    depth += 1;
+                    total += depth;
+                } else {
+                    depth = 0;
+                }
+            }
+            total
+        })
+        .sum()
+}
+
+fn column_heights(board: &Board) -> [i32; BOARD_COLS] {
+    let mut heights = [0; BOARD_COLS];
+    for (x, height) in heights.iter_mut().enumerate() {
+        if let Some(top) = (0..BOARD_ROWS).find(|&y| board[y][x] != EMPTY_CELL) {
+            *height = (BOARD_ROWS - top) as i32;
+        }
+    }
+    heights
+}
+
+/// Score of locking `placement` on `board` (higher is better); `None` if it would stick out of the top
+pub(crate) fn evaluate(board: &Board, placement: &Placement, weights: &Weights) -> Option<f32> {
+    let mut after = place(board, placement)?;
+    let dots = placement.block.dots_by_state();
+    let rows: Vec<usize> = dots.iter().map(|dot| (placement.row + dot.y) as usize).collect();
+    let full: Vec<usize> = (0..BOARD_ROWS).filter(|&y| after[y].iter().all(|&cell| cell != EMPTY_CELL)).collect();
+    let piece_cells_cleared = rows.iter().filter(|y| full.contains(y)).count();
+    let eroded = full.len() * piece_cells_cleared;
+    // Height of the middle of the piece, counted from the floor
+    let landing_height = BOARD_ROWS as f32 - rows.iter().sum::<usize>() as f32 / rows.len() as f32 - 0.5;
+
+    collapse_full_rows(&mut after);
+    let heights = column_heights(&after);
+    let bumpiness: i32 = heights.windows(2).map(|pair| (pair[0] - pair[1]).abs()).sum();
+    Some(
+        weights.landing_height * landing_height
+            + weights.eroded_cells * eroded as f32
+            + weights.row_transitions * row_transitions(&after) as f32
+            + weights.column_transitions * column_transitions(&after) as f32
+            + weights.holes * holes(&after) as f32
+            + weights.wells * wells(&after) as f32
+            + weights.aggregate_height * heights.iter().sum::<i32>() as f32
+            + weights.bumpiness * bumpiness as f32,
+    )
+}
+
+/// What the bot does with a piece: whether it holds first, and where the piece it plays goes and how
+#[derive(Clone)]
+pub struct Plan {
+    pub hold: bool,
+    pub target: Reachable,
+    /// Row of the piece after each input of the target's path, to know when a soft drop has gone far enough
+    pub rows: Vec<i8>,
+}
+
+/// The best placement `piece` can reach, with its score and path
+fn best(board: &Board, piece: &tetromino::Block, weights: &Weights) -> Option<(f32, Reachable)> {
+    reachable_placements(board, piece)
+        .into_iter()
+        .filter_map(|reachable| {
+            let spin_bonus = if reachable.t_spin { weights.t_spin } else { 0.0 };
+            Some((evaluate(board, &reachable.placement, weights)? + spin_bonus, reachable))
+        })
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+}
+
+/// Picks between playing `current` and holding it for `alternative` (the held piece, or the next one
+/// when nothing is held yet); `alternative` is `None` when holding is not possible
+pub(crate) fn plan(
+    board: &Board,
+    current: &tetromino::Block,
+    alternative: Option<&tetromino::Block>,
+    weights: &Weights,
+) -> Option<Plan> {
+    let play = best(board, current, weights);
+    let swap = alternative.and_then(|piece| Some((piece, best(board, piece, weights)?)));
+    let (hold, piece, reachable) = match (play, swap) {
+        (Some((play_score, _)), Some((piece, (hold_score, reachable)))) if hold_score > play_score => {
+            (true, piece, reachable)
+        }
+        (Some((_, reachable)), _) => (false, current, reachable),
+        (None, Some((piece, (_, reachable)))) => (true, piece, reachable),
+        (None, None) => return None,
+    };
+    Some(Plan {
+        hold,
+        rows: path_rows(board, piece, &reachable.path),
+        target: reachable,
+    })
+}
+
+/// A placement of `current` other than the best one, picked at random: the bot's mistake
+fn mistake(board: &Board, current: &tetromino::Block, weights: &Weights, rng: &mut impl Rng) -> Option<Plan> {
+    let best = best(board, current, weights)?.1.placement;
+    let same = |placement: &Placement| {
+        (placement.column, placement.row, placement.block.state()) == (best.column, best.row, best.block.state())
+    };
+    let others: Vec<Reachable> =
+        reachable_placements(board, current).into_iter().filter(|reachable| !same(&reachable.placement)).collect();
+    let target = others.choose(rng)?.clone();
+    Some(Plan {
+        hold: false,
+        rows: path_rows(board, current, &target.path),
+        target,
+    })
+}
+
+fn press(input: Input) -> PieceInput {
+    let mut press = PieceInput::default();
+    match input {
+        Input::Left => press.left_pressed = true,
+        Input::Right => press.right_pressed = true,
+        Input::RotateCw => press.rotate_cw = true,
+        Input::RotateCcw => press.rotate_ccw = true,
+        Input::SoftDrop => press.soft_drop = true,
+        Input::HardDrop => press.hard_drop = true,
+    }
+    press
+}
+
+/// The press that plays input `next` of the plan's path with the piece at `row`, moving `next` on;
+/// `fire` is false between presses. Soft drop is held until the piece is down to the row the path
+/// expects, so a tuck shifts in at the right height.
+fn follow(plan: &Plan, next: &mut usize, row: i8, fire: bool) -> PieceInput {
+    let path = &plan.target.path;
+    match path.get(*next) {
+        Some(Input::SoftDrop) => {
+            let run = path[*next..].iter().take_while(|&&input| input == Input::SoftDrop).count();
+            match plan.rows.get(*next + run - 1) {
+                Some(&target) if row < target => press(Input::SoftDrop),
+                _ => {
+                    *next += run;
+                    PieceInput::default()
+                }
+            }
+        }
+        Some(&input) if fire => {
+            *next += 1;
+            press(input)
+        }
+        // A path always ends in a hard drop; should the piece still be out, drop it
+        None if fire => press(Input::HardDrop),
+        _ => PieceInput::default(),
+    }
+}
+
+/// Plays a board by pressing its keys: the bot fills in the board's `PieceInput`, which the movement,
+/// rotation, hold and drop systems read the same way as a player's
+#[derive(Component)]
+pub(crate) struct Bot {
+    pub weights: Weights,
+    /// An external bot whose moves are played instead of the built-in evaluation's, when one is running
+    external: Option<TbpBot>,
+    /// Speed and mistakes when playing at a set level; `None` plays as fast and as well as it can
+    pub tier: Option<BotTier>,
+    input_timer: Timer,
+    /// Seconds left before the first press on a new piece
+    reaction_left: f32,
+    /// Seconds since the last hard drop
+    since_drop: f32,
+    /// The piece being played, what the bot wants to do with it, and the next input of its path
+    plan: Option<(Entity, Plan, usize)>,
+    /// Hold was pressed: the piece it brings in carries on with the plan
+    swapping: bool,
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Self {
+            weights: Weights::default(),
+            external: None,
+            tier: None,
+            input_timer: Timer::from_seconds(BOT_INPUT_SECS, TimerMode::Repeating),
+            reaction_left: 0.0,
+            since_drop: f32::MAX,
+            plan: None,
+            swapping: false,
+        }
+    }
+}
+
+impl Bot {
+    /// The built-in bot, or the external one when a command for it is set and it starts
+    pub(crate) fn for_settings(settings: &ModeSettings) -> Self {
+        let external = settings.external_bot.as_deref().and_then(|command| {
+            TbpBot::spawn(command)
+                .inspect_err(|err| bevy::log::warn!("cannot start the bot `{command}`: {err}"))
+                .ok()
+        });
+        Self { external, ..default() }
+    }
+
+    pub(crate) fn with_tier(self, tier: BotTier) -> Self {
+        Self {
+            tier: Some(tier),
+            ..self
+        }
+    }
+
+    /// The built-in evaluation's plan, or now and then a mistake when playing at a set level
+    fn built_in(
+        &self,
+        board: &Board,
+        current: &tetromino::Block,
+        alternative: Option<&tetromino::Block>,
+    ) -> Option<Plan> {
+        let mut rng = rand::thread_rng();
+        if self.tier.is_some_and(|tier| rng.gen::<f32>() < tier.mistake_chance) {
+            if let Some(plan) = mistake(board, current, &self.weights, &mut rng) {
+                return Some(plan);
+            }
+        }
+        plan(board, current, alternative, &self.weights)
+    }
+
+    /// What to do with `current`: `None` while an external bot is still thinking, then the plan, if any.
+    /// Should the external bot stop answering, the built-in evaluation takes over.
+    fn think(
+        &mut self,
+        game_data: &GameData,
+        queue: &Randomizer7Bag,
+        current: &tetromino::Block,
+        alternative: Option<&tetromino::Block>,
+    ) -> Option<Option<Plan>> {
+        let board = &game_data.board_matrix;
+        let Some(external) = &mut self.external else {
+            return Some(self.built_in(board, current, alternative));
+        };
+        let thought = external.suggest(&Snapshot::new(game_data, current, queue)).and_then(|moves| {
+            let Some(moves) = moves else {
+                return Ok(None);
+            };
+            match choose(&moves, board, current, alternative) {
+                Some((played, plan)) => external.play(&played, &plan.target.placement).map(|_| Some(Some(plan))),
+                None => {
+                    external.lost_track();
+                    Ok(Some(None))
+                }
+            }
+        });
+        thought.unwrap_or_else(|err| {
+            bevy::log::warn!("the external bot stopped playing: {err}");
+            self.external = None;
+            Some(self.built_in(board, current, alternative))
+        })
+    }
+}
+
+/// Presses the bots' keys. Runs after the keyboard is read, so the bot overrides it on its board.
+pub(crate) fn bot_input_system(
+    time: Res<Time>,
+    mut boards: Query<(Entity, &mut Bot, &GameData, &Randomizer7Bag, &mut PieceInput)>,
+    active_blocks: Query<(Entity, &Parent, &tetromino::Block, &Transform), With<ActiveBlock>>,
+) {
+    for (board, mut bot, game_data, queue, mut input) in boards.iter_mut() {
+        let bot = &mut *bot;
+        let fire = bot.input_timer.tick(time.delta()).just_finished();
+        bot.since_drop += time.delta_secs();
+        let piece = active_blocks.iter().find(|(_, parent, ..)| parent.get() == board);
+        let mut press = match piece {
+            Some((entity, _, block, transform)) if game_data.drop_type != DropType::Hard => {
+                let mut thinking = false;
+                match bot.plan.take() {
+                    Some((planned, plan, next)) if planned == entity || bot.swapping => {
+                        if planned != entity {
+                            bot.swapping = false;
+                        }
+                        bot.plan = Some((entity, plan, next));
+                    }
+                    _ => {
+                        let alternative = match &game_data.held_block {
+                            _ if game_data.hold_used => None,
+                            Some(held) => Some(held.clone()),
+                            None => queue.peek(1).first().map(|&next| next.clone()),
+                        };
+                        match bot.think(game_data, queue, block, alternative.as_ref()) {
+                            Some(plan) => {
+                                bot.plan = plan.map(|plan| (entity, plan, 0));
+                                bot.reaction_left = bot.tier.map_or(0.0, |tier| tier.reaction_secs);
+                            }
+                            None => thinking = true,
+                        }
+                    }
+                }
+                let (_, row) = get_object_position_in_board(transform.translation.x, transform.translation.y);
+                match &mut bot.plan {
+                    _ if bot.reaction_left > 0.0 => {
+                        bot.reaction_left -= time.delta_secs();
+                        PieceInput::default()
+                    }
+                    Some((_, plan, _)) if plan.hold => {
+                        if fire {
+                            plan.hold = false;
+                            bot.swapping = true;
+                            PieceInput {
+                                hold: true,
+                                ..Default::default()
+                            }
+                        } else {
+                            PieceInput::default()
+                        }
+                    }
+                    Some((_, plan, next)) => follow(plan, next, row, fire),
+                    None if fire && !thinking => press(Input::HardDrop),
+                    None => PieceInput::default(),
+                }
+            }
+            _ => PieceInput::default(),
+        };
+        if press.hard_drop {
+            // Past the end of its path the bot keeps pressing hard drop, so holding one back only delays it
+            if bot.tier.is_some_and(|tier| bot.since_drop < tier.piece_secs()) {
+                press = PieceInput::default();
+            } else {
+                bot.since_drop = 0.0;
+            }
+        }
+        input.set_if_neq(press);
+    }
+}
+
+/// Hands the main board to the bot while the demo is switched on (F3 on the start screen)
+pub(crate) fn demo_toggle_system(
+    mut commands: Commands,
+    settings: Res<ModeSettings>,
+    main_board: Query<(Entity, Option<&Bot>), With<MainBoard>>,
+) {
+    let Ok((board, bot)) = main_board.get_single() else {
+        return;
+    };
+    match (settings.demo, bot.is_some()) {
+        (true, false) => {
+            commands.entity(board).insert(Bot::for_settings(&settings));
+        }
+        (false, true) => {
+            commands.entity(board).remove::<Bot>();
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::BLOCK_CELL;
+    use crate::spawn_block_system::can_spawn;
+    use crate::tetromino::Block;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn board_from(rows: &[&str]) -> Board {
+        let mut board = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+        for (i, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == '#' {
+                    board[BOARD_ROWS - rows.len() + i][x] = BLOCK_CELL;
+                }
+            }
+        }
+        board
+    }
+
+    #[test]
+    fn board_features() {
+        let board = board_from(&["#.........", "#.#.#####.", "##########"]);
+        assert_eq!(holes(&board), 0);
+        assert_eq!(holes(&board_from(&["#.........", "..........", "#........."])), 1);
+        // Wells one deep in columns 1 and 3, and in column 9 against the wall
+        assert_eq!(wells(&board), 3);
+        // The n-th cell down a well adds n
+        assert_eq!(wells(&board_from(&["#.########", "#.########"])), 3);
+        assert_eq!(column_heights(&board)[0], 3);
+        let empty = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+        assert_eq!(row_transitions(&empty), 2 * BOARD_ROWS as u32);
+        assert_eq!(column_transitions(&empty), BOARD_COLS as u32);
+    }
+
+    #[test]
+    fn the_bot_fills_a_well_with_an_i_piece_it_holds() {
+        let board = board_from(&["#########.", "#########.", "#########.", "#########."]);
+        let plan = plan(&board, &Block::new_s(), Some(&Block::new_i()), &Weights::default()).expect("a placement");
+        assert!(plan.hold);
+        let mut after = place(&board, &plan.target.placement).expect("fits");
+        assert_eq!(collapse_full_rows(&mut after), 4);
+    }
+
+    #[test]
+    fn the_ladder_gets_faster_and_steadier() {
+        let tiers = CpuDifficulty::ALL.map(BotTier::preset);
+        for pair in tiers.windows(2) {
+            assert!(pair[0].pieces_per_second < pair[1].pieces_per_second);
+            assert!(pair[0].reaction_secs > pair[1].reaction_secs);
+            assert!(pair[0].mistake_chance > pair[1].mistake_chance || pair[1].mistake_chance == 0.0);
+        }
+        assert_eq!(BotTier::preset(CpuDifficulty::Expert).mistake_chance, 0.0);
+    }
+
+    #[test]
+    fn a_mistake_is_never_the_best_placement() {
+        let board = board_from(&["#########.", "#########.", "#########.", "#########."]);
+        let weights = Weights::default();
+        let best = plan(&board, &Block::new_i(), None, &weights).expect("a placement").target.placement;
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..20 {
+            let mistake = mistake(&board, &Block::new_i(), &weights, &mut rng).expect("another placement");
+            let placement = mistake.target.placement;
+            assert!(!mistake.hold);
+            assert_ne!(
+                (placement.column, placement.row, *placement.block.state() as u8),
+                (best.column, best.row, *best.block.state() as u8)
+            );
+        }
+    }
+
+    #[test]
+    fn presses_follow_the_path_holding_soft_drop_down_to_the_row() {
+        let plan = Plan {
+            hold: false,
+            target: Reachable {
+                placement: Placement {
+                    block: Block::new_t(),
+                    column: 2,
+                    row: 18,
+                },
+                path: vec![Input::Left, Input::SoftDrop, Input::SoftDrop, Input::RotateCw, Input::HardDrop],
+                t_spin: false,
+            },
+            rows: vec![0, 1, 2, 2, 18],
+        };
+        let mut next = 0;
+        assert_eq!(follow(&plan, &mut next, 0, false), PieceInput::default());
+        assert!(follow(&plan, &mut next, 0, true).left_pressed);
+        assert!(follow(&plan, &mut next, 0, false).soft_drop);
+        assert!(follow(&plan, &mut next, 1, true).soft_drop);
+        // Down at the row the path expects: let go, then carry on with the next press
+        assert_eq!(follow(&plan, &mut next, 2, false), PieceInput::default());
+        assert!(follow(&plan, &mut next, 2, true).rotate_cw);
+        assert!(follow(&plan, &mut next, 2, true).hard_drop);
+        assert!(follow(&plan, &mut next, 18, true).hard_drop);
+    }
+
+    #[test]
+    fn the_bot_keeps_playing_without_topping_out() {
+        let mut board = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+        let mut queue = Randomizer7Bag::seeded(7);
+        let mut held: Option<Block> = None;
+        let mut lines = 0;
+        let mut current = queue.pop_next().unwrap();
+        for _ in 0..500 {
+            assert!(can_spawn(&board, &current), "topped out after {lines} lines");
+            let alternative = held.clone().or_else(|| queue.peek(1).first().map(|&next| next.clone()));
+            let plan = plan(&board, &current, alternative.as_ref(), &Weights::default()).expect("a placement");
+            if plan.hold && held.replace(current.clone()).is_none() {
+                // Holding into an empty hold plays the next piece
+                queue.pop_next();
+            }
+            board = place(&board, &plan.target.placement).expect("fits");
+            lines += collapse_full_rows(&mut board);
+            current = queue.pop_next().unwrap();
+        }
+        assert!(lines >= 150, "cleared only {lines} lines in 500 pieces");
+    }
+}
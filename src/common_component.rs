@@ -1,8 +1,13 @@
 use bevy::{
-    prelude::{Component, Resource, States},
-    time::{Timer, TimerMode},
+    prelude::{Component, States},
+    time::{Stopwatch, Timer, TimerMode},
 };
+use std::time::Duration;
 
+use crate::attack::AttackState;
+use crate::drop::gravity_g;
+use crate::finesse::{FinesseCheck, LockedPiece};
+use crate::game_mode::GameMode;
 use crate::tetromino;
 
 pub const DOT_SIZE: f32 = 25.0;
@@ -10,53 +15,142 @@ pub const BOARD_COLS: usize = 10;
 pub const BOARD_ROWS: usize = 20;
 pub const SPAWN_X: f32 = -DOT_SIZE * 1.5;
 pub const MAX_LOCK_RESETS: u32 = 15;
-pub const GRAVITY_FLOOR: f32 = 0.05;
+/// Gravity is measured in G (rows per frame at this rate), whatever the real frame rate is
+pub const FRAME_RATE: f32 = 60.0;
+/// 20G: a piece falls through the whole board in a single frame
+pub const MAX_GRAVITY_G: f32 = 20.0;
+/// Soft drop moves one row every 0.05s, or faster when gravity already does
+pub const SOFT_DROP_G: f32 = 1.0 / 3.0;
 
-const TIMER_DROP_SECS: f32 = 1.0;
 const TIMER_HARD_DROP_SECS: f32 = 0.01;
-const TIMER_SOFT_DROP_SECS: f32 = 0.05;
-const TIMER_LOCK_DELAY_SECS: f32 = 0.5;
-
-#[derive(Resource)]
+pub const TIMER_LOCK_DELAY_SECS: f32 = 0.5;
+const TIMER_DAS_SECS: f32 = 10.0 / FRAME_RATE;
+const TIMER_ARR_SECS: f32 = 2.0 / FRAME_RATE;
+const TIMER_GARBAGE_RISE_SECS: f32 = 8.0;
+pub const LINE_CLEAR_DELAY_SECS: f32 = 0.3;
+pub const ENTRY_DELAY_SECS: f32 = 0.1;
+
+/// Everything one board plays with: its cells, timers, score and garbage. Lives on the board
+/// entity (see `player::PlayerBoard`), so a game can have several boards.
+#[derive(Component, Clone)]
 pub struct GameData {
     pub board_matrix: [[i8; BOARD_COLS]; BOARD_ROWS],
-    pub drop_timer: Timer,
+    /// How the falling piece is dropping (gravity, soft drop or hard drop)
+    pub drop_type: DropType,
+    /// Current gravity in G; fractions accumulate in `gravity_progress` until a whole row is due
+    pub gravity: f32,
+    pub gravity_progress: f32,
     pub hard_drop_timer: Timer,
-    pub soft_drop_timer: Timer,
     pub soft_drop_cells: u32,
     pub score: u32,
     pub lines_cleared: u32,
+    pub pieces_placed: u32,
     pub hard_drop_start_y: Option<f32>,
     pub held_block: Option<tetromino::Block>,
     pub hold_used: bool,
     pub level: u32,
+    /// Level the game started at (Marathon level select)
+    pub start_level: u32,
+    /// Line clear units collected toward the next level (variable goal)
+    pub goal_units: u32,
     pub lock_delay_timer: Timer,
     pub lock_delay_active: bool,
     pub lock_move_count: u32,
+    /// Horizontal auto-shift: -1 left, 1 right, 0 when no direction is held
+    pub shift_direction: i8,
+    pub das_timer: Timer,
+    pub arr_timer: Timer,
+    /// Rows waiting to be removed once the line clear delay finishes
+    pub clearing_rows: Vec<usize>,
+    pub line_clear_timer: Timer,
+    /// ARE: delay between a piece locking (or a line clear finishing) and the next spawn
+    pub entry_delay_timer: Timer,
+    pub entry_delay_active: bool,
+    /// Time spent playing, excluding pauses
+    pub play_time: Stopwatch,
+    /// Play time at every 10 cleared lines (Sprint)
+    pub splits: Vec<Duration>,
+    pub outcome: GameOutcome,
+    /// Set once a mode's starting board (e.g. Dig garbage) has been laid out
+    pub initial_board_placed: bool,
+    /// Survival: time until the next garbage row rises
+    pub garbage_rise_timer: Timer,
+    /// Whether the active piece's last successful move was a rotation (needed for T-spins)
+    pub last_move_rotation: bool,
+    /// Whether the piece that locked last was a T-spin
+    pub t_spin: bool,
+    /// The line clear made by the last locked piece, until a mode consumes it
+    pub last_clear: Option<LineClear>,
+    /// Set when a piece locks, until the attack system has settled it
+    pub piece_locked: bool,
+    pub attack: AttackState,
+    /// Left/right presses and rotations spent on the active piece
+    pub piece_inputs: u32,
+    /// The piece that locked this frame, until the finesse check has judged it
+    pub last_lock: Option<LockedPiece>,
+    pub last_finesse: Option<FinesseCheck>,
+    pub finesse_faults: u32,
 }
 
 impl Default for GameData {
     fn default() -> Self {
         Self {
             board_matrix: [[0; BOARD_COLS]; BOARD_ROWS],
-            drop_timer: Timer::from_seconds(TIMER_DROP_SECS, TimerMode::Repeating),
+            drop_type: DropType::default(),
+            gravity: gravity_g(1),
+            gravity_progress: 0.0,
             hard_drop_timer: Timer::from_seconds(TIMER_HARD_DROP_SECS, TimerMode::Repeating),
-            soft_drop_timer: Timer::from_seconds(TIMER_SOFT_DROP_SECS, TimerMode::Repeating),
             soft_drop_cells: 0,
             score: 0,
             lines_cleared: 0,
+            pieces_placed: 0,
             hard_drop_start_y: None,
             held_block: None,
             hold_used: false,
             level: 1,
+            start_level: 1,
+            goal_units: 0,
             lock_delay_timer: Timer::from_seconds(TIMER_LOCK_DELAY_SECS, TimerMode::Once),
             lock_delay_active: false,
             lock_move_count: 0,
+            shift_direction: 0,
+            das_timer: Timer::from_seconds(TIMER_DAS_SECS, TimerMode::Once),
+            arr_timer: Timer::from_seconds(TIMER_ARR_SECS, TimerMode::Repeating),
+            clearing_rows: Vec::new(),
+            line_clear_timer: Timer::from_seconds(LINE_CLEAR_DELAY_SECS, TimerMode::Once),
+            entry_delay_timer: Timer::from_seconds(ENTRY_DELAY_SECS, TimerMode::Once),
+            entry_delay_active: false,
+            play_time: Stopwatch::new(),
+            splits: Vec::new(),
+            outcome: GameOutcome::default(),
+            initial_board_placed: false,
+            garbage_rise_timer: Timer::from_seconds(TIMER_GARBAGE_RISE_SECS, TimerMode::Repeating),
+            last_move_rotation: false,
+            t_spin: false,
+            last_clear: None,
+            piece_locked: false,
+            attack: AttackState::default(),
+            piece_inputs: 0,
+            last_lock: None,
+            last_finesse: None,
+            finesse_faults: 0,
         }
     }
 }
 
-#[derive(States, Debug, Clone, Eq, PartialEq, Hash, Default)]
+impl GameData {
+    /// Fresh game data for a game starting at `level`, with gravity to match
+    pub fn with_start_level(level: u32) -> Self {
+        Self {
+            level,
+            start_level: level,
+            gravity: gravity_g(level),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
 pub enum DropType {
     #[default]
     Normal,
@@ -71,11 +165,52 @@ pub enum GameState {
     Playing,
     Paused,
     GameOver,
+    /// Following someone else's game (Watch mode)
+    Watching,
+}
+
+/// How a game ended
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+pub enum GameOutcome {
+    /// The game has not ended for this board (yet)
+    #[default]
+    Playing,
+    TopOut,
+    /// The mode's goal was reached (e.g. 40 lines in Sprint)
+    Cleared,
+    /// The time limit ran out (Ultra)
+    TimeUp,
+    /// The pieces ran out before the objective was met (Puzzle)
+    Failed,
+    /// The opponent topped out first (Versus, 2 Players)
+    Won,
+}
+
+/// What a single lock cleared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LineClear {
+    pub lines: u32,
+    pub t_spin: bool,
+    /// Nothing was left on the board afterwards
+    pub perfect_clear: bool,
 }
 
 #[derive(Component)]
 pub struct StartScreenOverlay;
 
+#[derive(Component)]
+pub struct ModeMenuEntry(pub GameMode);
+
+#[derive(Component)]
+pub struct ModeDescriptionText;
+
+#[derive(Component)]
+pub struct ModeOptionText;
+
+/// Start screen line with the F-key toggles (spectators, demo)
+#[derive(Component)]
+pub struct TogglesText;
+
 #[derive(Component)]
 pub struct ActiveBlock;
 
@@ -100,6 +235,18 @@ pub struct HoldDot;
 #[derive(Component)]
 pub struct LevelText;
 
+#[derive(Component)]
+pub struct TimerText;
+
+#[derive(Component)]
+pub struct SplitsText;
+
+#[derive(Component)]
+pub struct FinesseText;
+
+#[derive(Component)]
+pub struct GarbageMeter;
+
 #[derive(Component)]
 pub struct GhostDot;
 
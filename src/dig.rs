@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use bevy::prelude::{Commands, Entity, Has, NextState, Query, Res, ResMut, Resource};
+use rand::Rng;
+
+use crate::board::{count_garbage_cells, garbage_row, spawn_board_dot, GARBAGE_CELL, GARBAGE_COLOR};
+use crate::bot::Bot;
+use crate::common_component::{GameData, GameOutcome, GameState, BOARD_COLS, BOARD_ROWS};
+use crate::game_mode::{format_delta, format_time, ModeSettings};
+use crate::records::Records;
+
+/// Best Dig time for the played row count, taken before the last race was recorded
+#[derive(Resource, Default)]
+pub(crate) struct DigResult {
+    pub time: Duration,
+    pub previous_best: Option<Duration>,
+}
+
+/// Hole column for each of `count` garbage rows. Consecutive rows never share a hole,
+/// so every row has to be dug out on its own
+pub(crate) fn random_garbage_holes(count: usize, rng: &mut impl Rng) -> Vec<usize> {
+    let mut holes: Vec<usize> = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut hole = rng.gen_range(0..BOARD_COLS);
+        if holes.last() == Some(&hole) {
+            hole = (hole + rng.gen_range(1..BOARD_COLS)) % BOARD_COLS;
+        }
+        holes.push(hole);
+    }
+    holes
+}
+
+/// Fills the bottom of the board with the configured number of garbage rows at the start of a race
+pub(crate) fn dig_setup_system(
+    mut commands: Commands,
+    mut boards: Query<(Entity, &mut GameData)>,
+    settings: Res<ModeSettings>,
+) {
+    for (board, mut game_data) in boards.iter_mut() {
+        if game_data.initial_board_placed {
+            continue;
+        }
+        game_data.initial_board_placed = true;
+
+        let count = (settings.dig_rows as usize).min(BOARD_ROWS - 2);
+        let holes = random_garbage_holes(count, &mut game_data.attack.holes);
+        for (i, hole) in holes.into_iter().enumerate() {
+            let board_y = BOARD_ROWS - count + i;
+            game_data.board_matrix[board_y] = garbage_row(hole);
+            for board_x in 0..BOARD_COLS {
+                if game_data.board_matrix[board_y][board_x] == GARBAGE_CELL {
+                    spawn_board_dot(&mut commands, board, board_x as i8, board_y as i8, GARBAGE_COLOR);
+                }
+            }
+        }
+    }
+}
+
+/// Stops the clock once the last garbage cell has been cleared; a bot's time is not recorded
+pub(crate) fn dig_progress_system(
+    mut boards: Query<(&mut GameData, Has<Bot>)>,
+    settings: Res<ModeSettings>,
+    mut records: ResMut<Records>,
+    mut result: ResMut<DigResult>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (mut game_data, bot) in boards.iter_mut() {
+        if !game_data.initial_board_placed || count_garbage_cells(&game_data.board_matrix) > 0 {
+            continue;
+        }
+
+        let time = game_data.play_time.elapsed();
+        game_data.outcome = GameOutcome::Cleared;
+        *result = DigResult {
+            time,
+            previous_best: if bot {
+                records.dig_best.get(&settings.dig_rows).copied()
+            } else {
+                records.submit_dig(settings.dig_rows, time)
+            },
+        };
+        next_state.set(GameState::GameOver);
+    }
+}
+
+/// Number of rows that still contain garbage
+pub(crate) fn garbage_rows_left(board: &[[i8; BOARD_COLS]; BOARD_ROWS]) -> usize {
+    board.iter().filter(|row| row.contains(&GARBAGE_CELL)).count()
+}
+
+pub(crate) fn dig_result_lines(result: &DigResult, pieces_placed: u32) -> Vec<String> {
+    let mut lines = vec![
+        format!("Time: {}", format_time(result.time)),
+        format!("Pieces: {pieces_placed}"),
+    ];
+    match result.previous_best {
+        Some(best) if result.time >= best => lines.push(format!(
+            "Personal best: {} ({})",
+            format_time(best),
+            format_delta(result.time, best)
+        )),
+        Some(best) => lines.push(format!("New personal best! ({})", format_delta(result.time, best))),
+        None => lines.push("New personal best!".to_string()),
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn holes_never_repeat_on_adjacent_rows() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let holes = random_garbage_holes(200, &mut rng);
+        assert_eq!(holes.len(), 200);
+        assert!(holes.iter().all(|&hole| hole < BOARD_COLS));
+        assert!(holes.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn garbage_rows_left_counts_rows_not_cells() {
+        let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        board[19] = garbage_row(3);
+        board[18] = garbage_row(5);
+        board[17][0] = 1;
+        assert_eq!(garbage_rows_left(&board), 2);
+    }
+}
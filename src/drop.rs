@@ -1,152 +1,189 @@
 use bevy::prelude::{
-    BuildChildrenTransformExt, Children, Commands, Entity, GlobalTransform, KeyCode, Query, Res, ResMut, State, Transform,
-    With,
+    BuildChildren, Children, Commands, DespawnRecursiveExt, Entity, Parent, Query, Res, Transform, With, Without,
 };
-use bevy::input::ButtonInput;
-use bevy::prelude::NextState;
 use bevy::time::Time;
 
-use crate::board::{board_check_block_position, get_object_position_in_board, place_dot_on_board, BoardDot};
-use crate::common_component::{ActiveBlock, ActiveDot, DropType, GameData, DOT_SIZE, GRAVITY_FLOOR};
+use crate::board::{
+    board_cell_position, board_check_block_position, get_object_position_in_board, is_t_spin_position,
+    place_dot_on_board, BoardDot,
+};
+use crate::common_component::{
+    ActiveBlock, ActiveDot, DropType, GameData, BOARD_ROWS, DOT_SIZE, FRAME_RATE, MAX_GRAVITY_G, SOFT_DROP_G,
+};
+use crate::finesse::LockedPiece;
+use crate::player::PieceInput;
 use crate::tetromino;
 
 pub(crate) fn gravity_seconds(level: u32) -> f32 {
     let l = level as f32;
     let base = (0.8 - ((l - 1.0) * 0.007)).max(0.0);
-    base.powf(l - 1.0).max(GRAVITY_FLOOR)
+    base.powf(l - 1.0)
+}
+
+/// Guideline gravity for `level` in G (rows per frame), reaching 20G around level 19
+pub(crate) fn gravity_g(level: u32) -> f32 {
+    (1.0 / (gravity_seconds(level) * FRAME_RATE)).min(MAX_GRAVITY_G)
 }
 
+/// The piece's dots, positioned relative to it
+type ActiveDotQuery<'w, 's> = Query<'w, 's, &'static Transform, (With<ActiveDot>, Without<ActiveBlock>)>;
+
 pub(crate) fn block_drop_type_system(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut state: ResMut<NextState<DropType>>,
-    old_state: Res<State<DropType>>,
-    mut game_data: ResMut<GameData>,
-    query: Query<&Transform, With<ActiveBlock>>,
+    mut boards: Query<(Entity, &mut GameData, &PieceInput)>,
+    active_blocks: Query<(&Parent, &Transform), With<ActiveBlock>>,
 ) {
-    if old_state.get() == &DropType::Hard {
-        return;
-    }
-    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
-        state.set(DropType::Hard);
-        game_data.hard_drop_timer.reset();
+    for (board, mut game_data, input) in boards.iter_mut() {
+        if game_data.drop_type == DropType::Hard {
+            continue;
+        }
+        if input.hard_drop {
+            game_data.drop_type = DropType::Hard;
+            game_data.hard_drop_timer.reset();
 
-        // Capture starting y position for hard drop scoring
-        if let Ok(transform) = query.get_single() {
-            game_data.hard_drop_start_y = Some(transform.translation.y);
+            // Capture starting y position for hard drop scoring
+            if let Some((_, transform)) = active_blocks.iter().find(|(parent, _)| parent.get() == board) {
+                game_data.hard_drop_start_y = Some(transform.translation.y);
+            }
+            continue;
+        }
+        if input.soft_drop && game_data.drop_type != DropType::Soft {
+            game_data.drop_type = DropType::Soft;
+        }
+        if !input.soft_drop && game_data.drop_type == DropType::Soft {
+            game_data.drop_type = DropType::Normal;
+            game_data.score += game_data.soft_drop_cells;
+            game_data.soft_drop_cells = 0;
         }
-    }
-    if keyboard_input.pressed(KeyCode::ArrowDown) && old_state.get() != &DropType::Soft {
-        state.set(DropType::Soft);
-        game_data.soft_drop_timer.reset();
-    }
-    if keyboard_input.just_released(KeyCode::ArrowDown) && old_state.get() == &DropType::Soft {
-        state.set(DropType::Normal);
-        game_data.score += game_data.soft_drop_cells;
-        game_data.soft_drop_cells = 0;
     }
 }
 
 pub(crate) fn block_drop_system(
     mut commands: Commands,
-    mut query: Query<(Entity, &Children, &tetromino::Block, &mut Transform), With<ActiveBlock>>,
-    children_query: Query<&GlobalTransform, With<ActiveDot>>,
+    mut query: Query<(Entity, &Parent, &Children, &tetromino::Block, &mut Transform), With<ActiveBlock>>,
+    children_query: ActiveDotQuery,
     time: Res<Time>,
-    state: Res<State<DropType>>,
-    mut game_data: ResMut<GameData>,
+    mut boards: Query<&mut GameData>,
 ) {
-    if query.is_empty() {
-        return;
-    }
+    for (entity, parent, children, block, mut transform) in query.iter_mut() {
+        let Ok(mut game_data) = boards.get_mut(parent.get()) else {
+            continue;
+        };
+        let game_data = &mut *game_data;
+        let state = game_data.drop_type;
 
-    // If lock delay is active, tick it every frame (independent of drop timer)
-    if game_data.lock_delay_active && state.get() != &DropType::Hard {
-        // Check if the piece can still drop (rotation/movement may have opened space below)
-        let (_entity, _children, block, transform) = query.single();
-        let can_drop = board_check_block_position(
-            &game_data.board_matrix,
-            transform.translation.x,
-            transform.translation.y - DOT_SIZE,
-            block,
-        );
-        if can_drop {
-            // Piece is no longer on the ground — cancel lock delay, resume normal drop
-            game_data.lock_delay_active = false;
-            game_data.lock_move_count = 0;
-        } else {
-            game_data.lock_delay_timer.tick(time.delta());
-            if game_data.lock_delay_timer.finished() {
-                let (entity, children, _block, transform) = query.single_mut();
-                place_block_on_board(
-                    &mut commands,
-                    &mut game_data,
-                    children_query,
-                    entity,
-                    children,
-                    &transform,
-                );
+        // If lock delay is active, tick it every frame (independent of drop timer)
+        if game_data.lock_delay_active && state != DropType::Hard {
+            // Check if the piece can still drop (rotation/movement may have opened space below)
+            let can_drop = board_check_block_position(
+                &game_data.board_matrix,
+                transform.translation.x,
+                transform.translation.y - DOT_SIZE,
+                block,
+            );
+            if can_drop {
+                // Piece is no longer on the ground — cancel lock delay, resume normal drop
+                game_data.lock_delay_active = false;
+                game_data.lock_move_count = 0;
+            } else {
+                game_data.lock_delay_timer.tick(time.delta());
+                if game_data.lock_delay_timer.finished() {
+                    place_block_on_board(
+                        &mut commands,
+                        parent.get(),
+                        game_data,
+                        &children_query,
+                        (entity, children, block, &transform),
+                    );
+                }
+                continue;
             }
-            return;
         }
-    }
 
-    // Normal drop timer gating
-    if state.get() == &DropType::Hard {
-        let finished = game_data.hard_drop_timer.tick(time.delta()).finished();
-        if !finished {
-            return;
+        // Hard drop animates one row per tick; gravity and soft drop may move several rows a frame
+        let rows = if state == DropType::Hard {
+            let finished = game_data.hard_drop_timer.tick(time.delta()).finished();
+            if !finished {
+                continue;
+            }
+            1
+        } else {
+            let gravity = match state {
+                DropType::Soft => game_data.gravity.max(SOFT_DROP_G),
+                _ => game_data.gravity,
+            };
+            game_data.gravity_progress += gravity * time.delta_secs() * FRAME_RATE;
+            let rows = gravity_rows(&mut game_data.gravity_progress);
+            if rows == 0 {
+                continue;
+            }
+            rows
+        };
+
+        let mut landed = false;
+        for _ in 0..rows {
+            if !board_check_block_position(
+                &game_data.board_matrix,
+                transform.translation.x,
+                transform.translation.y - DOT_SIZE,
+                block,
+            ) {
+                landed = true;
+                break;
+            }
+            transform.translation.y -= DOT_SIZE;
+            game_data.last_move_rotation = false;
+            if state == DropType::Soft {
+                game_data.soft_drop_cells += 1;
+            }
+            game_data.lock_delay_active = false;
         }
-    } else if state.get() == &DropType::Soft {
-        let finished = game_data.soft_drop_timer.tick(time.delta()).finished();
-        if !finished {
-            return;
+
+        if !landed {
+            continue;
         }
-    } else {
-        let finished = game_data.drop_timer.tick(time.delta()).finished();
-        if !finished {
-            return;
+        game_data.gravity_progress = 0.0;
+        if state == DropType::Hard {
+            place_block_on_board(
+                &mut commands,
+                parent.get(),
+                game_data,
+                &children_query,
+                (entity, children, block, &transform),
+            );
+        } else {
+            // Start lock delay
+            game_data.lock_delay_active = true;
+            game_data.lock_delay_timer.reset();
+            game_data.lock_move_count = 0;
         }
     }
+}
 
-    let (entity, children, block, mut transform) = query.single_mut();
-
-    let can_drop = board_check_block_position(
-        &game_data.board_matrix,
-        transform.translation.x,
-        transform.translation.y - DOT_SIZE,
-        block,
-    );
-
-    if can_drop {
-        transform.translation.y -= DOT_SIZE;
-        if state.get() == &DropType::Soft {
-            game_data.soft_drop_cells += 1;
-        }
-        game_data.lock_delay_active = false;
-    } else if state.get() == &DropType::Hard {
-        place_block_on_board(
-            &mut commands,
-            &mut game_data,
-            children_query,
-            entity,
-            children,
-            &transform,
-        );
-    } else {
-        // Start lock delay
-        game_data.lock_delay_active = true;
-        game_data.lock_delay_timer.reset();
-        game_data.lock_move_count = 0;
-    }
+/// Takes the whole rows out of the accumulated gravity, leaving the fraction for later frames
+fn gravity_rows(progress: &mut f32) -> u32 {
+    let rows = progress.floor();
+    *progress -= rows;
+    (rows as u32).min(BOARD_ROWS as u32)
 }
 
+/// Locks the piece into `board`: its dots become the board's settled cells
 fn place_block_on_board(
     commands: &mut Commands,
-    game_data: &mut ResMut<GameData>,
-    children_query: Query<&GlobalTransform, With<ActiveDot>>,
-    entity: Entity,
-    children: &Children,
-    transform: &Transform,
+    board: Entity,
+    game_data: &mut GameData,
+    children_query: &ActiveDotQuery,
+    (entity, children, block, transform): (Entity, &Children, &tetromino::Block, &Transform),
 ) {
+    game_data.t_spin = game_data.last_move_rotation
+        && is_t_spin_position(&game_data.board_matrix, transform.translation.x, transform.translation.y, block);
+    game_data.last_move_rotation = false;
+    game_data.last_lock = Some(LockedPiece {
+        block: block.clone(),
+        x: transform.translation.x,
+        y: transform.translation.y,
+        inputs: game_data.piece_inputs,
+    });
+
     // Calculate hard drop score
     if let Some(start_y) = game_data.hard_drop_start_y {
         let current_y = transform.translation.y;
@@ -162,23 +199,25 @@ fn place_block_on_board(
     }
 
     children.iter().for_each(|child| {
-        commands.entity(*child).remove_parent_in_place();
-
-        let child_global_transform = children_query.get(*child)
-            .expect("active dot child should have GlobalTransform");
+        let child_transform = children_query.get(*child).expect("active dot child should have a Transform");
         let (board_x, board_y) = get_object_position_in_board(
-            child_global_transform.translation().x,
-            child_global_transform.translation().y,
+            transform.translation.x + child_transform.translation.x,
+            transform.translation.y + child_transform.translation.y,
         );
+        let (x, y) = board_cell_position(board_x, board_y);
 
         commands
             .entity(*child)
             .remove::<ActiveDot>()
-            .insert(BoardDot { board_x, board_y });
+            .insert((BoardDot { board_x, board_y }, Transform::from_xyz(x, y, transform.translation.z)))
+            .set_parent(board);
 
         place_dot_on_board(board_x, board_y, &mut game_data.board_matrix);
     });
-    commands.entity(entity).despawn();
+    commands.entity(entity).despawn_recursive();
+    game_data.pieces_placed += 1;
+    game_data.piece_locked = true;
+    game_data.last_clear = None;
 
     // Reset hold availability when a piece locks down
     game_data.hold_used = false;
@@ -186,6 +225,10 @@ fn place_block_on_board(
     // Reset lock delay state
     game_data.lock_delay_active = false;
     game_data.lock_move_count = 0;
+
+    // Start ARE; a line clear started this frame pushes it back until the rows are gone
+    game_data.entry_delay_active = true;
+    game_data.entry_delay_timer.reset();
 }
 
 #[cfg(test)]
@@ -210,13 +253,20 @@ mod tests {
     }
 
     #[test]
-    fn gravity_has_floor() {
+    fn gravity_caps_at_20g() {
         for level in [20, 50, 100, 1000] {
-            assert!(
-                gravity_seconds(level) >= GRAVITY_FLOOR,
-                "Gravity at level {level} should be >= {GRAVITY_FLOOR}, got {}",
-                gravity_seconds(level)
-            );
+            assert_eq!(gravity_g(level), MAX_GRAVITY_G, "Gravity at level {level} should be 20G");
         }
+        assert!((gravity_g(1) - 1.0 / 60.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn gravity_rows_keeps_the_fraction() {
+        let mut progress = 2.75;
+        assert_eq!(gravity_rows(&mut progress), 2);
+        assert!((progress - 0.75).abs() < f32::EPSILON);
+        let mut progress = 0.5;
+        assert_eq!(gravity_rows(&mut progress), 0);
+        assert!((progress - 0.5).abs() < f32::EPSILON);
     }
 }
@@ -0,0 +1,264 @@
+use std::collections::{HashSet, VecDeque};
+
+use bevy::prelude::{Commands, DespawnRecursiveExt, Entity, Parent, Query, Res, Text, With};
+
+use crate::board::{board_check_block_position, get_dot_position_in_board, BoardDot, EMPTY_CELL};
+use crate::common_component::{DropType, FinesseText, GameData, BOARD_COLS, BOARD_ROWS, DOT_SIZE, SPAWN_X};
+use crate::game_mode::GameMode;
+use crate::player::MainBoard;
+use crate::rotation::get_kick_offsets;
+use crate::spawn_block_system::{spawn_block, spawn_offset_y};
+use crate::tetromino;
+
+/// A piece as it locked, kept until the finesse check has looked at it
+#[derive(Clone)]
+pub struct LockedPiece {
+    pub block: tetromino::Block,
+    pub x: f32,
+    pub y: f32,
+    /// Left/right presses and rotations used to place it
+    pub inputs: u32,
+}
+
+/// Inputs used against the fewest that reach the same placement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FinesseCheck {
+    pub inputs: u32,
+    pub minimal: u32,
+}
+
+impl FinesseCheck {
+    pub fn is_fault(&self) -> bool {
+        self.inputs > self.minimal
+    }
+}
+
+/// Board cells the block covers, relative to its topmost row: two placements with the same
+/// footprint in the same columns are the same placement, whatever rotation state produced them
+fn footprint(block: &tetromino::Block, column: i8) -> Vec<(i8, i8)> {
+    let dots = block.dots_by_state();
+    let top = dots.iter().map(|dot| dot.y).min().unwrap_or(0);
+    let mut cells: Vec<(i8, i8)> = dots.iter().map(|dot| (column + dot.x, dot.y - top)).collect();
+    cells.sort_unstable();
+    cells
+}
+
+fn rotated(block: &tetromino::Block, clockwise: bool) -> (tetromino::Block, tetromino::State, tetromino::State) {
+    let mut block = block.clone();
+    let (from, to) = if clockwise {
+        tetromino::Rotation::rotate_right(&mut block)
+    } else {
+        tetromino::Rotation::rotate_left(&mut block)
+    };
+    (block, from, to)
+}
+
+/// Fewest inputs that bring `block` from its spawn position (spawn state, `SPAWN_X`) to the
+/// columns and orientation of `target`, with `target_column` the column of the target's origin.
+/// Every tap, every DAS to a wall and every rotation costs one input; searched on an empty board
+/// with the same SRS kicks the game uses.
+pub(crate) fn minimal_inputs(target: &tetromino::Block, target_column: i8) -> Option<u32> {
+    let board = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+    // Mid-board, so vertical kicks never leave the board
+    let y = 0.5 * DOT_SIZE;
+    let x_of = |offset: i8| SPAWN_X + offset as f32 * DOT_SIZE;
+    let spawn_column = get_dot_position_in_board(SPAWN_X, y, 0, 0).0;
+    let goal = footprint(target, target_column);
+
+    let mut start = target.clone();
+    start.reset_rotation();
+    let mut seen: HashSet<(i8, u8)> = HashSet::new();
+    let mut queue: VecDeque<(tetromino::Block, i8, u32)> = VecDeque::new();
+    seen.insert((0, *start.state() as u8));
+    queue.push_back((start, 0, 0));
+
+    while let Some((block, offset, cost)) = queue.pop_front() {
+        if footprint(&block, spawn_column + offset) == goal {
+            return Some(cost);
+        }
+
+        let mut next: Vec<(tetromino::Block, i8)> = Vec::new();
+        for step in [-1, 1] {
+            if board_check_block_position(&board, x_of(offset + step), y, &block) {
+                next.push((block.clone(), offset + step));
+                let mut wall = offset + step;
+                while board_check_block_position(&board, x_of(wall + step), y, &block) {
+                    wall += step;
+                }
+                next.push((block.clone(), wall));
+            }
+        }
+        for clockwise in [true, false] {
+            let (turned, from, to) = rotated(&block, clockwise);
+            let kick = get_kick_offsets(&turned, from, to).into_iter().find(|(kick_x, kick_y)| {
+                board_check_block_position(&board, x_of(offset + kick_x), y + *kick_y as f32 * DOT_SIZE, &turned)
+            });
+            if let Some((kick_x, _)) = kick {
+                next.push((turned, offset + kick_x));
+            }
+        }
+
+        for (block, offset) in next {
+            if seen.insert((offset, *block.state() as u8)) {
+                queue.push_back((block, offset, cost + 1));
+            }
+        }
+    }
+    None
+}
+
+/// Finesse only applies to placements a piece can drop straight into; tucks and spins under
+/// an overhang are skipped. `board` must not contain the piece itself.
+fn drop_reachable(board: &[[i8; BOARD_COLS]; BOARD_ROWS], piece: &LockedPiece) -> bool {
+    let mut y = piece.y;
+    while board_check_block_position(board, piece.x, y + DOT_SIZE, &piece.block) {
+        y += DOT_SIZE;
+    }
+    // Stopped by the top of the board rather than by a cell
+    piece
+        .block
+        .dots_by_state()
+        .iter()
+        .any(|dot| get_dot_position_in_board(piece.x, y + DOT_SIZE, dot.x, dot.y).1 < 0)
+}
+
+fn piece_cells(piece: &LockedPiece) -> Vec<(i8, i8)> {
+    piece
+        .block
+        .dots_by_state()
+        .iter()
+        .map(|dot| get_dot_position_in_board(piece.x, piece.y, dot.x, dot.y))
+        .collect()
+}
+
+/// Checks the finesse of every locked piece. In Finesse mode a fault takes the piece back off
+/// the board and deals it again from the top.
+pub(crate) fn finesse_check_system(
+    mut commands: Commands,
+    mut boards: Query<(Entity, &mut GameData)>,
+    mode: Res<GameMode>,
+    board_dots: Query<(Entity, &Parent, &BoardDot)>,
+) {
+    for (board, mut game_data) in boards.iter_mut() {
+        let Some(piece) = game_data.last_lock.take() else {
+            continue;
+        };
+
+        let cells = piece_cells(&piece);
+        let mut board_matrix = game_data.board_matrix;
+        for (board_x, board_y) in cells.iter() {
+            board_matrix[*board_y as usize][*board_x as usize] = EMPTY_CELL;
+        }
+        if !drop_reachable(&board_matrix, &piece) {
+            continue;
+        }
+        let column = get_dot_position_in_board(piece.x, piece.y, 0, 0).0;
+        let Some(minimal) = minimal_inputs(&piece.block, column) else {
+            continue;
+        };
+
+        let check = FinesseCheck {
+            inputs: piece.inputs,
+            minimal,
+        };
+        game_data.last_finesse = Some(check);
+        if !check.is_fault() {
+            continue;
+        }
+        game_data.finesse_faults += 1;
+
+        if *mode != GameMode::Finesse {
+            continue;
+        }
+        // Take the piece back before any line it completed is cleared
+        game_data.board_matrix = board_matrix;
+        for (entity, parent, board_dot) in board_dots.iter() {
+            if parent.get() == board && cells.contains(&(board_dot.board_x, board_dot.board_y)) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        let mut block = piece.block;
+        block.reset_rotation();
+        let offset_y = spawn_offset_y(&block);
+        spawn_block(&mut commands, board, block, 0.0, offset_y);
+        game_data.entry_delay_active = false;
+        game_data.gravity_progress = 0.0;
+        game_data.piece_inputs = 0;
+        game_data.hold_used = false;
+        game_data.drop_type = DropType::Normal;
+    }
+}
+
+/// Fault count and the last judged piece of the main board, in the left panel
+pub(crate) fn update_finesse_display(
+    game_data: Query<&GameData, With<MainBoard>>,
+    mut query: Query<&mut Text, With<FinesseText>>,
+) {
+    let (Ok(game_data), Ok(mut text)) = (game_data.get_single(), query.get_single_mut()) else {
+        return;
+    };
+    let mut content = format!("Finesse faults\n{}", game_data.finesse_faults);
+    if let Some(check) = game_data.last_finesse.filter(FinesseCheck::is_fault) {
+        content.push_str(&format!("\nLast: {} inputs\n{} needed", check.inputs, check.minimal));
+    }
+    if **text != content {
+        **text = content;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tetromino::{Block, State};
+
+    fn placed(mut block: Block, state: State) -> Block {
+        block.set_state(state);
+        block
+    }
+
+    #[test]
+    fn spawn_position_needs_no_inputs() {
+        assert_eq!(minimal_inputs(&Block::new_t(), 3), Some(0));
+    }
+
+    #[test]
+    fn walls_take_one_das() {
+        // O spawns over columns 4-5; its origin is one column left of its cells
+        assert_eq!(minimal_inputs(&Block::new_o(), -1), Some(1));
+        assert_eq!(minimal_inputs(&Block::new_o(), 7), Some(1));
+        assert_eq!(minimal_inputs(&Block::new_o(), 2), Some(1));
+        assert_eq!(minimal_inputs(&Block::new_o(), 1), Some(2));
+    }
+
+    #[test]
+    fn rotations_count() {
+        assert_eq!(minimal_inputs(&placed(Block::new_t(), State::One), 3), Some(1));
+        assert_eq!(minimal_inputs(&placed(Block::new_t(), State::Two), 3), Some(2));
+        assert_eq!(minimal_inputs(&placed(Block::new_t(), State::Three), 3), Some(1));
+    }
+
+    #[test]
+    fn symmetric_orientations_are_the_same_placement() {
+        // S in state Two covers the same shape as state Zero one row lower
+        let flat = minimal_inputs(&Block::new_s(), 3);
+        let flipped = minimal_inputs(&placed(Block::new_s(), State::Two), 3);
+        assert_eq!(flat, Some(0));
+        assert_eq!(flipped, Some(0));
+    }
+
+    #[test]
+    fn tucks_are_not_judged() {
+        let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        board[17] = [1, 1, 1, 1, 1, 1, 1, 0, 0, 0];
+        let under_overhang = LockedPiece {
+            block: Block::new_o(),
+            // Cells in columns 5-6, rows 18-19
+            x: -12.5,
+            y: -212.5,
+            inputs: 3,
+        };
+        assert!(!drop_reachable(&board, &under_overhang));
+        board[17] = [0; BOARD_COLS];
+        assert!(drop_reachable(&board, &under_overhang));
+    }
+}
@@ -0,0 +1,827 @@
+use std::time::Duration;
+
+use bevy::prelude::{Query, Res, Resource};
+use bevy::time::Time;
+
+use crate::attack::RuleSet;
+use crate::common_component::{GameData, ENTRY_DELAY_SECS, LINE_CLEAR_DELAY_SECS, TIMER_LOCK_DELAY_SECS};
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GameMode {
+    /// Endless play, speed increases every 10 lines
+    #[default]
+    Marathon,
+    /// Clear 40 lines as fast as possible
+    Sprint,
+    /// Score as much as possible before the time runs out
+    Ultra,
+    /// Relaxed play: no top-out, no speed-up, no records
+    Zen,
+    /// Dig through garbage rows as fast as possible
+    Dig,
+    /// Garbage rises from the bottom faster and faster; last as long as possible
+    Survival,
+    /// TGM-style speed curve up to 20G, with shorter delays every section
+    Master,
+    /// Set boards with a fixed piece sequence and an objective
+    Puzzle,
+    /// Finesse drill: a piece placed with more inputs than needed has to be placed again
+    Finesse,
+    /// Sandbox: paint the board and pick the queue to practise setups
+    Practice,
+    /// Trade garbage with a CPU opponent until one side tops out
+    Versus,
+    /// Two players on one keyboard (or keyboard and gamepad), trading garbage until one tops out
+    TwoPlayer,
+    /// Versus against another game instance over the network
+    Online,
+    /// Follow a game on another instance live, without playing
+    Watch,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 14] = [
+        GameMode::Marathon,
+        GameMode::Sprint,
+        GameMode::Ultra,
+        GameMode::Zen,
+        GameMode::Dig,
+        GameMode::Survival,
+        GameMode::Master,
+        GameMode::Puzzle,
+        GameMode::Finesse,
+        GameMode::Practice,
+        GameMode::Versus,
+        GameMode::TwoPlayer,
+        GameMode::Online,
+        GameMode::Watch,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Marathon => "Marathon",
+            GameMode::Sprint => "Sprint",
+            GameMode::Ultra => "Ultra",
+            GameMode::Zen => "Zen",
+            GameMode::Dig => "Dig",
+            GameMode::Survival => "Survival",
+            GameMode::Master => "Master",
+            GameMode::Puzzle => "Puzzle",
+            GameMode::Finesse => "Finesse",
+            GameMode::Practice => "Practice",
+            GameMode::Versus => "Versus",
+            GameMode::TwoPlayer => "2 Players",
+            GameMode::Online => "Online",
+            GameMode::Watch => "Watch",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            GameMode::Marathon => "Endless, speed up every 10 lines",
+            GameMode::Sprint => "Clear 40 lines as fast as possible",
+            GameMode::Ultra => "Score as much as you can before time is up",
+            GameMode::Zen => "No top-out, no speed-up, just play",
+            GameMode::Dig => "Clear every garbage row as fast as possible",
+            GameMode::Survival => "Garbage rises faster every level, hold on",
+            GameMode::Master => "Reach level 999 as gravity climbs to 20G",
+            GameMode::Puzzle => "Meet the objective with the pieces you are given",
+            GameMode::Finesse => "Place every piece in the fewest inputs, or place it again",
+            GameMode::Practice => "Paint the board, pick the pieces, practise setups",
+            GameMode::Versus => "Send garbage to the CPU until one of you tops out",
+            GameMode::TwoPlayer => "Player 2: J/L move, I/K drop, U/O rotate, H hold (or a gamepad)",
+            GameMode::Online => "Host or join a game over the network; type to edit the address",
+            GameMode::Watch => "Follow a game that allows spectators (F2 on its menu); type to edit the address",
+        }
+    }
+
+    /// Whether clearing lines raises the level (and gravity)
+    pub fn levels_up(&self) -> bool {
+        !matches!(
+            self,
+            GameMode::Sprint
+                | GameMode::Ultra
+                | GameMode::Zen
+                | GameMode::Dig
+                | GameMode::Master
+                | GameMode::Puzzle
+                | GameMode::Finesse
+                | GameMode::Practice
+                | GameMode::Versus
+                | GameMode::TwoPlayer
+                | GameMode::Online
+                | GameMode::Watch
+        )
+    }
+
+    /// Modes that start once connected to another instance rather than on Enter
+    pub fn starts_over_network(&self) -> bool {
+        matches!(self, GameMode::Online | GameMode::Watch)
+    }
+
+    pub fn next(&self) -> GameMode {
+        let index = Self::ALL.iter().position(|m| m == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn previous(&self) -> GameMode {
+        let index = Self::ALL.iter().position(|m| m == self).unwrap_or(0);
+        Self::ALL[(index + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+pub const ULTRA_DURATIONS_SECS: [u32; 4] = [60, 120, 180, 300];
+const DEFAULT_ULTRA_DURATION_SECS: u32 = 120;
+/// Marathon line goals; `0` stands for endless
+pub const MARATHON_GOALS: [u32; 3] = [150, 200, 0];
+pub const MAX_START_LEVEL: u32 = 15;
+pub const DIG_ROW_COUNTS: [u32; 3] = [5, 10, 18];
+const DEFAULT_DIG_ROWS: u32 = 10;
+pub const LOCK_DELAYS_MS: [u32; 5] = [200, 300, 500, 750, 1000];
+pub const LINE_CLEAR_DELAYS_MS: [u32; 4] = [0, 150, 300, 500];
+pub const ENTRY_DELAYS_MS: [u32; 5] = [0, 50, 100, 200, 300];
+
+/// How Marathon decides when to level up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LevelProgression {
+    /// Every 10 lines
+    #[default]
+    Fixed,
+    /// Guideline variable goal: level N needs 5×N line clear units (1/3/5/8 per single/double/triple/tetris)
+    Variable,
+}
+
+/// How strong the Versus CPU and the 2 Players bot play, from beginner to expert (`bot::BotTier::preset`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CpuDifficulty {
+    Beginner,
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+    Expert,
+}
+
+impl CpuDifficulty {
+    pub const ALL: [CpuDifficulty; 5] = [
+        CpuDifficulty::Beginner,
+        CpuDifficulty::Easy,
+        CpuDifficulty::Medium,
+        CpuDifficulty::Hard,
+        CpuDifficulty::Expert,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CpuDifficulty::Beginner => "Beginner",
+            CpuDifficulty::Easy => "Easy",
+            CpuDifficulty::Medium => "Medium",
+            CpuDifficulty::Hard => "Hard",
+            CpuDifficulty::Expert => "Expert",
+        }
+    }
+}
+
+/// Whether an online game waits for the opponent to connect or connects to them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnlineRole {
+    #[default]
+    Host,
+    Join,
+}
+
+/// How long a grounded piece waits before locking, cleared rows take to go and the next piece takes to appear
+/// (ARE). Master ignores them and follows its own speed curve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delays {
+    pub lock: Duration,
+    pub line_clear: Duration,
+    pub entry: Duration,
+}
+
+impl Default for Delays {
+    fn default() -> Self {
+        Self {
+            lock: Duration::from_secs_f32(TIMER_LOCK_DELAY_SECS),
+            line_clear: Duration::from_secs_f32(LINE_CLEAR_DELAY_SECS),
+            entry: Duration::from_secs_f32(ENTRY_DELAY_SECS),
+        }
+    }
+}
+
+impl Delays {
+    /// Steps the lock delay to the next of `LOCK_DELAYS_MS` (F5 on the start screen), wrapping around
+    pub fn cycle_lock(&mut self) {
+        self.lock = cycle_millis(&LOCK_DELAYS_MS, self.lock);
+    }
+
+    /// Steps the line clear delay to the next of `LINE_CLEAR_DELAYS_MS` (F6), wrapping around
+    pub fn cycle_line_clear(&mut self) {
+        self.line_clear = cycle_millis(&LINE_CLEAR_DELAYS_MS, self.line_clear);
+    }
+
+    /// Steps ARE to the next of `ENTRY_DELAYS_MS` (F7), wrapping around
+    pub fn cycle_entry(&mut self) {
+        self.entry = cycle_millis(&ENTRY_DELAYS_MS, self.entry);
+    }
+}
+
+/// The duration after `current` in `options` (milliseconds), back to the first after the last
+fn cycle_millis(options: &[u32], current: Duration) -> Duration {
+    let index = options
+        .iter()
+        .position(|&option| option as u128 == current.as_millis())
+        .map_or(0, |index| (index + 1) % options.len());
+    Duration::from_millis(options[index] as u64)
+}
+
+pub const DEFAULT_ONLINE_PORT: u16 = 7878;
+pub const DEFAULT_ONLINE_ADDRESS: &str = "127.0.0.1:7878";
+pub const DEFAULT_WATCH_ADDRESS: &str = "127.0.0.1:7900";
+const MAX_ADDRESS_LEN: usize = 64;
+
+/// A single adjustable option shown on the start screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModeOption {
+    UltraTimeLimit,
+    MarathonGoal,
+    StartLevel,
+    Progression,
+    DigRows,
+    Puzzle,
+    CpuDifficulty,
+    SharedQueue,
+    SecondPlayer,
+    OnlineRole,
+    Rules,
+    OnlineAddress,
+    WatchAddress,
+}
+
+/// Per-mode options. On the start screen Tab moves between a mode's options and Left/Right changes the focused one
+#[derive(Resource, Debug, Clone)]
+pub struct ModeSettings {
+    pub ultra_duration_secs: u32,
+    /// Lines to clear to finish Marathon, `None` for endless
+    pub marathon_goal: Option<u32>,
+    pub start_level: u32,
+    pub progression: LevelProgression,
+    /// Garbage rows a Dig game starts with
+    pub dig_rows: u32,
+    /// Index of the selected puzzle in the puzzle pack
+    pub puzzle: usize,
+    /// Name and objective of every loaded puzzle, for the start screen
+    pub puzzle_labels: Vec<String>,
+    /// Level of the Versus CPU, and of the bot when it takes player 2's seat
+    pub cpu_difficulty: CpuDifficulty,
+    /// Two players: both boards are dealt the same piece sequence
+    pub shared_queue: bool,
+    /// Two players: the bot plays the second board, as a sparring partner
+    pub bot_second_player: bool,
+    pub online_role: OnlineRole,
+    /// Garbage rules of a hosted online game
+    pub rule_set: RuleSet,
+    /// Address to join; hosting listens on its port
+    pub online_address: String,
+    /// Addresses of the games found on the local network, which Left/Right on the address steps through
+    pub lan_games: Vec<String>,
+    /// Address of the game to watch
+    pub watch_address: String,
+    /// Whether spectators may connect and follow the main board (F2 on the start screen)
+    pub allow_spectators: bool,
+    /// Whether the bot plays the main board (F3 on the start screen)
+    pub demo: bool,
+    /// Whether the players' boards outline where the bot would put the falling piece (F4 on the start screen)
+    pub hint: bool,
+    /// Command starting an external bot (Tetris Bot Protocol) to play instead of the built-in one
+    pub external_bot: Option<String>,
+    /// Lock delay, line clear delay and ARE of every board (F5–F7 on the start screen)
+    pub delays: Delays,
+    focused_option: usize,
+}
+
+impl Default for ModeSettings {
+    fn default() -> Self {
+        Self {
+            ultra_duration_secs: DEFAULT_ULTRA_DURATION_SECS,
+            marathon_goal: None,
+            start_level: 1,
+            progression: LevelProgression::default(),
+            dig_rows: DEFAULT_DIG_ROWS,
+            puzzle: 0,
+            puzzle_labels: Vec::new(),
+            cpu_difficulty: CpuDifficulty::default(),
+            shared_queue: true,
+            bot_second_player: false,
+            online_role: OnlineRole::default(),
+            rule_set: RuleSet::default(),
+            online_address: DEFAULT_ONLINE_ADDRESS.to_string(),
+            lan_games: Vec::new(),
+            watch_address: DEFAULT_WATCH_ADDRESS.to_string(),
+            allow_spectators: false,
+            demo: false,
+            hint: false,
+            external_bot: None,
+            delays: Delays::default(),
+            focused_option: 0,
+        }
+    }
+}
+
+impl ModeSettings {
+    /// Default settings offering the puzzles with these labels
+    pub fn with_puzzle_labels(puzzle_labels: Vec<String>) -> Self {
+        Self {
+            puzzle_labels,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_external_bot(self, external_bot: Option<String>) -> Self {
+        Self { external_bot, ..self }
+    }
+
+    fn options(mode: GameMode) -> &'static [ModeOption] {
+        match mode {
+            GameMode::Ultra => &[ModeOption::UltraTimeLimit],
+            GameMode::Marathon => &[ModeOption::MarathonGoal, ModeOption::StartLevel, ModeOption::Progression],
+            GameMode::Dig => &[ModeOption::DigRows],
+            GameMode::Puzzle => &[ModeOption::Puzzle],
+            GameMode::Versus => &[ModeOption::CpuDifficulty],
+            GameMode::TwoPlayer => &[ModeOption::SharedQueue, ModeOption::SecondPlayer, ModeOption::CpuDifficulty],
+            GameMode::Online => &[ModeOption::OnlineRole, ModeOption::Rules, ModeOption::OnlineAddress],
+            GameMode::Watch => &[ModeOption::WatchAddress],
+            _ => &[],
+        }
+    }
+
+    fn focused(&self, mode: GameMode) -> Option<ModeOption> {
+        let options = Self::options(mode);
+        options.get(self.focused_option.min(options.len().saturating_sub(1))).copied()
+    }
+
+    /// Labels of the selected mode's options, one per line, `None` when the mode has no options
+    pub fn option_label(&self, mode: GameMode) -> Option<String> {
+        let options = Self::options(mode);
+        if options.is_empty() {
+            return None;
+        }
+        let focused = self.focused(mode);
+        let lines: Vec<String> = options
+            .iter()
+            .map(|option| {
+                let value = self.option_value(*option);
+                let name = match option {
+                    ModeOption::UltraTimeLimit => "Time limit",
+                    ModeOption::MarathonGoal => "Goal",
+                    ModeOption::StartLevel => "Start level",
+                    ModeOption::Progression => "Level up",
+                    ModeOption::DigRows => "Garbage",
+                    ModeOption::Puzzle => "Puzzle",
+                    ModeOption::CpuDifficulty if mode == GameMode::TwoPlayer => "Bot level",
+                    ModeOption::CpuDifficulty => "CPU",
+                    ModeOption::SharedQueue => "Pieces",
+                    ModeOption::SecondPlayer => "Player 2",
+                    ModeOption::OnlineRole => "Role",
+                    ModeOption::Rules => "Rules",
+                    ModeOption::OnlineAddress | ModeOption::WatchAddress => "Address",
+                };
+                if Some(*option) == focused {
+                    format!("{name}: < {value} >")
+                } else {
+                    format!("{name}: {value}")
+                }
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    fn option_value(&self, option: ModeOption) -> String {
+        match option {
+            ModeOption::UltraTimeLimit => format_minutes(self.ultra_duration()),
+            ModeOption::MarathonGoal => match self.marathon_goal {
+                Some(goal) => format!("{goal} lines"),
+                None => "Endless".to_string(),
+            },
+            ModeOption::StartLevel => self.start_level.to_string(),
+            ModeOption::Progression => match self.progression {
+                LevelProgression::Fixed => "Fixed goal".to_string(),
+                LevelProgression::Variable => "Variable goal".to_string(),
+            },
+            ModeOption::DigRows => format!("{} rows", self.dig_rows),
+            ModeOption::Puzzle => match self.puzzle_labels.get(self.puzzle) {
+                Some(label) => format!("{}. {label}", self.puzzle + 1),
+                None => "none found".to_string(),
+            },
+            ModeOption::CpuDifficulty => self.cpu_difficulty.name().to_string(),
+            ModeOption::SharedQueue if self.shared_queue => "Same".to_string(),
+            ModeOption::SharedQueue => "Separate".to_string(),
+            ModeOption::SecondPlayer if self.bot_second_player => "Bot".to_string(),
+            ModeOption::SecondPlayer => "Human".to_string(),
+            ModeOption::OnlineRole => match self.online_role {
+                OnlineRole::Host => "Host".to_string(),
+                OnlineRole::Join => "Join".to_string(),
+            },
+            ModeOption::Rules => self.rule_set.name().to_string(),
+            ModeOption::OnlineAddress => self.online_address.clone(),
+            ModeOption::WatchAddress => self.watch_address.clone(),
+        }
+    }
+
+    /// Moves focus to the mode's next option, wrapping around
+    pub fn focus_next_option(&mut self, mode: GameMode) {
+        let count = Self::options(mode).len();
+        if count > 0 {
+            self.focused_option = (self.focused_option.min(count - 1) + 1) % count;
+        }
+    }
+
+    /// Moves the focused option one step forward (`step > 0`) or back
+    pub fn adjust(&mut self, mode: GameMode, step: i32) {
+        match self.focused(mode) {
+            Some(ModeOption::UltraTimeLimit) => {
+                self.ultra_duration_secs = step_option(&ULTRA_DURATIONS_SECS, self.ultra_duration_secs, step);
+            }
+            Some(ModeOption::MarathonGoal) => {
+                let goal = step_option(&MARATHON_GOALS, self.marathon_goal.unwrap_or(0), step);
+                self.marathon_goal = (goal > 0).then_some(goal);
+            }
+            Some(ModeOption::StartLevel) => {
+                self.start_level = (self.start_level as i32 + step).clamp(1, MAX_START_LEVEL as i32) as u32;
+            }
+            Some(ModeOption::Progression) => {
+                self.progression = match self.progression {
+                    LevelProgression::Fixed => LevelProgression::Variable,
+                    LevelProgression::Variable => LevelProgression::Fixed,
+                };
+            }
+            Some(ModeOption::DigRows) => {
+                self.dig_rows = step_option(&DIG_ROW_COUNTS, self.dig_rows, step);
+            }
+            Some(ModeOption::Puzzle) => {
+                let last = self.puzzle_labels.len().saturating_sub(1) as i32;
+                self.puzzle = (self.puzzle as i32 + step).clamp(0, last) as usize;
+            }
+            Some(ModeOption::CpuDifficulty) => {
+                let all = CpuDifficulty::ALL;
+                let index = all.iter().position(|&d| d == self.cpu_difficulty).unwrap_or(0) as i32;
+                self.cpu_difficulty = all[(index + step).clamp(0, all.len() as i32 - 1) as usize];
+            }
+            Some(ModeOption::SharedQueue) => {
+                self.shared_queue = !self.shared_queue;
+            }
+            Some(ModeOption::SecondPlayer) => {
+                self.bot_second_player = !self.bot_second_player;
+            }
+            Some(ModeOption::OnlineRole) => {
+                self.online_role = match self.online_role {
+                    OnlineRole::Host => OnlineRole::Join,
+                    OnlineRole::Join => OnlineRole::Host,
+                };
+            }
+            Some(ModeOption::Rules) => {
+                let all = RuleSet::ALL;
+                let index = all.iter().position(|&r| r == self.rule_set).unwrap_or(0) as i32;
+                self.rule_set = all[(index + step).clamp(0, all.len() as i32 - 1) as usize];
+            }
+            Some(ModeOption::OnlineAddress) => {
+                let count = self.lan_games.len() as i32;
+                if count > 0 {
+                    let index = match self.lan_games.iter().position(|game| *game == self.online_address) {
+                        Some(index) => (index as i32 + step).rem_euclid(count),
+                        None => 0,
+                    };
+                    self.online_address = self.lan_games[index as usize].clone();
+                }
+            }
+            Some(ModeOption::WatchAddress) | None => {}
+        }
+    }
+
+    /// The address typing edits: the mode's address option, when it is focused
+    fn focused_address(&mut self, mode: GameMode) -> Option<&mut String> {
+        match self.focused(mode) {
+            Some(ModeOption::OnlineAddress) => Some(&mut self.online_address),
+            Some(ModeOption::WatchAddress) => Some(&mut self.watch_address),
+            _ => None,
+        }
+    }
+
+    /// Whether typing edits an address (the focused option is one)
+    pub fn editing_address(&self, mode: GameMode) -> bool {
+        matches!(self.focused(mode), Some(ModeOption::OnlineAddress | ModeOption::WatchAddress))
+    }
+
+    /// Types `text` into the focused address, dropping characters no address uses
+    pub fn type_address(&mut self, mode: GameMode, text: &str) {
+        let Some(address) = self.focused_address(mode) else {
+            return;
+        };
+        for c in text.chars().filter(|c| c.is_ascii_alphanumeric() || ".:-[]".contains(*c)) {
+            if address.len() < MAX_ADDRESS_LEN {
+                address.push(c);
+            }
+        }
+    }
+
+    pub fn erase_address_char(&mut self, mode: GameMode) {
+        if let Some(address) = self.focused_address(mode) {
+            address.pop();
+        }
+    }
+
+    /// Port to host on: the one in the address, or the default port
+    pub fn online_port(&self) -> u16 {
+        self.online_address
+            .rsplit_once(':')
+            .and_then(|(_, port)| port.parse().ok())
+            .unwrap_or(DEFAULT_ONLINE_PORT)
+    }
+
+    pub fn ultra_duration(&self) -> Duration {
+        Duration::from_secs(self.ultra_duration_secs as u64)
+    }
+
+    /// Level a new game of `mode` starts at
+    pub fn start_level_for(&self, mode: GameMode) -> u32 {
+        match mode {
+            GameMode::Marathon => self.start_level,
+            // Master counts levels from 0 like TGM
+            GameMode::Master => 0,
+            _ => 1,
+        }
+    }
+}
+
+/// Picks the neighbouring value in `options`, stopping at both ends
+fn step_option(options: &[u32], current: u32, step: i32) -> u32 {
+    let index = options.iter().position(|&o| o == current).unwrap_or(0) as i32;
+    let index = (index + step).clamp(0, options.len() as i32 - 1);
+    options[index as usize]
+}
+
+/// Advances every board's play clock while a game is running (not while paused or on the results screen)
+pub(crate) fn game_clock_system(time: Res<Time>, mut boards: Query<&mut GameData>) {
+    for mut game_data in boards.iter_mut() {
+        game_data.play_time.tick(time.delta());
+    }
+}
+
+/// Gives every board the lock delay, line clear delay and ARE of the settings
+pub(crate) fn delay_settings_system(settings: Res<ModeSettings>, mut boards: Query<&mut GameData>) {
+    let delays = settings.delays;
+    for mut game_data in boards.iter_mut() {
+        let current = Delays {
+            lock: game_data.lock_delay_timer.duration(),
+            line_clear: game_data.line_clear_timer.duration(),
+            entry: game_data.entry_delay_timer.duration(),
+        };
+        if current == delays {
+            continue;
+        }
+        game_data.lock_delay_timer.set_duration(delays.lock);
+        game_data.line_clear_timer.set_duration(delays.line_clear);
+        game_data.entry_delay_timer.set_duration(delays.entry);
+    }
+}
+
+/// Formats a duration as `m:ss.mmm`
+pub(crate) fn format_time(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    let minutes = millis / 60_000;
+    let seconds = (millis / 1000) % 60;
+    let millis = millis % 1000;
+    format!("{minutes}:{seconds:02}.{millis:03}")
+}
+
+/// Signed difference to a reference time, e.g. `-1.250` when ahead
+pub(crate) fn format_delta(time: Duration, reference: Duration) -> String {
+    if time < reference {
+        let delta = reference - time;
+        format!("-{}.{:03}", delta.as_secs(), delta.subsec_millis())
+    } else {
+        let delta = time - reference;
+        format!("+{}.{:03}", delta.as_secs(), delta.subsec_millis())
+    }
+}
+
+/// Formats a duration as `m:ss`, rounding partial seconds up so a countdown only shows `0:00` at the end
+pub(crate) fn format_minutes(duration: Duration) -> String {
+    let seconds = duration.as_millis().div_ceil(1000);
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::World;
+
+    #[test]
+    fn boards_take_the_configured_delays() {
+        let mut world = World::new();
+        let delays = Delays {
+            lock: Duration::from_millis(1000),
+            line_clear: Duration::ZERO,
+            entry: Duration::from_millis(50),
+        };
+        world.insert_resource(ModeSettings {
+            delays,
+            ..Default::default()
+        });
+        let board = world.spawn(GameData::default()).id();
+        world.run_system_once(delay_settings_system).expect("the system runs");
+        let game_data = world.get::<GameData>(board).expect("a board");
+        assert_eq!(game_data.lock_delay_timer.duration(), delays.lock);
+        assert_eq!(game_data.line_clear_timer.duration(), delays.line_clear);
+        assert_eq!(game_data.entry_delay_timer.duration(), delays.entry);
+    }
+
+    #[test]
+    fn delays_cycle_through_their_choices() {
+        let mut delays = Delays::default();
+        delays.cycle_lock();
+        assert_eq!(delays.lock, Duration::from_millis(750));
+        delays.cycle_line_clear();
+        assert_eq!(delays.line_clear, Duration::from_millis(500));
+        delays.cycle_line_clear();
+        assert_eq!(delays.line_clear, Duration::ZERO);
+        for _ in 0..ENTRY_DELAYS_MS.len() {
+            delays.cycle_entry();
+        }
+        assert_eq!(delays.entry.as_millis(), Delays::default().entry.as_millis());
+    }
+
+    #[test]
+    fn format_time_zero() {
+        assert_eq!(format_time(Duration::ZERO), "0:00.000");
+    }
+
+    #[test]
+    fn format_time_minutes_and_millis() {
+        assert_eq!(format_time(Duration::from_millis(83_456)), "1:23.456");
+    }
+
+    #[test]
+    fn mode_cycling_wraps() {
+        for mode in GameMode::ALL {
+            assert_eq!(mode.next().previous(), mode);
+        }
+        assert_eq!(GameMode::ALL[GameMode::ALL.len() - 1].next(), GameMode::ALL[0]);
+    }
+
+    #[test]
+    fn sprint_does_not_level_up() {
+        assert!(GameMode::Marathon.levels_up());
+        assert!(!GameMode::Sprint.levels_up());
+        assert!(!GameMode::Ultra.levels_up());
+        assert!(!GameMode::Zen.levels_up());
+    }
+
+    #[test]
+    fn delta_ahead_and_behind() {
+        let a = Duration::from_millis(10_000);
+        let b = Duration::from_millis(11_250);
+        assert_eq!(format_delta(a, b), "-1.250");
+        assert_eq!(format_delta(b, a), "+1.250");
+    }
+
+    #[test]
+    fn format_minutes_rounds_up() {
+        assert_eq!(format_minutes(Duration::from_secs(120)), "2:00");
+        assert_eq!(format_minutes(Duration::from_millis(59_001)), "1:00");
+        assert_eq!(format_minutes(Duration::ZERO), "0:00");
+    }
+
+    #[test]
+    fn step_option_clamps_at_ends() {
+        assert_eq!(step_option(&ULTRA_DURATIONS_SECS, 120, 1), 180);
+        assert_eq!(step_option(&ULTRA_DURATIONS_SECS, 120, -1), 60);
+        assert_eq!(step_option(&ULTRA_DURATIONS_SECS, 60, -1), 60);
+        assert_eq!(step_option(&ULTRA_DURATIONS_SECS, 300, 1), 300);
+    }
+
+    #[test]
+    fn ultra_defaults_to_two_minutes() {
+        let settings = ModeSettings::default();
+        assert_eq!(settings.ultra_duration(), Duration::from_secs(120));
+        assert_eq!(settings.option_label(GameMode::Ultra).as_deref(), Some("Time limit: < 2:00 >"));
+        assert_eq!(settings.option_label(GameMode::Sprint), None);
+    }
+
+    #[test]
+    fn marathon_goal_cycles_through_endless() {
+        let mut settings = ModeSettings::default();
+        assert_eq!(settings.marathon_goal, None);
+        settings.adjust(GameMode::Marathon, -1);
+        assert_eq!(settings.marathon_goal, Some(200));
+        settings.adjust(GameMode::Marathon, -1);
+        assert_eq!(settings.marathon_goal, Some(150));
+        settings.adjust(GameMode::Marathon, 1);
+        settings.adjust(GameMode::Marathon, 1);
+        assert_eq!(settings.marathon_goal, None);
+    }
+
+    #[test]
+    fn start_level_is_clamped_and_only_used_by_marathon() {
+        let mut settings = ModeSettings::default();
+        settings.focus_next_option(GameMode::Marathon);
+        settings.adjust(GameMode::Marathon, -1);
+        assert_eq!(settings.start_level, 1);
+        for _ in 0..20 {
+            settings.adjust(GameMode::Marathon, 1);
+        }
+        assert_eq!(settings.start_level, MAX_START_LEVEL);
+        assert_eq!(settings.start_level_for(GameMode::Marathon), MAX_START_LEVEL);
+        assert_eq!(settings.start_level_for(GameMode::Sprint), 1);
+        assert_eq!(settings.start_level_for(GameMode::Master), 0);
+    }
+
+    #[test]
+    fn option_label_marks_focused_option() {
+        let mut settings = ModeSettings::default();
+        settings.focus_next_option(GameMode::Marathon);
+        let label = settings.option_label(GameMode::Marathon).unwrap();
+        assert_eq!(label, "Goal: Endless\nStart level: < 1 >\nLevel up: Fixed goal");
+    }
+
+    #[test]
+    fn cpu_difficulty_stops_at_both_ends() {
+        let mut settings = ModeSettings::default();
+        assert_eq!(settings.option_label(GameMode::Versus).as_deref(), Some("CPU: < Medium >"));
+        settings.adjust(GameMode::Versus, 1);
+        settings.adjust(GameMode::Versus, 1);
+        settings.adjust(GameMode::Versus, 1);
+        assert_eq!(settings.cpu_difficulty, CpuDifficulty::Expert);
+        settings.adjust(GameMode::Versus, -5);
+        assert_eq!(settings.cpu_difficulty, CpuDifficulty::Beginner);
+    }
+
+    #[test]
+    fn two_player_options_toggle() {
+        let mut settings = ModeSettings::default();
+        assert_eq!(
+            settings.option_label(GameMode::TwoPlayer).as_deref(),
+            Some("Pieces: < Same >\nPlayer 2: Human\nBot level: Medium")
+        );
+        settings.adjust(GameMode::TwoPlayer, 1);
+        assert!(!settings.shared_queue);
+        assert_eq!(
+            settings.option_label(GameMode::TwoPlayer).as_deref(),
+            Some("Pieces: < Separate >\nPlayer 2: Human\nBot level: Medium")
+        );
+        settings.focus_next_option(GameMode::TwoPlayer);
+        settings.adjust(GameMode::TwoPlayer, 1);
+        assert!(settings.bot_second_player);
+        settings.focus_next_option(GameMode::TwoPlayer);
+        settings.adjust(GameMode::TwoPlayer, -1);
+        assert_eq!(settings.cpu_difficulty, CpuDifficulty::Easy);
+    }
+
+    #[test]
+    fn puzzle_option_steps_through_loaded_puzzles() {
+        let mut settings = ModeSettings {
+            puzzle_labels: vec!["A".to_string(), "B".to_string()],
+            ..Default::default()
+        };
+        settings.adjust(GameMode::Puzzle, 1);
+        settings.adjust(GameMode::Puzzle, 1);
+        assert_eq!(settings.puzzle, 1);
+        assert_eq!(settings.option_label(GameMode::Puzzle).as_deref(), Some("Puzzle: < 2. B >"));
+        assert_eq!(ModeSettings::default().option_label(GameMode::Puzzle).as_deref(), Some("Puzzle: < none found >"));
+    }
+
+    #[test]
+    fn online_address_edits_only_when_focused() {
+        let mut settings = ModeSettings::default();
+        assert!(!settings.editing_address(GameMode::Online));
+        settings.adjust(GameMode::Online, 1);
+        assert_eq!(settings.online_role, OnlineRole::Join);
+        settings.focus_next_option(GameMode::Online);
+        settings.focus_next_option(GameMode::Online);
+        assert!(settings.editing_address(GameMode::Online));
+        for _ in 0..4 {
+            settings.erase_address_char(GameMode::Online);
+        }
+        settings.type_address(GameMode::Online, "9000 ");
+        assert_eq!(settings.online_address, "127.0.0.1:9000");
+        assert_eq!(settings.online_port(), 9000);
+        settings.online_address = "localhost".to_string();
+        assert_eq!(settings.online_port(), DEFAULT_ONLINE_PORT);
+    }
+
+    #[test]
+    fn address_steps_through_games_found_on_the_network() {
+        let mut settings = ModeSettings {
+            lan_games: vec!["10.0.0.2:7878".to_string(), "10.0.0.3:7879".to_string()],
+            focused_option: 2,
+            ..Default::default()
+        };
+        settings.adjust(GameMode::Online, 1);
+        assert_eq!(settings.online_address, "10.0.0.2:7878");
+        settings.adjust(GameMode::Online, 1);
+        assert_eq!(settings.online_address, "10.0.0.3:7879");
+        settings.adjust(GameMode::Online, 1);
+        assert_eq!(settings.online_address, "10.0.0.2:7878");
+    }
+}
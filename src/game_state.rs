@@ -1,7 +1,7 @@
 use bevy::color::Color;
 use bevy::input::ButtonInput;
 use bevy::prelude::{
-    BuildChildren, ChildBuild, Commands, DespawnRecursiveExt, Entity, KeyCode, NextState, Query,
+    BuildChildren, ChildBuild, Commands, DespawnRecursiveExt, Entity, Has, KeyCode, NextState, Query,
     Res, ResMut, State, Text, With, Without,
 };
 use bevy::text::{TextColor, TextFont};
@@ -10,32 +10,128 @@ use bevy::utils::default;
 
 use crate::board::BoardDot;
 use crate::common_component::{
-    ActiveBlock, DropType, GameData, GameOverOverlay, GameState, GhostDot, HoldDot, LevelText,
-    LinesText, PauseOverlay, PreviewDot, ScoreText,
+    ActiveBlock, GameData, GameOutcome, GameOverOverlay, GameState, GhostDot, HoldDot,
+    LevelText, LinesText, PauseOverlay, PreviewDot, ScoreText, TimerText,
 };
+use crate::game_mode::{format_minutes, format_time, GameMode, ModeSettings};
+use crate::dig::{dig_result_lines, garbage_rows_left, DigResult};
 use crate::ghost::GhostTracker;
+use crate::hint::HintDot;
+use crate::marathon::marathon_result_lines;
+use crate::master::{master_result_lines, section_goal};
+use crate::puzzle::{puzzle_result_lines, PuzzlePack};
+use crate::history::History;
 use crate::hold::HoldTracker;
+use crate::online::{online_result_lines, OnlineSession};
+use crate::player::MainBoard;
 use crate::spawn_block_system::Randomizer7Bag;
+use crate::sprint::{sprint_result_lines, SprintResult, SPRINT_LINES};
+use crate::survival::survival_result_lines;
+use crate::ultra::{ultra_result_lines, UltraResult};
+use crate::two_player::two_player_result_lines;
+use crate::versus::versus_result_lines;
 
 #[allow(clippy::type_complexity)]
 pub(crate) fn update_score_display(
-    game_data: Res<GameData>,
+    game_data: Query<&GameData, With<MainBoard>>,
+    mode: Res<GameMode>,
+    settings: Res<ModeSettings>,
     mut score_query: Query<&mut Text, With<ScoreText>>,
-    mut lines_query: Query<&mut Text, (With<LinesText>, Without<ScoreText>, Without<LevelText>)>,
-    mut level_query: Query<&mut Text, (With<LevelText>, Without<ScoreText>, Without<LinesText>)>,
+    mut lines_query: Query<&mut Text, (With<LinesText>, Without<ScoreText>, Without<LevelText>, Without<TimerText>)>,
+    mut level_query: Query<&mut Text, (With<LevelText>, Without<ScoreText>, Without<LinesText>, Without<TimerText>)>,
+    mut timer_query: Query<&mut Text, (With<TimerText>, Without<ScoreText>, Without<LinesText>, Without<LevelText>)>,
 ) {
+    let Ok(game_data) = game_data.get_single() else {
+        return;
+    };
     if let Ok(mut text) = score_query.get_single_mut() {
         **text = format!("{}", game_data.score);
     }
     if let Ok(mut text) = lines_query.get_single_mut() {
-        **text = format!("Lines: {}", game_data.lines_cleared);
+        **text = match *mode {
+            GameMode::Sprint => format!("Lines: {}/{}", game_data.lines_cleared, SPRINT_LINES),
+            GameMode::Dig => format!("Garbage: {}", garbage_rows_left(&game_data.board_matrix)),
+            GameMode::Marathon if settings.marathon_goal.is_some() => format!(
+                "Lines: {}/{}",
+                game_data.lines_cleared,
+                settings.marathon_goal.unwrap_or_default()
+            ),
+            _ => format!("Lines: {}", game_data.lines_cleared),
+        };
     }
     if let Ok(mut text) = level_query.get_single_mut() {
-        **text = format!("Level: {}", game_data.level);
+        **text = match *mode {
+            GameMode::Zen => format!("Pieces: {}", game_data.pieces_placed),
+            GameMode::Master => format!("Level: {}/{}", game_data.level, section_goal(game_data.level)),
+            _ => format!("Level: {}", game_data.level),
+        };
+    }
+    if let Ok(mut text) = timer_query.get_single_mut() {
+        **text = match *mode {
+            GameMode::Ultra => {
+                let remaining = settings.ultra_duration().saturating_sub(game_data.play_time.elapsed());
+                format_minutes(remaining)
+            }
+            _ => format_time(game_data.play_time.elapsed()),
+        };
     }
 }
 
-pub(crate) fn game_over_display_system(mut commands: Commands, game_data: Res<GameData>) {
+/// Once a board has topped out, every board still playing has won
+pub(crate) fn settle_outcomes_system(mut boards: Query<&mut GameData>) {
+    if !boards.iter().any(|game_data| game_data.outcome == GameOutcome::TopOut) {
+        return;
+    }
+    for mut game_data in boards.iter_mut() {
+        if game_data.outcome == GameOutcome::Playing {
+            game_data.outcome = GameOutcome::Won;
+        }
+    }
+}
+
+pub(crate) fn game_over_display_system(
+    mut commands: Commands,
+    boards: Query<(&GameData, Has<MainBoard>)>,
+    mode: Res<GameMode>,
+    sprint_result: Res<SprintResult>,
+    ultra_result: Res<UltraResult>,
+    dig_result: Res<DigResult>,
+    (settings, puzzles, online): (Res<ModeSettings>, Res<PuzzlePack>, Res<OnlineSession>),
+) {
+    let Some((game_data, _)) = boards.iter().find(|(_, main)| *main) else {
+        return;
+    };
+    let other_boards: Vec<&GameData> = boards.iter().filter(|(_, main)| !main).map(|(data, _)| data).collect();
+    let title = match (game_data.outcome, *mode) {
+        (GameOutcome::TopOut, GameMode::Versus | GameMode::Online) => "YOU LOSE",
+        (GameOutcome::TopOut, GameMode::TwoPlayer) => "PLAYER 2 WINS",
+        (GameOutcome::Won, GameMode::TwoPlayer) => "PLAYER 1 WINS",
+        (GameOutcome::TopOut | GameOutcome::Playing, _) => "GAME OVER",
+        (GameOutcome::Cleared, _) => "COMPLETE",
+        (GameOutcome::TimeUp, _) => "TIME UP",
+        (GameOutcome::Failed, _) => "FAILED",
+        (GameOutcome::Won, _) => "YOU WIN",
+    };
+    let result_lines = match (*mode, game_data.outcome) {
+        (GameMode::Sprint, GameOutcome::Cleared) => sprint_result_lines(&sprint_result, &game_data.splits),
+        (GameMode::Marathon, GameOutcome::Cleared) => marathon_result_lines(game_data),
+        (GameMode::Dig, GameOutcome::Cleared) => dig_result_lines(&dig_result, game_data.pieces_placed),
+        (GameMode::Survival, _) => survival_result_lines(game_data),
+        (GameMode::Master, _) => master_result_lines(game_data),
+        (GameMode::Puzzle, _) => puzzle_result_lines(game_data, puzzles.puzzles.get(settings.puzzle)),
+        (GameMode::Versus, _) => versus_result_lines(game_data, &other_boards, settings.cpu_difficulty),
+        (GameMode::TwoPlayer, _) => two_player_result_lines(game_data, &other_boards),
+        (GameMode::Online, _) => online_result_lines(game_data, &other_boards, &online),
+        (GameMode::Ultra, GameOutcome::TimeUp) => {
+            ultra_result_lines(&ultra_result, game_data.score, game_data.lines_cleared)
+        }
+        _ => vec![format!("Score: {}", game_data.score)],
+    };
+    let hint = match *mode {
+        GameMode::Online => "Press Enter for a rematch, Esc to disconnect",
+        _ => "Press Enter to restart, Esc for menu",
+    };
+
     commands
         .spawn((
             Node {
@@ -52,27 +148,30 @@ pub(crate) fn game_over_display_system(mut commands: Commands, game_data: Res<Ga
         ))
         .with_children(|parent| {
             parent.spawn((
-                Text::new("GAME OVER"),
+                Text::new(title),
                 TextColor(Color::WHITE),
                 TextFont {
                     font_size: 60.0,
                     ..default()
                 },
             ));
+            for (i, line) in result_lines.iter().enumerate() {
+                let (font_size, margin) = if i == 0 { (30.0, 20.0) } else { (18.0, 6.0) };
+                parent.spawn((
+                    Text::new(line.clone()),
+                    TextColor(Color::WHITE),
+                    TextFont {
+                        font_size,
+                        ..default()
+                    },
+                    Node {
+                        margin: bevy::ui::UiRect::top(Val::Px(margin)),
+                        ..default()
+                    },
+                ));
+            }
             parent.spawn((
-                Text::new(format!("Score: {}", game_data.score)),
-                TextColor(Color::WHITE),
-                TextFont {
-                    font_size: 30.0,
-                    ..default()
-                },
-                Node {
-                    margin: bevy::ui::UiRect::top(Val::Px(20.0)),
-                    ..default()
-                },
-            ));
-            parent.spawn((
-                Text::new("Press Enter to restart"),
+                Text::new(hint),
                 TextColor(Color::srgba(1.0, 1.0, 1.0, 0.7)),
                 TextFont {
                     font_size: 20.0,
@@ -90,33 +189,43 @@ pub(crate) fn game_over_display_system(mut commands: Commands, game_data: Res<Ga
 pub(crate) fn restart_system(
     mut commands: Commands,
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut game_data: ResMut<GameData>,
+    mut boards: Query<(&mut GameData, &mut Randomizer7Bag, &mut GhostTracker, &mut History)>,
     board_dots: Query<Entity, With<BoardDot>>,
     active_blocks: Query<Entity, With<ActiveBlock>>,
     overlay: Query<Entity, With<GameOverOverlay>>,
     preview_dots: Query<Entity, With<PreviewDot>>,
     hold_dots: Query<Entity, With<HoldDot>>,
     ghost_dots: Query<Entity, With<GhostDot>>,
+    hint_dots: Query<Entity, With<HintDot>>,
     mut game_state: ResMut<NextState<GameState>>,
-    mut drop_state: ResMut<NextState<DropType>>,
-    mut randomizer: ResMut<Randomizer7Bag>,
-    mut ghost_tracker: ResMut<GhostTracker>,
     mut hold_tracker: ResMut<HoldTracker>,
+    (mode, settings): (Res<GameMode>, Res<ModeSettings>),
 ) {
-    if !keyboard_input.just_pressed(KeyCode::Enter) {
+    let next = if keyboard_input.just_pressed(KeyCode::Enter) {
+        // An online rematch starts from the lobby once both players have asked for it
+        match *mode {
+            GameMode::Online => GameState::StartScreen,
+            _ => GameState::Playing,
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        GameState::StartScreen
+    } else {
         return;
-    }
+    };
 
-    *game_data = GameData::default();
-    *randomizer = Randomizer7Bag::default();
-    *ghost_tracker = GhostTracker::default();
+    for (mut game_data, mut randomizer, mut ghost_tracker, mut history) in boards.iter_mut() {
+        *game_data = GameData::with_start_level(settings.start_level_for(*mode));
+        *randomizer = Randomizer7Bag::default();
+        *ghost_tracker = GhostTracker::default();
+        *history = History::default();
+    }
     *hold_tracker = HoldTracker::default();
 
     for entity in board_dots.iter() {
-        commands.entity(entity).despawn();
+        commands.entity(entity).despawn_recursive();
     }
     for entity in active_blocks.iter() {
-        commands.entity(entity).despawn();
+        commands.entity(entity).despawn_recursive();
     }
     for entity in overlay.iter() {
         commands.entity(entity).despawn_recursive();
@@ -127,12 +236,11 @@ pub(crate) fn restart_system(
     for entity in hold_dots.iter() {
         commands.entity(entity).despawn();
     }
-    for entity in ghost_dots.iter() {
-        commands.entity(entity).despawn();
+    for entity in ghost_dots.iter().chain(hint_dots.iter()) {
+        commands.entity(entity).despawn_recursive();
     }
 
-    game_state.set(GameState::Playing);
-    drop_state.set(DropType::Normal);
+    game_state.set(next);
 }
 
 pub(crate) fn pause_system(
@@ -151,7 +259,8 @@ pub(crate) fn pause_system(
     }
 }
 
-pub(crate) fn pause_display_system(mut commands: Commands) {
+/// Shows the pause overlay. Zen has no game over, so its pause screen is also the way to restart or leave.
+pub(crate) fn pause_display_system(mut commands: Commands, mode: Res<GameMode>) {
     commands
         .spawn((
             Node {
@@ -176,7 +285,10 @@ pub(crate) fn pause_display_system(mut commands: Commands) {
                 },
             ));
             parent.spawn((
-                Text::new("Press P to resume"),
+                Text::new(match *mode {
+                    GameMode::Zen => "Press P to resume, Enter to restart, Esc for menu",
+                    _ => "Press P to resume",
+                }),
                 TextColor(Color::srgba(1.0, 1.0, 1.0, 0.7)),
                 TextFont {
                     font_size: 20.0,
@@ -197,4 +309,36 @@ pub(crate) fn unpause_cleanup_system(
     for entity in overlay.iter() {
         commands.entity(entity).despawn_recursive();
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::World;
+
+    fn settled(outcomes: &[GameOutcome]) -> Vec<GameOutcome> {
+        let mut world = World::new();
+        let boards: Vec<Entity> = outcomes
+            .iter()
+            .map(|&outcome| world.spawn(GameData { outcome, ..Default::default() }).id())
+            .collect();
+        world.run_system_once(settle_outcomes_system).expect("the system runs");
+        boards.iter().map(|&board| world.get::<GameData>(board).expect("still a board").outcome).collect()
+    }
+
+    #[test]
+    fn boards_still_playing_win_when_another_tops_out() {
+        assert_eq!(
+            settled(&[GameOutcome::Playing, GameOutcome::TopOut]),
+            vec![GameOutcome::Won, GameOutcome::TopOut]
+        );
+    }
+
+    #[test]
+    fn outcomes_stand_without_a_top_out() {
+        assert_eq!(
+            settled(&[GameOutcome::Cleared, GameOutcome::Playing]),
+            vec![GameOutcome::Cleared, GameOutcome::Playing]
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use bevy::prelude::{Commands, DespawnRecursiveExt, Entity, Parent, Query, Transform, With, Without};
+
+use crate::board::{board_check_block_position, spawn_board_dot, BoardDot, GARBAGE_CELL, GARBAGE_COLOR};
+use crate::common_component::{ActiveBlock, GameData, BOARD_COLS, DOT_SIZE};
+use crate::line_clear::insert_rows_at_bottom;
+use crate::tetromino;
+
+/// Settled cells, which slide up with the stack
+pub(crate) type BoardDotQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static Parent, &'static mut BoardDot, &'static mut Transform), Without<ActiveBlock>>;
+/// The falling piece, which is lifted out of the way of the rising stack
+pub(crate) type LiftedBlockQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static Parent, &'static tetromino::Block, &'static mut Transform),
+    (With<ActiveBlock>, Without<BoardDot>),
+>;
+
+/// Pushes `rows` in under the stack of `board`: settled cells move up, new garbage cells are spawned
+/// and the active piece is lifted until it no longer overlaps. Returns false on a top-out,
+/// i.e. when the stack or the active piece is pushed out of the top of the board.
+pub(crate) fn raise_garbage(
+    commands: &mut Commands,
+    board: Entity,
+    game_data: &mut GameData,
+    board_dots: &mut BoardDotQuery,
+    active_block: &mut LiftedBlockQuery,
+    rows: &[[i8; BOARD_COLS]],
+) -> bool {
+    if rows.is_empty() {
+        return true;
+    }
+    let count = rows.len() as i8;
+    let mut fits = insert_rows_at_bottom(&mut game_data.board_matrix, rows);
+
+    let own_dots = board_dots.iter_mut().filter(|(_, parent, ..)| parent.get() == board);
+    for (entity, _, mut board_dot, mut transform) in own_dots {
+        board_dot.board_y -= count;
+        transform.translation.y += DOT_SIZE * count as f32;
+        if board_dot.board_y < 0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    let first_row = game_data.board_matrix.len() - rows.len();
+    for (i, row) in game_data.board_matrix[first_row..].iter().enumerate() {
+        for (board_x, cell) in row.iter().enumerate() {
+            if *cell == GARBAGE_CELL {
+                spawn_board_dot(commands, board, board_x as i8, (first_row + i) as i8, GARBAGE_COLOR);
+            }
+        }
+    }
+
+    if let Some((_, block, mut transform)) = active_block.iter_mut().find(|(parent, ..)| parent.get() == board) {
+        let mut lifted = 0;
+        while !board_check_block_position(
+            &game_data.board_matrix,
+            transform.translation.x,
+            transform.translation.y,
+            block,
+        ) {
+            if lifted == count {
+                fits = false;
+                break;
+            }
+            transform.translation.y += DOT_SIZE;
+            lifted += 1;
+        }
+    }
+
+    fits
+}
@@ -1,14 +1,17 @@
 use bevy::math::Vec2;
-use bevy::prelude::{Commands, Entity, Query, Res, Resource, Transform, With};
+use bevy::prelude::{
+    BuildChildren, Commands, Component, DespawnRecursiveExt, Entity, Parent, Query, Transform, With,
+};
 use bevy::sprite::Sprite;
 use bevy::utils::default;
 use bevy::color::Color;
 
-use crate::board::board_check_block_position;
+use crate::board::landing_y;
 use crate::common_component::{ActiveBlock, GameData, GhostDot, DOT_SIZE};
 use crate::tetromino;
 
-#[derive(Resource, Default)]
+/// Where a board's ghost was last drawn
+#[derive(Component, Default)]
 pub(crate) struct GhostTracker {
     block_x: f32,
     ghost_y: f32,
@@ -17,75 +20,73 @@ pub(crate) struct GhostTracker {
 
 pub(crate) fn update_ghost_piece_system(
     mut commands: Commands,
-    ghost_dots: Query<Entity, With<GhostDot>>,
-    query: Query<(&tetromino::Block, &Transform), With<ActiveBlock>>,
-    game_data: Res<GameData>,
-    mut tracker: bevy::prelude::ResMut<GhostTracker>,
+    ghost_dots: Query<(Entity, &Parent), With<GhostDot>>,
+    query: Query<(&Parent, &tetromino::Block, &Transform), With<ActiveBlock>>,
+    mut boards: Query<(Entity, &GameData, &mut GhostTracker)>,
 ) {
-    let Ok((block, transform)) = query.get_single() else {
-        // No active block — clear ghost if present
-        if tracker.rotation_state.is_some() {
-            for entity in ghost_dots.iter() {
-                commands.entity(entity).despawn();
+    for (board, game_data, mut tracker) in boards.iter_mut() {
+        let mut despawn_ghost = || {
+            for (entity, parent) in ghost_dots.iter() {
+                if parent.get() == board {
+                    commands.entity(entity).despawn_recursive();
+                }
             }
-            *tracker = GhostTracker::default();
-        }
-        return;
-    };
+        };
+        let Some((_, block, transform)) = query.iter().find(|(parent, ..)| parent.get() == board) else {
+            // No active block — clear ghost if present
+            if tracker.rotation_state.is_some() {
+                despawn_ghost();
+                *tracker = GhostTracker::default();
+            }
+            continue;
+        };
 
-    // Simulate dropping until we can't go further
-    let mut ghost_y = transform.translation.y;
-    while board_check_block_position(
-        &game_data.board_matrix,
-        transform.translation.x,
-        ghost_y - DOT_SIZE,
-        block,
-    ) {
-        ghost_y -= DOT_SIZE;
-    }
+        // Simulate dropping until we can't go further
+        let ghost_y = landing_y(&game_data.board_matrix, transform.translation.x, transform.translation.y, block);
 
-    let current_state = *block.state();
-    let block_x = transform.translation.x;
+        let current_state = *block.state();
+        let block_x = transform.translation.x;
 
-    // Skip rebuild if nothing changed
-    if tracker.rotation_state == Some(current_state)
-        && tracker.block_x == block_x
-        && tracker.ghost_y == ghost_y
-    {
-        return;
-    }
+        // Skip rebuild if nothing changed
+        if tracker.rotation_state == Some(current_state)
+            && tracker.block_x == block_x
+            && tracker.ghost_y == ghost_y
+        {
+            continue;
+        }
 
-    // Despawn existing ghost dots
-    for entity in ghost_dots.iter() {
-        commands.entity(entity).despawn();
-    }
+        // Despawn existing ghost dots
+        despawn_ghost();
 
-    // Update tracker
-    tracker.block_x = block_x;
-    tracker.ghost_y = ghost_y;
-    tracker.rotation_state = Some(current_state);
+        // Update tracker
+        tracker.block_x = block_x;
+        tracker.ghost_y = ghost_y;
+        tracker.rotation_state = Some(current_state);
 
-    // Don't draw ghost if it's at the same position as the active piece
-    if ghost_y == transform.translation.y {
-        return;
-    }
+        // Don't draw ghost if it's at the same position as the active piece
+        if ghost_y == transform.translation.y {
+            continue;
+        }
 
-    // Spawn ghost dots at the landing position
-    let srgba = block.color().to_srgba();
-    let ghost_color = Color::srgba(srgba.red, srgba.green, srgba.blue, 0.2);
+        // Spawn ghost dots at the landing position
+        let srgba = block.color().to_srgba();
+        let ghost_color = Color::srgba(srgba.red, srgba.green, srgba.blue, 0.2);
 
-    for dot in block.dots_by_state().iter() {
-        let x = transform.translation.x + dot.x as f32 * DOT_SIZE;
-        let y = ghost_y + (-dot.y as f32) * DOT_SIZE;
+        for dot in block.dots_by_state().iter() {
+            let x = transform.translation.x + dot.x as f32 * DOT_SIZE;
+            let y = ghost_y + (-dot.y as f32) * DOT_SIZE;
 
-        commands.spawn((
-            Sprite {
-                color: ghost_color,
-                custom_size: Some(Vec2::new(DOT_SIZE, DOT_SIZE)),
-                ..default()
-            },
-            Transform::from_xyz(x, y, 0.5),
-            GhostDot,
-        ));
+            commands
+                .spawn((
+                    Sprite {
+                        color: ghost_color,
+                        custom_size: Some(Vec2::new(DOT_SIZE, DOT_SIZE)),
+                        ..default()
+                    },
+                    Transform::from_xyz(x, y, 0.5),
+                    GhostDot,
+                ))
+                .set_parent(board);
+        }
     }
 }
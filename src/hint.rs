@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+
+use bevy::color::Alpha;
+use bevy::math::Vec2;
+use bevy::prelude::{
+    BuildChildren, Commands, Component, DespawnRecursiveExt, Entity, Has, Parent, Query, Res, Transform, With, Without,
+};
+use bevy::sprite::Sprite;
+use bevy::utils::default;
+
+use crate::board::{board_cell_position, Board, Placement};
+use crate::bot::{plan, Bot, Weights};
+use crate::common_component::{ActiveBlock, GameData, DOT_SIZE};
+use crate::game_mode::ModeSettings;
+use crate::player::PlayerBoard;
+use crate::tetromino;
+
+/// Thickness of the hint's outline
+const OUTLINE_WIDTH: f32 = 2.0;
+const HINT_ALPHA: f32 = 0.8;
+
+/// One edge of the hint's outline, a child of its board
+#[derive(Component)]
+pub(crate) struct HintDot;
+
+/// What a board's hint was last worked out for: the piece, the settled cells and the held piece
+#[derive(Component, Default)]
+pub(crate) struct HintTracker {
+    piece: Option<Entity>,
+    board: Option<Board>,
+    held: Option<char>,
+}
+
+/// Edges of the placement's cells that face away from the piece, as (centre, size) in board space
+fn outline(placement: &Placement) -> Vec<(Vec2, Vec2)> {
+    let cells: HashSet<(i8, i8)> = placement
+        .block
+        .dots_by_state()
+        .iter()
+        .map(|dot| (placement.column + dot.x, placement.row + dot.y))
+        .collect();
+    let mut edges = Vec::new();
+    for &(column, row) in &cells {
+        let (x, y) = board_cell_position(column, row);
+        let inset = (DOT_SIZE - OUTLINE_WIDTH) / 2.0;
+        // Rows count downwards, so the cell above is row - 1
+        for (neighbour, offset, vertical) in [
+            ((column - 1, row), Vec2::new(-inset, 0.0), true),
+            ((column + 1, row), Vec2::new(inset, 0.0), true),
+            ((column, row - 1), Vec2::new(0.0, inset), false),
+            ((column, row + 1), Vec2::new(0.0, -inset), false),
+        ] {
+            if !cells.contains(&neighbour) {
+                let size = if vertical {
+                    Vec2::new(OUTLINE_WIDTH, DOT_SIZE)
+                } else {
+                    Vec2::new(DOT_SIZE, OUTLINE_WIDTH)
+                };
+                edges.push((Vec2::new(x, y) + offset, size));
+            }
+        }
+    }
+    edges
+}
+
+/// Shows the hint on the players' boards while it is switched on (F4 on the start screen); boards the bot
+/// plays get none
+pub(crate) fn hint_toggle_system(
+    mut commands: Commands,
+    settings: Res<ModeSettings>,
+    boards: Query<(Entity, Has<HintTracker>, Has<Bot>), With<PlayerBoard>>,
+    hint_dots: Query<(Entity, &Parent), With<HintDot>>,
+) {
+    for (board, tracked, played_by_bot) in boards.iter() {
+        match (settings.hint && !played_by_bot, tracked) {
+            (true, false) => {
+                commands.entity(board).insert(HintTracker::default());
+            }
+            (false, true) => {
+                commands.entity(board).remove::<HintTracker>();
+                for (entity, parent) in hint_dots.iter() {
+                    if parent.get() == board {
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Outlines where the bot would put the falling piece, next to the ghost. It is worked out again whenever
+/// a new piece comes in (hold included) or the settled cells change, e.g. when garbage rises.
+pub(crate) fn update_hint_system(
+    mut commands: Commands,
+    hint_dots: Query<(Entity, &Parent), With<HintDot>>,
+    active_blocks: Query<(Entity, &Parent, &tetromino::Block), With<ActiveBlock>>,
+    mut boards: Query<(Entity, &GameData, &mut HintTracker), Without<Bot>>,
+) {
+    for (board, game_data, mut tracker) in boards.iter_mut() {
+        let piece = active_blocks.iter().find(|(_, parent, _)| parent.get() == board);
+        let held = game_data.held_block.as_ref().map(|block| block.letter());
+        if tracker.piece == piece.map(|(entity, ..)| entity)
+            && tracker.board == Some(game_data.board_matrix)
+            && tracker.held == held
+        {
+            continue;
+        }
+        *tracker = HintTracker {
+            piece: piece.map(|(entity, ..)| entity),
+            board: Some(game_data.board_matrix),
+            held,
+        };
+        for (entity, parent) in hint_dots.iter() {
+            if parent.get() == board {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+
+        let Some((_, _, block)) = piece else {
+            continue;
+        };
+        let Some(best) = plan(&game_data.board_matrix, block, None, &Weights::default()) else {
+            continue;
+        };
+        let color = block.color().with_alpha(HINT_ALPHA);
+        for (position, size) in outline(&best.target.placement) {
+            commands
+                .spawn((
+                    Sprite {
+                        color,
+                        custom_size: Some(size),
+                        ..default()
+                    },
+                    Transform::from_xyz(position.x, position.y, 0.4),
+                    HintDot,
+                ))
+                .set_parent(board);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetromino::Block;
+
+    #[test]
+    fn the_outline_goes_round_the_piece() {
+        let placement = Placement {
+            block: Block::new_o(),
+            column: 4,
+            row: 18,
+        };
+        // A 2x2 square has two outer edges per cell
+        let edges = outline(&placement);
+        assert_eq!(edges.len(), 8);
+        let cells: Vec<_> = placement.block.dots_by_state().iter().map(|dot| (4 + dot.x, 18 + dot.y)).collect();
+        let (left, top) = cells.iter().map(|&(x, y)| board_cell_position(x, y)).fold(
+            (f32::MAX, f32::MIN),
+            |(left, top), (x, y)| (left.min(x), top.max(y)),
+        );
+        // No edge runs through the middle of the square
+        let middle = Vec2::new(left + DOT_SIZE / 2.0, top - DOT_SIZE / 2.0);
+        assert!(edges.iter().all(|(centre, _)| centre.distance(middle) > DOT_SIZE / 4.0));
+
+        let i = Placement {
+            block: Block::new_i(),
+            column: 0,
+            row: 0,
+        };
+        // Both long sides of the I plus its two ends
+        assert_eq!(outline(&i).len(), 10);
+    }
+}
@@ -0,0 +1,247 @@
+use bevy::color::Color;
+use bevy::input::ButtonInput;
+use bevy::prelude::{
+    Added, Commands, Component, DespawnRecursiveExt, Entity, KeyCode, Parent, Query, Res, With,
+};
+use bevy::sprite::Sprite;
+
+use crate::board::{spawn_board_dot, BoardDot};
+use crate::common_component::{ActiveBlock, DropType, GameData, BOARD_COLS, BOARD_ROWS};
+use crate::player::MainBoard;
+use crate::spawn_block_system::{spawn_block, spawn_offset_y, Randomizer7Bag};
+use crate::tetromino;
+
+/// Everything needed to put the game back to the moment a piece appeared
+#[derive(Clone)]
+pub(crate) struct Snapshot {
+    board: [[i8; BOARD_COLS]; BOARD_ROWS],
+    /// Color of every settled cell, to rebuild the rendered board
+    cells: Vec<(i8, i8, Color)>,
+    piece: tetromino::Block,
+    queue: Randomizer7Bag,
+    held_block: Option<tetromino::Block>,
+    hold_used: bool,
+    score: u32,
+    lines_cleared: u32,
+    level: u32,
+    goal_units: u32,
+    pieces_placed: u32,
+}
+
+/// One snapshot per placed piece of a board; undo and redo move `cursor` through them
+#[derive(Component, Default)]
+pub(crate) struct History {
+    snapshots: Vec<Snapshot>,
+    cursor: usize,
+}
+
+impl History {
+    /// Adds the snapshot of a newly dealt piece, dropping anything that could have been redone.
+    /// A piece dealt without a placement in between (hold, or the piece an undo put back) is not recorded.
+    fn record(&mut self, snapshot: Snapshot) {
+        if let Some(current) = self.snapshots.get(self.cursor) {
+            if current.pieces_placed == snapshot.pieces_placed {
+                return;
+            }
+            self.snapshots.truncate(self.cursor + 1);
+        }
+        self.snapshots.push(snapshot);
+        self.cursor = self.snapshots.len() - 1;
+    }
+
+    /// Steps back one placement. While no piece is out (entry or line clear delay) the placement to take back is
+    /// the piece that just locked, so the game goes back to the moment it appeared.
+    fn undo(&mut self, piece_out: bool) -> Option<&Snapshot> {
+        if piece_out {
+            self.cursor = self.cursor.checked_sub(1)?;
+        } else {
+            self.drop_redo();
+        }
+        self.snapshots.get(self.cursor)
+    }
+
+    fn redo(&mut self, piece_out: bool) -> Option<&Snapshot> {
+        if !piece_out {
+            self.drop_redo();
+        }
+        if self.cursor + 1 >= self.snapshots.len() {
+            return None;
+        }
+        self.cursor += 1;
+        self.snapshots.get(self.cursor)
+    }
+
+    /// A piece locked since the snapshot at the cursor, so what came after it can no longer be redone
+    fn drop_redo(&mut self) {
+        self.snapshots.truncate(self.cursor + 1);
+    }
+}
+
+/// Settled cells of every board
+type BoardDotQuery<'w, 's> = Query<'w, 's, (Entity, &'static Parent, &'static BoardDot, &'static Sprite)>;
+
+/// Takes a snapshot whenever a piece is dealt on the main board after a placement
+pub(crate) fn history_record_system(
+    mut boards: Query<(Entity, &mut History, &GameData, &Randomizer7Bag), With<MainBoard>>,
+    spawned: Query<(&Parent, &tetromino::Block), Added<ActiveBlock>>,
+    board_dots: BoardDotQuery,
+) {
+    let Ok((board, mut history, game_data, randomizer)) = boards.get_single_mut() else {
+        return;
+    };
+    let Some((_, piece)) = spawned.iter().find(|(parent, _)| parent.get() == board) else {
+        return;
+    };
+    history.record(Snapshot {
+        board: game_data.board_matrix,
+        cells: board_dots
+            .iter()
+            .filter(|(_, parent, ..)| parent.get() == board)
+            .map(|(_, _, board_dot, sprite)| (board_dot.board_x, board_dot.board_y, sprite.color))
+            .collect(),
+        piece: piece.clone(),
+        queue: randomizer.clone(),
+        held_block: game_data.held_block.clone(),
+        hold_used: game_data.hold_used,
+        score: game_data.score,
+        lines_cleared: game_data.lines_cleared,
+        level: game_data.level,
+        goal_units: game_data.goal_units,
+        pieces_placed: game_data.pieces_placed,
+    });
+}
+
+/// Ctrl+Z takes back the last placement on the main board, Ctrl+Y (or Ctrl+Shift+Z) places it again
+pub(crate) fn history_undo_system(
+    mut commands: Commands,
+    mut boards: Query<(Entity, &mut GameData, &mut History, &mut Randomizer7Bag), With<MainBoard>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    board_dots: BoardDotQuery,
+    active_blocks: Query<(Entity, &Parent), With<ActiveBlock>>,
+) {
+    let Ok((board, mut game_data, mut history, mut randomizer)) = boards.get_single_mut() else {
+        return;
+    };
+    if !keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) || !game_data.clearing_rows.is_empty() {
+        return;
+    }
+    let shift = keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let piece_out = active_blocks.iter().any(|(_, parent)| parent.get() == board);
+    let snapshot = if keyboard.just_pressed(KeyCode::KeyY) || (shift && keyboard.just_pressed(KeyCode::KeyZ)) {
+        history.redo(piece_out)
+    } else if keyboard.just_pressed(KeyCode::KeyZ) {
+        history.undo(piece_out)
+    } else {
+        return;
+    };
+    let Some(snapshot) = snapshot.cloned() else {
+        return;
+    };
+
+    game_data.board_matrix = snapshot.board;
+    game_data.held_block = snapshot.held_block;
+    game_data.hold_used = snapshot.hold_used;
+    game_data.score = snapshot.score;
+    game_data.lines_cleared = snapshot.lines_cleared;
+    game_data.level = snapshot.level;
+    game_data.goal_units = snapshot.goal_units;
+    game_data.pieces_placed = snapshot.pieces_placed;
+    // Nothing of the piece being played survives the jump
+    game_data.entry_delay_active = false;
+    game_data.lock_delay_active = false;
+    game_data.lock_move_count = 0;
+    game_data.gravity_progress = 0.0;
+    game_data.soft_drop_cells = 0;
+    game_data.hard_drop_start_y = None;
+    game_data.piece_inputs = 0;
+    game_data.last_move_rotation = false;
+    game_data.t_spin = false;
+    game_data.last_lock = None;
+    game_data.last_clear = None;
+
+    game_data.drop_type = DropType::Normal;
+    *randomizer = snapshot.queue;
+
+    for (entity, parent, ..) in board_dots.iter() {
+        if parent.get() == board {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    for (entity, parent) in active_blocks.iter() {
+        if parent.get() == board {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    for (board_x, board_y, color) in snapshot.cells {
+        spawn_board_dot(&mut commands, board, board_x, board_y, color);
+    }
+    let offset_y = spawn_offset_y(&snapshot.piece);
+    spawn_block(&mut commands, board, snapshot.piece, 0.0, offset_y);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(pieces_placed: u32) -> Snapshot {
+        Snapshot {
+            board: [[0; BOARD_COLS]; BOARD_ROWS],
+            cells: Vec::new(),
+            piece: tetromino::Block::new_t(),
+            queue: Randomizer7Bag::default(),
+            held_block: None,
+            hold_used: false,
+            score: pieces_placed * 100,
+            lines_cleared: 0,
+            level: 1,
+            goal_units: 0,
+            pieces_placed,
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_placements() {
+        let mut history = History::default();
+        for placed in 0..3 {
+            history.record(snapshot(placed));
+        }
+        assert_eq!(history.undo(true).map(|s| s.pieces_placed), Some(1));
+        assert_eq!(history.undo(true).map(|s| s.pieces_placed), Some(0));
+        assert!(history.undo(true).is_none());
+        assert_eq!(history.redo(true).map(|s| s.pieces_placed), Some(1));
+        assert_eq!(history.redo(true).map(|s| s.pieces_placed), Some(2));
+        assert!(history.redo(true).is_none());
+    }
+
+    #[test]
+    fn a_new_placement_drops_the_redo_branch() {
+        let mut history = History::default();
+        for placed in 0..3 {
+            history.record(snapshot(placed));
+        }
+        history.undo(true);
+        history.undo(true);
+        // The piece the undo dealt again is not a new placement
+        history.record(snapshot(0));
+        assert_eq!(history.snapshots.len(), 3);
+        history.record(snapshot(1));
+        assert_eq!(history.snapshots.len(), 2);
+        assert!(history.redo(true).is_none());
+    }
+
+    #[test]
+    fn undo_during_the_entry_delay_takes_back_the_piece_that_just_locked() {
+        let mut history = History::default();
+        for placed in 0..3 {
+            history.record(snapshot(placed));
+        }
+        // The third piece locks; the next one has not appeared yet
+        assert_eq!(history.undo(false).map(|s| s.pieces_placed), Some(2));
+        assert_eq!(history.undo(true).map(|s| s.pieces_placed), Some(1));
+
+        // After going back, a new lock leaves nothing to redo
+        history.record(snapshot(1));
+        assert_eq!(history.undo(false).map(|s| s.pieces_placed), Some(1));
+        assert!(history.redo(true).is_none());
+    }
+}
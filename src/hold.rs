@@ -1,16 +1,17 @@
 use bevy::color::Color;
-use bevy::input::ButtonInput;
 use bevy::math::Vec2;
 use bevy::prelude::{
-    Commands, DespawnRecursiveExt, Entity, KeyCode, NextState, Query, Res, ResMut, Resource,
-    Transform, With,
+    Commands, DespawnRecursiveExt, Entity, NextState, Parent, Query, ResMut, Resource, Transform, With,
 };
 use bevy::sprite::Sprite;
 use bevy::utils::default;
 
-use crate::common_component::{ActiveBlock, DropType, GameData, HoldDot, DOT_SIZE};
+use crate::common_component::{ActiveBlock, DropType, GameData, GameState, HoldDot};
+use crate::player::{MainBoard, PieceInput};
+use crate::spawn_block_system::deal_block;
 use crate::tetromino;
 
+/// What the HOLD box currently shows for the main board
 #[derive(Resource, Default)]
 pub(crate) struct HoldTracker {
     /// Discriminant of the last rendered held block (None = no block rendered)
@@ -20,46 +21,38 @@ pub(crate) struct HoldTracker {
 
 pub(crate) fn hold_block_system(
     mut commands: Commands,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut game_data: ResMut<GameData>,
-    query: Query<(Entity, &tetromino::Block), With<ActiveBlock>>,
-    mut state: ResMut<NextState<DropType>>,
+    mut boards: Query<(&mut GameData, &PieceInput)>,
+    query: Query<(Entity, &Parent, &tetromino::Block), With<ActiveBlock>>,
+    mut game_state: ResMut<NextState<GameState>>,
 ) {
-    if !keyboard_input.just_pressed(KeyCode::KeyC) {
-        return;
-    }
-    if game_data.hold_used {
-        return;
-    }
-
-    let Ok((entity, block)) = query.get_single() else {
-        return;
-    };
+    for (entity, parent, block) in query.iter() {
+        let Ok((mut game_data, input)) = boards.get_mut(parent.get()) else {
+            continue;
+        };
+        if !input.hold || game_data.hold_used || game_data.drop_type == DropType::Hard {
+            continue;
+        }
 
-    let mut current_block = block.clone();
-    current_block.reset_rotation();
+        let mut current_block = block.clone();
+        current_block.reset_rotation();
 
-    let previously_held = game_data.held_block.take();
-    game_data.held_block = Some(current_block);
-    game_data.hold_used = true;
+        let previously_held = game_data.held_block.take();
+        game_data.held_block = Some(current_block);
+        game_data.hold_used = true;
 
-    // Reset soft drop tracking
-    game_data.soft_drop_cells = 0;
-    game_data.hard_drop_start_y = None;
+        // Reset soft drop tracking
+        game_data.soft_drop_cells = 0;
+        game_data.piece_inputs = 0;
+        game_data.hard_drop_start_y = None;
 
-    commands.entity(entity).despawn_recursive();
+        game_data.drop_type = DropType::Normal;
+        commands.entity(entity).despawn_recursive();
 
-    // Spawn previously held piece if there was one
-    if let Some(held) = previously_held {
-        let mut transform_y_times: f32 = 8.0;
-        if let tetromino::Block::I { .. } = held {
-            transform_y_times = 9.0;
+        // The piece out of hold comes in like any other; with hold empty, spawn_block_system deals the next one
+        if let Some(held) = previously_held {
+            deal_block(&mut commands, parent.get(), &mut game_data, held, &mut game_state);
         }
-        crate::spawn_block_system::spawn_block(&mut commands, held, 0.0, DOT_SIZE * transform_y_times);
     }
-    // If hold was empty, spawn_block_system will handle spawning next piece
-
-    state.set(DropType::Normal);
 }
 
 const HOLD_BOX_CENTER_X: f32 = -165.0;
@@ -68,10 +61,13 @@ const HOLD_DOT_SIZE: f32 = 11.25;
 
 pub(crate) fn update_hold_preview_system(
     mut commands: Commands,
-    game_data: Res<GameData>,
+    game_data: Query<&GameData, With<MainBoard>>,
     hold_dots: Query<Entity, With<HoldDot>>,
     mut tracker: ResMut<HoldTracker>,
 ) {
+    let Ok(game_data) = game_data.get_single() else {
+        return;
+    };
     let current_disc = game_data.held_block.as_ref().map(std::mem::discriminant);
     let current_hold_used = game_data.hold_used;
 
@@ -124,9 +120,58 @@ pub(crate) fn update_hold_preview_system(
 
 #[cfg(test)]
 mod tests {
-    use crate::common_component::GameData;
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{BuildChildren, World};
+
+    use crate::board::{landing_y, BLOCK_CELL, EMPTY_CELL};
+    use crate::common_component::{GameOutcome, BOARD_COLS, BOARD_ROWS, DOT_SIZE, MAX_GRAVITY_G, SPAWN_X};
+    use crate::spawn_block_system::spawn_offset_y;
     use crate::tetromino::{Block, Rotation, State};
 
+    /// Holds a falling T at 20G with an O in hold; returns the held piece, the outcome and where the O came in
+    fn hold_at_20g(board_matrix: [[i8; BOARD_COLS]; BOARD_ROWS]) -> (Option<char>, GameOutcome, Option<f32>) {
+        let mut world = World::new();
+        world.init_resource::<NextState<GameState>>();
+        let input = PieceInput {
+            hold: true,
+            ..Default::default()
+        };
+        let game_data = GameData {
+            board_matrix,
+            gravity: MAX_GRAVITY_G,
+            held_block: Some(Block::new_o()),
+            ..Default::default()
+        };
+        let board = world.spawn((game_data, input)).id();
+        world.spawn((Block::new_t(), ActiveBlock)).set_parent(board);
+        world.run_system_once(hold_block_system).expect("the system runs");
+
+        let mut pieces = world.query_filtered::<(&Block, &Transform), With<ActiveBlock>>();
+        let o = pieces.iter(&world).find(|(block, _)| matches!(block, Block::O { .. }));
+        let o_y = o.map(|(_, transform)| transform.translation.y);
+        let game_data = world.get::<GameData>(board).expect("a board");
+        (game_data.held_block.as_ref().map(Block::letter), game_data.outcome, o_y)
+    }
+
+    #[test]
+    fn the_piece_out_of_hold_comes_in_like_a_dealt_one() {
+        let empty = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+        let (held, outcome, o_y) = hold_at_20g(empty);
+        assert_eq!((held, outcome), (Some('T'), GameOutcome::Playing));
+        // At 20G it is already on the floor
+        let o = Block::new_o();
+        let spawn_y = DOT_SIZE * 1.5 + spawn_offset_y(&o);
+        assert_eq!(o_y, Some(landing_y(&empty, SPAWN_X, spawn_y, &o)));
+        assert!(o_y < Some(spawn_y));
+
+        // With the spawn cells taken it tops out instead of overlapping the stack
+        let full = [[BLOCK_CELL; BOARD_COLS]; BOARD_ROWS];
+        let (_, outcome, o_y) = hold_at_20g(full);
+        assert_eq!(outcome, GameOutcome::TopOut);
+        assert_eq!(o_y, None);
+    }
+
     #[test]
     fn hold_resets_rotation() {
         let mut block = Block::new_t();
@@ -165,6 +210,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn hold_swap_returns_previous() {
         let mut game_data = GameData::default();
         game_data.held_block = Some(Block::new_i());
@@ -185,6 +231,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn hold_used_resets_on_placement() {
         let mut game_data = GameData::default();
         game_data.hold_used = true;
@@ -0,0 +1,283 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use bevy::prelude::{Res, ResMut, Resource};
+use bevy::time::Time;
+
+use crate::attack::RuleSet;
+use crate::game_mode::{GameMode, ModeSettings, OnlineRole, DEFAULT_ONLINE_PORT};
+use crate::online::{OnlineSession, PROTOCOL_VERSION};
+
+/// Ports searched for hosted games. A host answers on the UDP port with the number of its TCP port,
+/// so several games hosted on one machine (on different ports) are all found.
+pub(crate) const DISCOVERY_PORTS: RangeInclusive<u16> = DEFAULT_ONLINE_PORT..=DEFAULT_ONLINE_PORT + 7;
+const SEARCH: &str = "TETRIS?";
+const ANSWER: &str = "TETRIS";
+const SEARCH_INTERVAL: Duration = Duration::from_secs(1);
+/// A game that stops answering for this long drops off the list
+const GAME_EXPIRY: Duration = Duration::from_secs(3);
+const MAX_NAME_LEN: usize = 16;
+
+/// A hosted game found on the local network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LanGame {
+    pub address: SocketAddr,
+    pub name: String,
+    pub rules: RuleSet,
+    /// Picked at random by the hosting game, so the answers it sends to several searches are known as one game
+    pub instance: u64,
+}
+
+/// The name a hosted game is listed under: the user's login name
+pub(crate) fn player_name() -> String {
+    let name: String = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_NAME_LEN)
+        .collect();
+    if name.trim().is_empty() {
+        "Tetris".to_string()
+    } else {
+        name
+    }
+}
+
+fn answer_line(port: u16, rules: RuleSet, instance: u64, name: &str) -> String {
+    format!("{ANSWER} {PROTOCOL_VERSION} {port} {} {instance} {name}", rules.name())
+}
+
+/// The game described by an answer from `from`; answers from other versions are ignored
+fn parse_answer(line: &str, from: SocketAddr) -> Option<LanGame> {
+    let mut words = line.splitn(6, ' ');
+    if words.next()? != ANSWER || words.next()?.parse::<u32>().ok()? != PROTOCOL_VERSION {
+        return None;
+    }
+    let port = words.next()?.parse().ok()?;
+    let rules = RuleSet::from_name(words.next()?)?;
+    let instance = words.next()?.parse().ok()?;
+    Some(LanGame {
+        address: SocketAddr::new(from.ip(), port),
+        name: words.next().unwrap_or_default().to_string(),
+        rules,
+        instance,
+    })
+}
+
+/// Answers searches for a hosted game while it waits for an opponent
+pub(crate) struct Beacon {
+    socket: UdpSocket,
+    answer: String,
+}
+
+impl Beacon {
+    pub fn new(port: u16, rules: RuleSet, name: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            answer: answer_line(port, rules, rand::random(), name),
+        })
+    }
+
+    pub fn answer_searches(&self) {
+        let mut buffer = [0; 64];
+        while let Ok((read, from)) = self.socket.recv_from(&mut buffer) {
+            if &buffer[..read] == SEARCH.as_bytes() {
+                let _ = self.socket.send_to(self.answer.as_bytes(), from);
+            }
+        }
+    }
+}
+
+/// Broadcasts a search to the discovery ports every second and collects the answers
+pub(crate) struct Finder {
+    socket: UdpSocket,
+    ports: RangeInclusive<u16>,
+    /// Games that answered, with when they last did
+    found: Vec<(LanGame, Duration)>,
+    last_search: Option<Duration>,
+}
+
+impl Finder {
+    pub fn new(ports: RangeInclusive<u16>) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            ports,
+            found: Vec::new(),
+            last_search: None,
+        })
+    }
+
+    /// Searches when it is time to and takes in answers; `now` is any clock that only moves forward
+    pub fn update(&mut self, now: Duration) {
+        if self.last_search.is_none_or(|last| now >= last + SEARCH_INTERVAL) {
+            self.last_search = Some(now);
+            // The loopback search finds games on this machine even where broadcasts do not come back
+            for port in self.ports.clone() {
+                for ip in [Ipv4Addr::BROADCAST, Ipv4Addr::LOCALHOST] {
+                    let _ = self.socket.send_to(SEARCH.as_bytes(), (ip, port));
+                }
+            }
+        }
+        let mut buffer = [0; 256];
+        while let Ok((read, from)) = self.socket.recv_from(&mut buffer) {
+            if let Some(game) = parse_answer(&String::from_utf8_lossy(&buffer[..read]), from) {
+                self.take_in(game, now);
+            }
+        }
+        self.found.retain(|(_, seen)| now.saturating_sub(*seen) < GAME_EXPIRY);
+        self.found.sort_by_key(|(game, _)| game.address);
+    }
+
+    /// Lists a game that answered. A game on this machine answers both the broadcast and the loopback search;
+    /// it is listed once, under the address other machines reach it at when that answer came.
+    fn take_in(&mut self, mut game: LanGame, now: Duration) {
+        if let Some(index) = self.found.iter().position(|(known, _)| known.instance == game.instance) {
+            let (known, _) = self.found.remove(index);
+            if game.address.ip().is_loopback() && !known.address.ip().is_loopback() {
+                game.address = known.address;
+            }
+        }
+        self.found.push((game, now));
+    }
+
+    pub fn games(&self) -> impl Iterator<Item = &LanGame> {
+        self.found.iter().map(|(game, _)| game)
+    }
+}
+
+/// The search for hosted games, running while the Join lobby waits for the player to pick one
+#[derive(Resource, Default)]
+pub(crate) struct LanDiscovery {
+    finder: Option<Finder>,
+    pub games: Vec<LanGame>,
+}
+
+/// Keeps the list of games on the local network fresh while joining, and offers their addresses
+pub(crate) fn lan_discovery_system(
+    time: Res<Time>,
+    mode: Res<GameMode>,
+    session: Res<OnlineSession>,
+    mut settings: ResMut<ModeSettings>,
+    mut discovery: ResMut<LanDiscovery>,
+) {
+    let searching = *mode == GameMode::Online && settings.online_role == OnlineRole::Join && session.is_closed();
+    if !searching {
+        if discovery.finder.is_some() {
+            *discovery = LanDiscovery::default();
+        }
+        if !settings.lan_games.is_empty() {
+            settings.lan_games.clear();
+        }
+        return;
+    }
+    if discovery.finder.is_none() {
+        discovery.finder = Finder::new(DISCOVERY_PORTS).ok();
+    }
+    let Some(finder) = discovery.finder.as_mut() else {
+        return;
+    };
+    finder.update(time.elapsed());
+    let games: Vec<LanGame> = finder.games().cloned().collect();
+    if discovery.games != games {
+        settings.lan_games = games.iter().map(|game| game.address.to_string()).collect();
+        discovery.games = games;
+    }
+}
+
+/// The found games, one per line, marking the one whose address is entered
+pub(crate) fn lan_games_text(games: &[LanGame], address: &str) -> String {
+    if games.is_empty() {
+        return "No games found on this network yet".to_string();
+    }
+    let mut lines = vec!["Games on this network (Left/Right on Address):".to_string()];
+    for game in games {
+        let marker = if game.address.to_string() == address { ">" } else { " " };
+        lines.push(format!("{marker} {} - {} - {}", game.name, game.rules.name(), game.address));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_name_the_game_at_the_senders_address() {
+        let from: SocketAddr = "192.168.1.20:50000".parse().unwrap();
+        let game = parse_answer(&answer_line(7879, RuleSet::Classic, 42, "Ada L"), from).expect("a valid answer");
+        assert_eq!(game.address, "192.168.1.20:7879".parse().unwrap());
+        assert_eq!(game.name, "Ada L");
+        assert_eq!(game.rules, RuleSet::Classic);
+        assert_eq!(game.instance, 42);
+        assert_eq!(parse_answer("TETRIS 0 7878 Guideline 42 Old", from), None);
+        assert_eq!(parse_answer("TETRIS? ", from), None);
+    }
+
+    #[test]
+    fn a_search_finds_a_game_hosted_on_this_machine() {
+        let port = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+        let beacon = Beacon::new(port, RuleSet::Guideline, "Host").expect("a free port");
+        let mut finder = Finder::new(port..=port).expect("a socket");
+        let mut now = Duration::ZERO;
+        for _ in 0..200 {
+            finder.update(now);
+            beacon.answer_searches();
+            if finder.games().next().is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+            now += Duration::from_millis(10);
+        }
+        let game = finder.games().next().expect("the beacon answered").clone();
+        assert_eq!(game.address.port(), port);
+        assert_eq!(game.name, "Host");
+
+        // Once the host stops answering, it drops off the list
+        drop(beacon);
+        finder.update(now + GAME_EXPIRY);
+        assert_eq!(finder.games().count(), 0);
+    }
+
+    #[test]
+    fn a_game_answering_twice_is_listed_once_at_its_network_address() {
+        let game = |address: &str| LanGame {
+            address: address.parse().unwrap(),
+            name: "Host".to_string(),
+            rules: RuleSet::Guideline,
+            instance: 7,
+        };
+        let mut finder = Finder::new(DISCOVERY_PORTS).expect("a socket");
+        finder.take_in(game("192.168.1.20:7878"), Duration::ZERO);
+        finder.take_in(game("127.0.0.1:7878"), Duration::ZERO);
+        assert_eq!(finder.games().cloned().collect::<Vec<_>>(), vec![game("192.168.1.20:7878")]);
+
+        // The loopback answer may come first
+        let mut finder = Finder::new(DISCOVERY_PORTS).expect("a socket");
+        finder.take_in(game("127.0.0.1:7878"), Duration::ZERO);
+        finder.take_in(game("192.168.1.20:7878"), Duration::ZERO);
+        assert_eq!(finder.games().cloned().collect::<Vec<_>>(), vec![game("192.168.1.20:7878")]);
+    }
+
+    #[test]
+    fn games_text_marks_the_entered_address() {
+        let games = vec![LanGame {
+            address: "10.0.0.2:7878".parse().unwrap(),
+            name: "Ada".to_string(),
+            rules: RuleSet::Guideline,
+            instance: 1,
+        }];
+        assert_eq!(
+            lan_games_text(&games, "10.0.0.2:7878"),
+            "Games on this network (Left/Right on Address):\n> Ada - Guideline - 10.0.0.2:7878"
+        );
+        assert_eq!(lan_games_text(&[], ""), "No games found on this network yet");
+    }
+}
@@ -0,0 +1,227 @@
+mod attack;
+mod background;
+pub mod board;
+mod bot;
+pub mod common_component;
+mod dig;
+mod drop;
+mod finesse;
+mod game_mode;
+mod game_state;
+mod garbage;
+mod ghost;
+mod hint;
+mod history;
+mod hold;
+mod lan;
+pub mod line_clear;
+mod marathon;
+mod master;
+mod move_gen;
+mod movement;
+mod online;
+mod player;
+mod practice;
+mod puzzle;
+mod records;
+mod rollback;
+mod rotation;
+mod side_board;
+pub mod spawn_block_system;
+mod spectate;
+mod sprint;
+mod start_screen;
+mod step;
+mod survival;
+pub mod tbp;
+pub mod tetromino;
+mod two_player;
+mod ultra;
+mod versus;
+mod zen;
+
+use bevy::app::{PreStartup, Startup, Update};
+#[cfg(feature = "bevy_dev_tools")]
+use bevy::prelude::info_once;
+use bevy::prelude::{
+    in_state, not, resource_equals, AppExtStates, Condition, IntoSystemConfigs, PluginGroup,
+};
+use bevy::utils::default;
+use bevy::window::Window;
+use bevy::{app::App, window::WindowPlugin, DefaultPlugins};
+#[cfg(debug_assertions)]
+use bevy::prelude::{Query, With};
+#[cfg(debug_assertions)]
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+#[cfg(debug_assertions)]
+use common_component::GameData;
+use common_component::GameState;
+use game_mode::{GameMode, ModeSettings};
+use spawn_block_system::update_preview_system;
+
+/// Builds the game and runs it until the window closes
+pub fn run() {
+    let puzzles = puzzle::PuzzlePack::load();
+
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(Window {
+            title: "Tetris".to_string(),
+            resolution: (800.0, 600.0).into(),
+            resizable: false,
+            #[cfg(target_arch = "wasm32")]
+            canvas: Some("#bevy-canvas".to_owned()),
+            #[cfg(target_arch = "wasm32")]
+            prevent_default_event_handling: false,
+            ..Default::default()
+        }),
+        ..default()
+    }))
+    .add_systems(Update, game_state::update_score_display)
+    .add_systems(Update, hold::update_hold_preview_system)
+    .init_resource::<hold::HoldTracker>()
+    .init_resource::<attack::AttackTable>()
+    .init_resource::<online::OnlineSession>()
+    .init_resource::<rollback::Rollback>()
+    .init_resource::<lan::LanDiscovery>()
+    .init_resource::<spectate::SpectatorServer>()
+    .init_resource::<spectate::Spectator>()
+    .add_systems(PreStartup, background::setup_background)
+    .add_systems(Startup, player::spawn_main_board)
+    .add_systems(Update, background::setup_background_grid)
+    .init_resource::<GameMode>()
+    .insert_resource(ModeSettings::with_puzzle_labels(puzzles.labels()).with_external_bot(tbp::bot_command()))
+    .insert_resource(puzzles)
+    .insert_resource(records::Records::load())
+    .init_resource::<sprint::SprintResult>()
+    .init_resource::<ultra::UltraResult>()
+    .init_resource::<dig::DigResult>()
+    .init_state::<GameState>()
+    .add_systems(bevy::prelude::OnEnter(GameState::StartScreen), start_screen::start_screen_display_system)
+    .add_systems(
+        Update,
+        (
+            start_screen::start_screen_input_system,
+            start_screen::address_input_system,
+            online::online_lobby_system,
+            lan::lan_discovery_system,
+            spectate::watch_lobby_system,
+            start_screen::start_screen_menu_display_system,
+            online::online_lobby_display_system,
+            spectate::watch_lobby_display_system,
+        )
+            .chain()
+            .run_if(in_state(GameState::StartScreen)),
+    )
+    .add_schedule(step::game_step_schedule())
+    .add_systems(Update, step::game_step_system.run_if(not(resource_equals(GameMode::Online))))
+    .add_systems(Update, spectate::spectator_broadcast_system)
+    .add_systems(bevy::prelude::OnEnter(GameState::Watching), spectate::spawn_spectator_banner)
+    .add_systems(
+        Update,
+        (spectate::spectate_system, update_preview_system).chain().run_if(in_state(GameState::Watching)),
+    )
+    .add_systems(bevy::prelude::OnExit(GameState::Watching), spectate::stop_watching_system)
+    .add_systems(Update, bot::demo_toggle_system)
+    .add_systems(Update, (hint::hint_toggle_system, hint::update_hint_system.run_if(in_state(GameState::Playing))))
+    .add_systems(Update, finesse::update_finesse_display)
+    .add_systems(Update, attack::update_garbage_meter)
+    .add_systems(
+        Update,
+        two_player::update_second_board_view.run_if(
+            resource_equals(GameMode::TwoPlayer)
+                .or(resource_equals(GameMode::Versus))
+                .or(resource_equals(GameMode::Online))
+                .and(not(in_state(GameState::StartScreen))),
+        ),
+    )
+    .add_systems(
+        Update,
+        (
+            online::online_setup_system,
+            online::online_sync_system,
+            rollback::rollback_system,
+            online::online_send_system,
+        )
+            .chain()
+            .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Online))),
+    )
+    .add_systems(
+        Update,
+        online::online_rematch_system
+            .before(game_state::restart_system)
+            .run_if(in_state(GameState::GameOver).and(resource_equals(GameMode::Online))),
+    )
+    .add_systems(
+        bevy::prelude::OnEnter(GameState::StartScreen),
+        (side_board::side_board_cleanup_system, player::extra_board_cleanup_system),
+    )
+    .add_systems(
+        Update,
+        ghost::update_ghost_piece_system.run_if(in_state(GameState::Playing).or(in_state(GameState::Watching))),
+    )
+    .add_systems(Update, sprint::update_splits_display)
+    // The opponent of an online game keeps playing, so there is no pausing it
+    .add_systems(
+        Update,
+        game_state::pause_system.run_if(
+            in_state(GameState::Playing)
+                .or(in_state(GameState::Paused))
+                .and(not(resource_equals(GameMode::Online))),
+        ),
+    )
+    .add_systems(bevy::prelude::OnEnter(GameState::Paused), game_state::pause_display_system)
+    .add_systems(bevy::prelude::OnExit(GameState::Paused), game_state::unpause_cleanup_system)
+    .add_systems(
+        bevy::prelude::OnEnter(GameState::GameOver),
+        (game_state::settle_outcomes_system, game_state::game_over_display_system).chain(),
+    )
+    .add_systems(
+        Update,
+        game_state::restart_system.run_if(
+            in_state(GameState::GameOver).or(in_state(GameState::Paused).and(resource_equals(GameMode::Zen))),
+        ),
+    );
+
+    #[cfg(debug_assertions)]
+    app.add_plugins(EguiPlugin)
+        .add_systems(Update, ui_example_system);
+
+    #[cfg(feature = "bevy_dev_tools")]
+    {
+        app.add_plugins(bevy::dev_tools::ui_debug_overlay::DebugUiPlugin)
+            .add_systems(Update, toggle_overlay);
+    }
+
+    app.run();
+}
+
+#[cfg(debug_assertions)]
+fn ui_example_system(mut contexts: EguiContexts, game_data: Query<&GameData, With<player::MainBoard>>) {
+    let Ok(game_data) = game_data.get_single() else {
+        return;
+    };
+    egui::Window::new("Hello").show(contexts.ctx_mut(), |ui| {
+        for line in game_data.board_matrix.iter() {
+            let mut line_str = String::new();
+            line.iter().for_each(|&x| match x {
+                board::BLOCK_CELL => line_str.push_str("|■"),
+                board::GARBAGE_CELL => line_str.push_str("|▒"),
+                _ => line_str.push_str("|□"),
+            });
+            line_str.push('|');
+            ui.label(line_str);
+        }
+    });
+}
+
+#[cfg(feature = "bevy_dev_tools")]
+fn toggle_overlay(
+    input: Res<bevy::input::ButtonInput<bevy::prelude::KeyCode>>,
+    mut options: bevy::prelude::ResMut<bevy::dev_tools::ui_debug_overlay::UiDebugOptions>,
+) {
+    info_once!("The debug outlines are enabled, press Space to turn them on/off");
+    if input.just_pressed(bevy::prelude::KeyCode::Space) {
+        options.toggle();
+    }
+}
@@ -1,22 +1,39 @@
 use std::collections::HashMap;
 
-use bevy::prelude::{Commands, Entity, Query, ResMut, Transform};
+use bevy::color::Alpha;
+use bevy::math::Vec3;
+use bevy::prelude::{Commands, DespawnRecursiveExt, Entity, Parent, Query, Res, Transform};
+use bevy::sprite::Sprite;
+use bevy::time::Time;
 
-use crate::board::BoardDot;
-use crate::common_component::{GameData, BOARD_COLS, BOARD_ROWS, DOT_SIZE};
-use crate::drop::gravity_seconds;
+use crate::board::{BoardDot, EMPTY_CELL};
+use crate::common_component::{GameData, LineClear, BOARD_COLS, BOARD_ROWS, DOT_SIZE};
+use crate::drop::gravity_g;
+use crate::game_mode::{GameMode, LevelProgression, ModeSettings};
+use crate::marathon::advance_level;
+use crate::master;
 
+/// Settled cells of every board
+type BoardDotQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static Parent, &'static mut Transform, &'static mut Sprite, &'static BoardDot)>;
+
+/// Detects completed rows after a lock, awards the clear and starts the line clear delay.
+/// The rows stay on the board until `line_clear_delay_system` removes them.
 pub(crate) fn eliminate_line_system(
-    mut commands: Commands,
-    mut game_data: ResMut<GameData>,
-    mut board_dot_query: Query<((Entity, &mut Transform), &BoardDot)>,
+    mut boards: Query<&mut GameData>,
+    mode: Res<GameMode>,
+    settings: Res<ModeSettings>,
 ) {
-    let mut line_indices_to_eliminate: Vec<usize> = Vec::new();
-    for (i, line) in game_data.board_matrix.iter().enumerate() {
-        if line.iter().all(|&x| x == 1) {
-            line_indices_to_eliminate.push(i);
+    for mut game_data in boards.iter_mut() {
+        if game_data.clearing_rows.is_empty() {
+            detect_clear(&mut game_data, *mode, &settings);
         }
     }
+}
+
+/// Awards the clear made by the last lock on one board, if any
+fn detect_clear(game_data: &mut GameData, mode: GameMode, settings: &ModeSettings) {
+    let line_indices_to_eliminate = full_rows(&game_data.board_matrix);
     if line_indices_to_eliminate.is_empty() {
         return;
     }
@@ -24,19 +41,79 @@ pub(crate) fn eliminate_line_system(
     // Award points for line clears
     let lines_count = line_indices_to_eliminate.len();
     let points = line_clear_points(lines_count);
-    game_data.score += points * game_data.level;
+    // Master counts levels from 0
+    game_data.score += points * game_data.level.max(1);
     game_data.lines_cleared += lines_count as u32;
-    game_data.level = (game_data.lines_cleared / 10) + 1;
-    let new_duration = std::time::Duration::from_secs_f32(gravity_seconds(game_data.level));
-    game_data.drop_timer.set_duration(new_duration);
+    if mode.levels_up() {
+        let progression = match mode {
+            GameMode::Marathon => settings.progression,
+            _ => LevelProgression::Fixed,
+        };
+        advance_level(game_data, progression, lines_count);
+        game_data.gravity = gravity_g(game_data.level);
+    } else if mode == GameMode::Master {
+        master::advance_level_on_clear(game_data, lines_count);
+    }
+
+    game_data.last_clear = Some(LineClear {
+        lines: lines_count as u32,
+        t_spin: game_data.t_spin,
+        perfect_clear: leaves_empty_board(&game_data.board_matrix),
+    });
+
+    game_data.clearing_rows = line_indices_to_eliminate;
+    game_data.line_clear_timer.reset();
+}
+
+/// Animates the rows being cleared (they shrink and fade out), then removes them,
+/// shifts the stack down and starts ARE.
+pub(crate) fn line_clear_delay_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut boards: Query<(Entity, &mut GameData)>,
+    mut board_dot_query: BoardDotQuery,
+) {
+    for (board, mut game_data) in boards.iter_mut() {
+        if game_data.clearing_rows.is_empty() {
+            continue;
+        }
+
+        game_data.line_clear_timer.tick(time.delta());
+        if !game_data.line_clear_timer.finished() {
+            let fade = 1.0 - game_data.line_clear_timer.fraction();
+            board_dot_query
+                .iter_mut()
+                .for_each(|(_entity, parent, mut transform, mut sprite, board_dot)| {
+                    if parent.get() == board && game_data.clearing_rows.contains(&(board_dot.board_y as usize)) {
+                        sprite.color.set_alpha(fade);
+                        transform.scale = Vec3::splat(fade);
+                    }
+                });
+            continue;
+        }
 
+        let line_indices_to_eliminate = std::mem::take(&mut game_data.clearing_rows);
+        remove_rows(&mut commands, board, &mut game_data, &mut board_dot_query, &line_indices_to_eliminate);
+
+        game_data.entry_delay_active = true;
+        game_data.entry_delay_timer.reset();
+    }
+}
+
+fn remove_rows(
+    commands: &mut Commands,
+    board: Entity,
+    game_data: &mut GameData,
+    board_dot_query: &mut BoardDotQuery,
+    line_indices_to_eliminate: &[usize],
+) {
     for index in line_indices_to_eliminate.iter() {
         game_data.board_matrix[*index] = [0; BOARD_COLS];
         board_dot_query
             .iter()
-            .for_each(|((entity, _transform), board_dot)| {
-                if board_dot.board_y == *index as i8 {
-                    commands.entity(entity).despawn();
+            .for_each(|(entity, parent, _transform, _sprite, board_dot)| {
+                if parent.get() == board && board_dot.board_y == *index as i8 {
+                    commands.entity(entity).despawn_recursive();
                 }
             });
     }
@@ -44,7 +121,7 @@ pub(crate) fn eliminate_line_system(
     let mut is_reached_dot_line: bool = false;
     let mut empty_lines: Vec<usize> = Vec::new();
     for (i, line) in game_data.board_matrix.iter().enumerate() {
-        if line.contains(&1) {
+        if line.iter().any(|&x| x != EMPTY_CELL) {
             is_reached_dot_line = true;
         }
         if !is_reached_dot_line {
@@ -73,8 +150,8 @@ pub(crate) fn eliminate_line_system(
     if !line_change_map.is_empty() {
         board_dot_query
             .iter_mut()
-            .for_each(|((entity, mut transform), board_dot)| {
-                if line_change_map.contains_key(&(board_dot.board_y as usize)) {
+            .for_each(|(entity, parent, mut transform, _sprite, board_dot)| {
+                if parent.get() == board && line_change_map.contains_key(&(board_dot.board_y as usize)) {
                     let line_change_times = line_change_map[&(board_dot.board_y as usize)];
                     transform.translation.y -= DOT_SIZE * line_change_times as f32;
                     commands.entity(entity).insert(BoardDot {
@@ -86,7 +163,23 @@ pub(crate) fn eliminate_line_system(
     }
 }
 
-fn line_clear_points(lines: usize) -> u32 {
+/// Whether removing the full rows would leave nothing on the board (a perfect clear)
+pub(crate) fn leaves_empty_board(board: &[[i8; BOARD_COLS]; BOARD_ROWS]) -> bool {
+    board
+        .iter()
+        .all(|line| line.iter().all(|&x| x != EMPTY_CELL) || line.iter().all(|&x| x == EMPTY_CELL))
+}
+
+fn full_rows(board: &[[i8; BOARD_COLS]; BOARD_ROWS]) -> Vec<usize> {
+    board
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.iter().all(|&x| x != EMPTY_CELL))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+pub(crate) fn line_clear_points(lines: usize) -> u32 {
     match lines {
         1 => 100,
         2 => 300,
@@ -113,6 +206,28 @@ fn eliminate_line_inner(board: &mut [[i8; BOARD_COLS]; BOARD_ROWS], i: usize) {
     eliminate_line_inner(board, i - 1);
 }
 
+/// Removes every full row at once and drops the rows above into place; for boards that
+/// are not animated (the CPU opponent's). Returns the number of rows removed.
+pub fn collapse_full_rows(board: &mut [[i8; BOARD_COLS]; BOARD_ROWS]) -> usize {
+    let kept: Vec<[i8; BOARD_COLS]> = board.iter().filter(|line| line.contains(&EMPTY_CELL)).copied().collect();
+    let cleared = BOARD_ROWS - kept.len();
+    *board = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+    board[cleared..].copy_from_slice(&kept);
+    cleared
+}
+
+/// Pushes `rows` in under the stack, moving everything else up. Returns false when
+/// filled cells were pushed out of the top of the board (a top-out).
+pub(crate) fn insert_rows_at_bottom(board: &mut [[i8; BOARD_COLS]; BOARD_ROWS], rows: &[[i8; BOARD_COLS]]) -> bool {
+    let count = rows.len().min(BOARD_ROWS);
+    let overflow = board[..count].iter().any(|line| line.iter().any(|&x| x != EMPTY_CELL));
+
+    board.copy_within(count.., 0);
+    board[BOARD_ROWS - count..].copy_from_slice(&rows[rows.len() - count..]);
+
+    !overflow
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -142,6 +257,19 @@ mod tests {
         assert_eq!(line_clear_points(0), 0);
     }
 
+    #[test]
+    fn collapse_removes_full_rows_and_keeps_the_rest_in_order() {
+        let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        board[19] = [1; BOARD_COLS];
+        board[18][0] = 2;
+        board[17] = [1; BOARD_COLS];
+        board[16][3] = 1;
+        assert_eq!(collapse_full_rows(&mut board), 2);
+        assert_eq!(board[19][0], 2);
+        assert_eq!(board[18][3], 1);
+        assert!(board[..18].iter().all(|line| line.iter().all(|&x| x == 0)));
+    }
+
     #[test]
     fn eliminate_inner_shifts_line_down() {
         let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
@@ -181,6 +309,26 @@ mod tests {
         assert_eq!(board[16], [1; BOARD_COLS]);
     }
 
+    #[test]
+    fn insert_rows_pushes_stack_up() {
+        let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        board[19] = [1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+        let garbage = [[2, 2, 2, 2, 0, 2, 2, 2, 2, 2]];
+        assert!(insert_rows_at_bottom(&mut board, &garbage));
+        assert_eq!(board[19], garbage[0]);
+        assert_eq!(board[18], [1, 1, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(board[17], [0; BOARD_COLS]);
+    }
+
+    #[test]
+    fn insert_rows_reports_overflow() {
+        let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        board[1][4] = 1;
+        let garbage = [[2; BOARD_COLS], [2; BOARD_COLS]];
+        assert!(!insert_rows_at_bottom(&mut board, &garbage));
+        assert_eq!(board[19], [2; BOARD_COLS]);
+    }
+
     #[test]
     fn line_detection_finds_full_rows() {
         let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
@@ -198,4 +346,37 @@ mod tests {
 
         assert_eq!(full_lines, vec![18, 19]);
     }
+
+    #[test]
+    fn full_rows_skips_partial_rows() {
+        let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        board[19] = [1; BOARD_COLS];
+        board[18] = [1; BOARD_COLS];
+        board[17] = [1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(full_rows(&board), vec![18, 19]);
+    }
+
+    #[test]
+    fn full_rows_counts_garbage_cells() {
+        let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        board[19] = [2; BOARD_COLS];
+        board[19][3] = 1;
+        assert_eq!(full_rows(&board), vec![19]);
+    }
+
+    #[test]
+    fn perfect_clear_only_when_nothing_is_left() {
+        let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        board[18] = [1; BOARD_COLS];
+        board[19] = [2; BOARD_COLS];
+        assert!(leaves_empty_board(&board));
+        board[17][0] = 1;
+        assert!(!leaves_empty_board(&board));
+    }
+
+    #[test]
+    fn full_rows_empty_board() {
+        let board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        assert!(full_rows(&board).is_empty());
+    }
 }
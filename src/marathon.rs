@@ -0,0 +1,98 @@
+use bevy::prelude::{NextState, Query, Res, ResMut};
+
+use crate::common_component::{GameData, GameOutcome, GameState};
+use crate::game_mode::{format_time, LevelProgression, ModeSettings};
+
+const FIXED_GOAL_LINES: u32 = 10;
+const VARIABLE_GOAL_UNITS_PER_LEVEL: u32 = 5;
+
+/// Line clear units awarded toward the variable goal
+fn goal_units(lines: usize) -> u32 {
+    match lines {
+        1 => 1,
+        2 => 3,
+        3 => 5,
+        4 => 8,
+        _ => 0,
+    }
+}
+
+/// Raises the level after a clear of `lines` rows according to the chosen progression
+pub(crate) fn advance_level(game_data: &mut GameData, progression: LevelProgression, lines: usize) {
+    match progression {
+        LevelProgression::Fixed => {
+            game_data.level = game_data.start_level + game_data.lines_cleared / FIXED_GOAL_LINES;
+        }
+        LevelProgression::Variable => {
+            game_data.goal_units += goal_units(lines);
+            while game_data.goal_units >= VARIABLE_GOAL_UNITS_PER_LEVEL * game_data.level {
+                game_data.goal_units -= VARIABLE_GOAL_UNITS_PER_LEVEL * game_data.level;
+                game_data.level += 1;
+            }
+        }
+    }
+}
+
+/// Finishes Marathon once the line goal is reached (never for endless)
+pub(crate) fn marathon_goal_system(
+    mut boards: Query<&mut GameData>,
+    settings: Res<ModeSettings>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(goal) = settings.marathon_goal else {
+        return;
+    };
+    for mut game_data in boards.iter_mut() {
+        if game_data.lines_cleared >= goal {
+            game_data.outcome = GameOutcome::Cleared;
+            next_state.set(GameState::GameOver);
+        }
+    }
+}
+
+pub(crate) fn marathon_result_lines(game_data: &GameData) -> Vec<String> {
+    vec![
+        format!("Score: {}", game_data.score),
+        format!("Level: {}", game_data.level),
+        format!("Time: {}", format_time(game_data.play_time.elapsed())),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_goal_counts_from_start_level() {
+        let mut game_data = GameData::with_start_level(5);
+        game_data.lines_cleared = 9;
+        advance_level(&mut game_data, LevelProgression::Fixed, 1);
+        assert_eq!(game_data.level, 5);
+        game_data.lines_cleared = 10;
+        advance_level(&mut game_data, LevelProgression::Fixed, 1);
+        assert_eq!(game_data.level, 6);
+    }
+
+    #[test]
+    fn variable_goal_needs_five_units_per_level() {
+        let mut game_data = GameData::with_start_level(1);
+        // Level 1 needs 5 units: a tetris (8) carries 3 over into level 2
+        advance_level(&mut game_data, LevelProgression::Variable, 4);
+        assert_eq!(game_data.level, 2);
+        assert_eq!(game_data.goal_units, 3);
+        // Level 2 needs 10 units: 3 + 5 (triple) = 8
+        advance_level(&mut game_data, LevelProgression::Variable, 3);
+        assert_eq!(game_data.level, 2);
+        advance_level(&mut game_data, LevelProgression::Variable, 2);
+        assert_eq!(game_data.level, 3);
+        assert_eq!(game_data.goal_units, 1);
+    }
+
+    #[test]
+    fn goal_units_follow_guideline() {
+        assert_eq!(goal_units(1), 1);
+        assert_eq!(goal_units(2), 3);
+        assert_eq!(goal_units(3), 5);
+        assert_eq!(goal_units(4), 8);
+    }
+}
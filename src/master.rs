@@ -0,0 +1,201 @@
+use std::time::Duration;
+
+use bevy::prelude::{Added, NextState, Parent, Query, ResMut};
+use bevy::time::Timer;
+
+use crate::common_component::{ActiveBlock, GameData, GameOutcome, GameState, FRAME_RATE, MAX_GRAVITY_G};
+use crate::game_mode::format_time;
+
+pub const MASTER_MAX_LEVEL: u32 = 999;
+const SECTION_LEVELS: u32 = 100;
+
+/// Gravity by level in 1/256 G, from TGM's Master curve: `(from level, gravity)`
+const GRAVITY_CURVE: [(u32, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 5120),
+];
+
+/// Speed settings for one point of the Master curve; delays are in frames at 60 fps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SectionTiming {
+    pub gravity_g: f32,
+    pub are: u32,
+    pub das: u32,
+    pub lock_delay: u32,
+    pub line_clear: u32,
+}
+
+pub(crate) fn master_gravity_g(level: u32) -> f32 {
+    let units = GRAVITY_CURVE
+        .iter()
+        .rev()
+        .find(|(from, _)| level >= *from)
+        .map_or(GRAVITY_CURVE[0].1, |(_, units)| *units);
+    (units as f32 / 256.0).min(MAX_GRAVITY_G)
+}
+
+/// TGM-style timing: gravity follows its own curve up to 20G at level 500,
+/// then every section from 500 on shortens the delays
+pub(crate) fn master_timing(level: u32) -> SectionTiming {
+    let (are, das, lock_delay, line_clear) = match level {
+        0..=499 => (25, 14, 30, 40),
+        500..=599 => (25, 8, 30, 25),
+        600..=699 => (25, 8, 30, 16),
+        700..=799 => (16, 8, 30, 12),
+        800..=899 => (12, 8, 30, 6),
+        _ => (12, 6, 17, 6),
+    };
+    SectionTiming {
+        gravity_g: master_gravity_g(level),
+        are,
+        das,
+        lock_delay,
+        line_clear,
+    }
+}
+
+/// Level shown as the goal of the current section (100, 200, ... 999)
+pub(crate) fn section_goal(level: u32) -> u32 {
+    ((level / SECTION_LEVELS + 1) * SECTION_LEVELS).min(MASTER_MAX_LEVEL)
+}
+
+/// Each new piece raises the level by one, except at a section stop (x99 and 998),
+/// which only a line clear can get past
+fn advance_level_on_piece(game_data: &mut GameData) {
+    let level = game_data.level;
+    if level % SECTION_LEVELS != SECTION_LEVELS - 1 && level < MASTER_MAX_LEVEL - 1 {
+        game_data.level += 1;
+    }
+}
+
+/// Each cleared line raises the level by one
+pub(crate) fn advance_level_on_clear(game_data: &mut GameData, lines: usize) {
+    game_data.level = (game_data.level + lines as u32).min(MASTER_MAX_LEVEL);
+}
+
+/// Counts new pieces toward the level and ends the game at level 999
+pub(crate) fn master_level_system(
+    mut boards: Query<&mut GameData>,
+    spawned: Query<&Parent, Added<ActiveBlock>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for parent in spawned.iter() {
+        if let Ok(mut game_data) = boards.get_mut(parent.get()) {
+            advance_level_on_piece(&mut game_data);
+        }
+    }
+    for mut game_data in boards.iter_mut() {
+        if game_data.level >= MASTER_MAX_LEVEL {
+            game_data.outcome = GameOutcome::Cleared;
+            next_state.set(GameState::GameOver);
+        }
+    }
+}
+
+/// Applies the speed of the current level to gravity, ARE, DAS, lock delay and line clear delay
+pub(crate) fn master_timing_system(mut boards: Query<&mut GameData>) {
+    for mut game_data in boards.iter_mut() {
+        let timing = master_timing(game_data.level);
+        game_data.gravity = timing.gravity_g;
+        set_frames(&mut game_data.entry_delay_timer, timing.are);
+        set_frames(&mut game_data.das_timer, timing.das);
+        set_frames(&mut game_data.lock_delay_timer, timing.lock_delay);
+        set_frames(&mut game_data.line_clear_timer, timing.line_clear);
+    }
+}
+
+fn set_frames(timer: &mut Timer, frames: u32) {
+    let duration = Duration::from_secs_f32(frames as f32 / FRAME_RATE);
+    if timer.duration() != duration {
+        timer.set_duration(duration);
+    }
+}
+
+pub(crate) fn master_result_lines(game_data: &GameData) -> Vec<String> {
+    vec![
+        format!("Level: {}", game_data.level),
+        format!("Time: {}", format_time(game_data.play_time.elapsed())),
+        format!("Lines: {}", game_data.lines_cleared),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gravity_starts_slow_and_reaches_20g_at_500() {
+        assert_eq!(master_gravity_g(0), 4.0 / 256.0);
+        assert_eq!(master_gravity_g(251), 1.0);
+        assert_eq!(master_gravity_g(499), 3.0);
+        assert_eq!(master_gravity_g(500), MAX_GRAVITY_G);
+        assert_eq!(master_gravity_g(999), MAX_GRAVITY_G);
+    }
+
+    #[test]
+    fn delays_shorten_by_section() {
+        let early = master_timing(100);
+        let late = master_timing(950);
+        assert!(late.are < early.are);
+        assert!(late.das < early.das);
+        assert!(late.lock_delay < early.lock_delay);
+        assert!(late.line_clear < early.line_clear);
+    }
+
+    #[test]
+    fn pieces_stop_at_section_end() {
+        let mut game_data = GameData {
+            level: 98,
+            ..Default::default()
+        };
+        advance_level_on_piece(&mut game_data);
+        assert_eq!(game_data.level, 99);
+        advance_level_on_piece(&mut game_data);
+        assert_eq!(game_data.level, 99);
+        advance_level_on_clear(&mut game_data, 2);
+        assert_eq!(game_data.level, 101);
+    }
+
+    #[test]
+    fn level_caps_at_999() {
+        let mut game_data = GameData {
+            level: 997,
+            ..Default::default()
+        };
+        advance_level_on_piece(&mut game_data);
+        advance_level_on_piece(&mut game_data);
+        assert_eq!(game_data.level, 998);
+        advance_level_on_clear(&mut game_data, 4);
+        assert_eq!(game_data.level, MASTER_MAX_LEVEL);
+        assert_eq!(section_goal(950), MASTER_MAX_LEVEL);
+        assert_eq!(section_goal(123), 200);
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::board::{board_cell_position, fits, get_object_position_in_board, is_t_spin_position, Board, Placement};
+use crate::common_component::{DOT_SIZE, SPAWN_X};
+use crate::rotation::get_kick_offsets;
+use crate::spawn_block_system::spawn_offset_y;
+use crate::tetromino;
+
+/// One input of a path from spawn. `SoftDrop` moves the piece down a single row; `HardDrop` ends
+/// every path and locks the piece where it lands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Input {
+    Left,
+    Right,
+    RotateCw,
+    RotateCcw,
+    SoftDrop,
+    HardDrop,
+}
+
+/// A placement a piece can reach from spawn, with the shortest input path that reaches it
+#[derive(Clone)]
+pub struct Reachable {
+    pub placement: Placement,
+    pub path: Vec<Input>,
+    /// The piece is a T that locks right after a rotation, with three corners filled
+    pub t_spin: bool,
+}
+
+/// A piece on its way down: its origin cell and whether the last input turned it
+#[derive(Clone)]
+struct Position {
+    block: tetromino::Block,
+    column: i8,
+    row: i8,
+    rotated: bool,
+}
+
+impl Position {
+    fn key(&self) -> (i8, i8, u8, bool) {
+        (self.column, self.row, *self.block.state() as u8, self.rotated)
+    }
+}
+
+/// Origin cell of a freshly dealt piece, as `spawn_block_system` places it
+pub(crate) fn spawn_cell(block: &tetromino::Block) -> (i8, i8) {
+    get_object_position_in_board(SPAWN_X, DOT_SIZE * 1.5 + spawn_offset_y(block))
+}
+
+/// Where `input` takes the piece, using the game's movement and SRS kick rules; `None` when it is
+/// blocked. `HardDrop` is not a step: it ends a path.
+fn step(board: &Board, from: &Position, input: Input) -> Option<Position> {
+    let moved = |column: i8, row: i8| {
+        fits(board, &from.block, column, row, false).then(|| Position {
+            block: from.block.clone(),
+            column,
+            row,
+            rotated: false,
+        })
+    };
+    match input {
+        Input::Left => moved(from.column - 1, from.row),
+        Input::Right => moved(from.column + 1, from.row),
+        Input::SoftDrop => moved(from.column, from.row + 1),
+        Input::RotateCw | Input::RotateCcw => {
+            let mut block = from.block.clone();
+            let (old, new) = if input == Input::RotateCw {
+                tetromino::Rotation::rotate_right(&mut block)
+            } else {
+                tetromino::Rotation::rotate_left(&mut block)
+            };
+            // Kicks are in world space, where up is positive; board rows count downwards
+            get_kick_offsets(&block, old, new)
+                .into_iter()
+                .map(|(kick_x, kick_y)| (from.column + kick_x, from.row - kick_y))
+                .find(|&(column, row)| fits(board, &block, column, row, false))
+                .map(|(column, row)| Position {
+                    block: block.clone(),
+                    column,
+                    row,
+                    rotated: true,
+                })
+        }
+        Input::HardDrop => None,
+    }
+}
+
+/// Row the piece comes to rest on when dropped from `position`
+fn landing_row(board: &Board, position: &Position) -> i8 {
+    let mut row = position.row;
+    while fits(board, &position.block, position.column, row + 1, false) {
+        row += 1;
+    }
+    row
+}
+
+fn is_t_spin(board: &Board, position: &Position) -> bool {
+    let (x, y) = board_cell_position(position.column, position.row);
+    position.rotated && is_t_spin_position(board, x, y, &position.block)
+}
+
+/// Every placement `piece` can reach from its spawn position by shifting, rotating (with SRS kicks)
+/// and soft dropping one row at a time, including tucks under overhangs and spins, each with the
+/// fewest inputs that reach it. Gravity is left out: the player is assumed to be faster than it.
+pub(crate) fn reachable_placements(board: &Board, piece: &tetromino::Block) -> Vec<Reachable> {
+    let (column, row) = spawn_cell(piece);
+    if !fits(board, piece, column, row, false) {
+        return Vec::new();
+    }
+    let start = Position {
+        block: piece.clone(),
+        column,
+        row,
+        rotated: false,
+    };
+    let mut seen = HashSet::from([start.key()]);
+    let mut queue = VecDeque::from([(start, Vec::new())]);
+    let mut placed: HashSet<(i8, i8, u8, bool)> = HashSet::new();
+    let mut found = Vec::new();
+
+    while let Some((position, path)) = queue.pop_front() {
+        // Dropping from here lands somewhere; a piece already resting locks where it is, spin and all
+        let landing = landing_row(board, &position);
+        let t_spin = landing == position.row && is_t_spin(board, &position);
+        if placed.insert((position.column, landing, *position.block.state() as u8, t_spin)) {
+            let mut path = path.clone();
+            path.push(Input::HardDrop);
+            found.push(Reachable {
+                placement: Placement {
+                    block: position.block.clone(),
+                    column: position.column,
+                    row: landing,
+                },
+                path,
+                t_spin,
+            });
+        }
+
+        for input in [Input::Left, Input::Right, Input::RotateCw, Input::RotateCcw, Input::SoftDrop] {
+            let Some(next) = step(board, &position, input) else {
+                continue;
+            };
+            if seen.insert(next.key()) {
+                let mut path = path.clone();
+                path.push(input);
+                queue.push_back((next, path));
+            }
+        }
+    }
+    found
+}
+
+/// Origin row after each input of `path` when played from spawn, for following a path as it is
+/// pressed; stops at the first blocked input
+pub(crate) fn path_rows(board: &Board, piece: &tetromino::Block, path: &[Input]) -> Vec<i8> {
+    let (column, row) = spawn_cell(piece);
+    let mut position = Position {
+        block: piece.clone(),
+        column,
+        row,
+        rotated: false,
+    };
+    let mut rows = Vec::with_capacity(path.len());
+    for &input in path {
+        if input == Input::HardDrop {
+            rows.push(landing_row(board, &position));
+            break;
+        }
+        let Some(next) = step(board, &position, input) else {
+            break;
+        };
+        rows.push(next.row);
+        position = next;
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{place, placements, BLOCK_CELL, EMPTY_CELL};
+    use crate::common_component::{BOARD_COLS, BOARD_ROWS};
+    use crate::line_clear::collapse_full_rows;
+    use crate::tetromino::Block;
+
+    fn board_from(rows: &[&str]) -> Board {
+        let mut board = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+        for (i, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == '#' {
+                    board[BOARD_ROWS - rows.len() + i][x] = BLOCK_CELL;
+                }
+            }
+        }
+        board
+    }
+
+    /// Where a path ends when replayed input by input from spawn
+    fn replay(board: &Board, piece: &Block, path: &[Input]) -> (i8, i8, u8) {
+        let (column, row) = spawn_cell(piece);
+        let mut position = Position {
+            block: piece.clone(),
+            column,
+            row,
+            rotated: false,
+        };
+        for &input in path {
+            if input == Input::HardDrop {
+                return (position.column, landing_row(board, &position), *position.block.state() as u8);
+            }
+            position = step(board, &position, input).expect("every input of a path moves the piece");
+        }
+        panic!("a path ends with a hard drop");
+    }
+
+    #[test]
+    fn an_empty_board_offers_every_column_and_rotation() {
+        let board = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+        let found = reachable_placements(&board, &Block::new_t());
+        let mut ends = HashSet::new();
+        for reachable in &found {
+            let placement = &reachable.placement;
+            let end = (placement.column, placement.row, *placement.block.state() as u8);
+            assert_eq!(replay(&board, &Block::new_t(), &reachable.path), end);
+            ends.insert(end);
+            // Walls and floor count as corners, so only an upright T turned in against a wall spins here
+            if !reachable.t_spin {
+                assert!(!reachable.path.contains(&Input::SoftDrop), "a drop from above needs no soft drop");
+            }
+        }
+        // 8 columns flat (states 0 and 2), 9 columns upright (states 1 and 3)
+        assert_eq!(ends.len(), 34);
+    }
+
+    #[test]
+    fn tucks_under_an_overhang_are_found() {
+        let board = board_from(&["....######", "..........", "#########."]);
+        let covered = |placement: &Placement| {
+            placement.block.dots_by_state().iter().any(|dot| placement.column + dot.x >= 4)
+                && placement.block.dots_by_state().iter().all(|dot| placement.row + dot.y == BOARD_ROWS as i8 - 2)
+        };
+        assert!(!placements(&board, &Block::new_i()).iter().any(covered));
+        let tuck = reachable_placements(&board, &Block::new_i())
+            .into_iter()
+            .find(|reachable| covered(&reachable.placement))
+            .expect("the I tucks under the overhang");
+        // Down past the overhang, then turned or shifted in under it
+        assert!(tuck.path.contains(&Input::SoftDrop));
+        let last_move = tuck.path[tuck.path.len() - 2];
+        assert_ne!(last_move, Input::SoftDrop);
+    }
+
+    #[test]
+    fn a_t_spin_double_needs_the_last_rotation() {
+        let board = board_from(&["...#......", "###...####", "####.#####"]);
+        let clears_two = |placement: &Placement| {
+            let mut after = place(&board, placement).expect("fits");
+            collapse_full_rows(&mut after) == 2
+        };
+        assert!(!placements(&board, &Block::new_t()).iter().any(clears_two));
+        let spin = reachable_placements(&board, &Block::new_t())
+            .into_iter()
+            .find(|reachable| clears_two(&reachable.placement))
+            .expect("the T spins into the slot");
+        assert!(spin.t_spin);
+        let last_move = spin.path[spin.path.len() - 2];
+        assert!(matches!(last_move, Input::RotateCw | Input::RotateCcw));
+        let rows = path_rows(&board, &Block::new_t(), &spin.path);
+        assert_eq!(rows.len(), spin.path.len());
+        assert_eq!(rows.last(), Some(&spin.placement.row));
+    }
+}
@@ -1,38 +1,55 @@
-use bevy::input::ButtonInput;
-use bevy::prelude::{KeyCode, Query, Res, ResMut, Transform, With};
+use std::time::Duration;
+
+use bevy::prelude::{Entity, Parent, Query, Res, Transform, With};
+use bevy::time::{Time, Timer};
 
 use crate::board::board_check_block_position;
-use crate::common_component::{ActiveBlock, GameData, DOT_SIZE, MAX_LOCK_RESETS};
+use crate::common_component::{ActiveBlock, DropType, GameData, BOARD_COLS, DOT_SIZE, MAX_LOCK_RESETS};
+use crate::player::PieceInput;
 use crate::tetromino;
 
 pub(crate) fn block_movement_system(
-    mut query: Query<(&tetromino::Block, &mut Transform), With<ActiveBlock>>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut game_data: ResMut<GameData>,
+    mut query: Query<(&Parent, &tetromino::Block, &mut Transform), With<ActiveBlock>>,
+    time: Res<Time>,
+    mut boards: Query<(Entity, &mut GameData, &PieceInput)>,
 ) {
-    for (block, mut transform) in query.iter_mut() {
-        let mut transform_x: f32 = 0.0;
-
-        if keyboard_input.just_pressed(KeyCode::ArrowLeft) {
-            transform_x = -DOT_SIZE;
+    for (board, mut game_data, input) in boards.iter_mut() {
+        if game_data.drop_type == DropType::Hard {
+            continue;
         }
-        if keyboard_input.just_pressed(KeyCode::ArrowRight) {
-            transform_x = DOT_SIZE;
+        let mut piece = query.iter_mut().find(|(parent, ..)| parent.get() == board);
+        let pressed = input.shift_pressed();
+        let held = input.shift_held(game_data.shift_direction);
+        // DAS keeps charging while no piece is out (ARE), so the next piece can shift at once
+        let data = &mut *game_data;
+        let (direction, das_timer, arr_timer) = (&mut data.shift_direction, &mut data.das_timer, &mut data.arr_timer);
+        let shift = auto_shift(direction, das_timer, arr_timer, pressed, held, time.delta());
+        if shift == 0 {
+            continue;
         }
 
-        if transform_x == 0.0 {
+        let Some((_, block, transform)) = piece.as_mut() else {
             continue;
-        }
+        };
+        let transform_x = DOT_SIZE * shift.signum() as f32;
 
-        let in_board = board_check_block_position(
-            &game_data.board_matrix,
-            transform.translation.x + transform_x,
-            transform.translation.y,
-            block,
-        );
+        for _ in 0..shift.unsigned_abs() {
+            let in_board = board_check_block_position(
+                &game_data.board_matrix,
+                transform.translation.x + transform_x,
+                transform.translation.y,
+                block,
+            );
+            if !in_board {
+                break;
+            }
 
-        if in_board {
             transform.translation.x += transform_x;
+            game_data.last_move_rotation = false;
+            // A fresh press moves a single column, and counts as an input only when it does
+            if pressed != 0 {
+                game_data.piece_inputs += 1;
+            }
 
             // Reset lock delay on successful move (up to 15 resets)
             if game_data.lock_delay_active {
@@ -45,9 +62,41 @@ pub(crate) fn block_movement_system(
     }
 }
 
+/// Columns to shift this frame. A fresh press (`pressed` = -1 or 1) moves once; holding the
+/// direction for the DAS delay moves once more, then again every ARR interval.
+pub(crate) fn auto_shift(
+    direction: &mut i8,
+    das_timer: &mut Timer,
+    arr_timer: &mut Timer,
+    pressed: i8,
+    held: bool,
+    delta: Duration,
+) -> i32 {
+    if pressed != 0 {
+        *direction = pressed;
+        das_timer.reset();
+        arr_timer.reset();
+        return pressed as i32;
+    }
+    if !held {
+        *direction = 0;
+        return 0;
+    }
+
+    let step = *direction as i32;
+    if !das_timer.finished() {
+        return if das_timer.tick(delta).just_finished() { step } else { 0 };
+    }
+    let repeats = arr_timer.tick(delta).times_finished_this_tick().min(BOARD_COLS as u32);
+    step * repeats as i32
+}
+
 #[cfg(test)]
 mod tests {
+    use super::auto_shift;
     use crate::board::board_check_block_position;
+    use crate::common_component::GameData;
+    use std::time::Duration;
     use crate::common_component::{BOARD_COLS, BOARD_ROWS, DOT_SIZE};
     use crate::tetromino::Block;
 
@@ -81,6 +130,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_range_loop)]
     fn move_blocked_by_placed_piece() {
         let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
         // Fill column 5 entirely
@@ -92,4 +142,34 @@ mod tests {
         // At x=-37.5, base board_x=3, so dots at columns 4,5 — column 5 is filled
         assert!(!board_check_block_position(&board, -37.5, 200.0, &block));
     }
+
+    fn shift(game_data: &mut GameData, pressed: i8, held: bool, delta: Duration) -> i32 {
+        auto_shift(&mut game_data.shift_direction, &mut game_data.das_timer, &mut game_data.arr_timer, pressed, held, delta)
+    }
+
+    #[test]
+    fn auto_shift_moves_once_on_press() {
+        let mut game_data = GameData::default();
+        assert_eq!(shift(&mut game_data, -1, true, Duration::ZERO), -1);
+        // Holding for less than DAS does not repeat
+        assert_eq!(shift(&mut game_data, 0, true, Duration::from_millis(100)), 0);
+    }
+
+    #[test]
+    fn auto_shift_repeats_after_das() {
+        let mut game_data = GameData::default();
+        shift(&mut game_data, 1, true, Duration::ZERO);
+        let das = game_data.das_timer.duration();
+        assert_eq!(shift(&mut game_data, 0, true, das), 1);
+        let arr = game_data.arr_timer.duration();
+        assert_eq!(shift(&mut game_data, 0, true, arr * 3), 3);
+    }
+
+    #[test]
+    fn auto_shift_stops_on_release() {
+        let mut game_data = GameData::default();
+        shift(&mut game_data, 1, true, Duration::ZERO);
+        assert_eq!(shift(&mut game_data, 0, false, Duration::from_secs(1)), 0);
+        assert_eq!(game_data.shift_direction, 0);
+    }
 }
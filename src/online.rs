@@ -0,0 +1,774 @@
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread::JoinHandle;
+
+use bevy::input::gamepad::Gamepad;
+use bevy::input::ButtonInput;
+use bevy::prelude::{
+    Commands, DetectChangesMut, Entity, KeyCode, NextState, Query, Res, ResMut, Resource, Text, With, Without,
+};
+
+use crate::attack::{AttackState, AttackTable, RuleSet};
+use crate::common_component::{GameData, GameOutcome, GameState, ModeDescriptionText, StartScreenOverlay};
+use crate::game_mode::{format_time, GameMode, ModeSettings, OnlineRole};
+use crate::lan::{lan_games_text, player_name, Beacon, LanDiscovery};
+use crate::player::{Controls, MainBoard, PieceInput, PlayerBoard};
+use crate::rollback::Rollback;
+use crate::side_board::SideBoardView;
+use crate::spawn_block_system::Randomizer7Bag;
+use crate::start_screen::start_game;
+use crate::two_player::replace_second_board;
+
+/// Bumped whenever the messages change, so mismatched builds refuse to play each other
+pub(crate) const PROTOCOL_VERSION: u32 = 4;
+
+/// One line of the text protocol spoken between two game instances. Both games play both boards from the
+/// players' inputs (see `rollback`), so once a game runs only inputs cross the wire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Message {
+    /// Sent by both sides on connecting: their half of the piece seed and their garbage rules (the host's apply)
+    Hello { version: u32, seed: u64, rules: String },
+    /// The player wants to start the next game
+    Ready,
+    /// The sender's input for one frame of the game, counted from 0
+    Input { frame: u32, input: PieceInput },
+}
+
+impl Message {
+    fn encode(&self) -> String {
+        match self {
+            Message::Hello { version, seed, rules } => format!("HELLO {version} {seed} {rules}"),
+            Message::Ready => "READY".to_string(),
+            Message::Input { frame, input } => format!("INPUT {frame} {}", input_bits(input)),
+        }
+    }
+
+    fn decode(line: &str) -> Option<Message> {
+        let mut words = line.split(' ');
+        let message = match words.next()? {
+            "HELLO" => Message::Hello {
+                version: words.next()?.parse().ok()?,
+                seed: words.next()?.parse().ok()?,
+                rules: words.next()?.to_string(),
+            },
+            "READY" => Message::Ready,
+            "INPUT" => Message::Input {
+                frame: words.next()?.parse().ok()?,
+                input: input_from_bits(words.next()?.parse().ok()?)?,
+            },
+            _ => return None,
+        };
+        words.next().is_none().then_some(message)
+    }
+}
+
+/// The buttons of an input as one number, a bit each
+fn input_bits(input: &PieceInput) -> u16 {
+    [
+        input.left,
+        input.right,
+        input.soft_drop,
+        input.left_pressed,
+        input.right_pressed,
+        input.hard_drop,
+        input.rotate_cw,
+        input.rotate_ccw,
+        input.hold,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |bits, (i, &on)| bits | (u16::from(on) << i))
+}
+
+/// The input written by `input_bits`
+fn input_from_bits(bits: u16) -> Option<PieceInput> {
+    if bits >> 9 != 0 {
+        return None;
+    }
+    let on = |i: u16| bits & (1 << i) != 0;
+    Some(PieceInput {
+        left: on(0),
+        right: on(1),
+        soft_drop: on(2),
+        left_pressed: on(3),
+        right_pressed: on(4),
+        hard_drop: on(5),
+        rotate_cw: on(6),
+        rotate_ccw: on(7),
+        hold: on(8),
+    })
+}
+
+/// A non-blocking TCP connection that sends and receives whole lines
+pub(crate) struct Link {
+    stream: TcpStream,
+    received: Vec<u8>,
+    /// Lines sent while the socket's buffer was full, written out on the next calls
+    unsent: Vec<u8>,
+    closed: bool,
+}
+
+impl Link {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            received: Vec::new(),
+            unsent: Vec::new(),
+            closed: false,
+        })
+    }
+
+    /// Queues `line` and sends as much of the queue as the socket takes; the rest waits for the next call
+    pub fn send_line(&mut self, line: &str) -> io::Result<()> {
+        self.unsent.extend_from_slice(line.as_bytes());
+        self.unsent.push(b'\n');
+        self.flush()
+    }
+
+    /// Writes queued lines until the socket's buffer is full
+    pub fn flush(&mut self) -> io::Result<()> {
+        while !self.unsent.is_empty() {
+            match self.stream.write(&self.unsent) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.unsent.drain(..written);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Bytes still waiting for room in the socket's buffer
+    pub fn unsent_len(&self) -> usize {
+        self.unsent.len()
+    }
+
+    /// Lines that arrived since the last call; an error once the other side is gone. What is still queued
+    /// for sending goes out first.
+    pub fn receive_lines(&mut self) -> io::Result<Vec<String>> {
+        self.flush()?;
+        let mut chunk = [0; 4096];
+        while !self.closed {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => self.closed = true,
+                Ok(read) => self.received.extend_from_slice(&chunk[..read]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let mut lines = Vec::new();
+        while let Some(end) = self.received.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.received.drain(..=end).collect();
+            lines.push(String::from_utf8_lossy(&line).trim_end().to_string());
+        }
+        if self.closed && lines.is_empty() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(lines)
+    }
+}
+
+/// Connects to `address` (`host:port`) on a helper thread, so a slow or unreachable address never stalls a frame
+pub(crate) fn connect_in_background(address: &str) -> io::Result<JoinHandle<io::Result<TcpStream>>> {
+    let target = address.to_string();
+    std::thread::Builder::new()
+        .name("connect".to_string())
+        .spawn(move || TcpStream::connect(target.as_str()))
+}
+
+#[derive(Default)]
+enum Connection {
+    #[default]
+    Closed,
+    Listening(TcpListener),
+    Connecting {
+        address: String,
+        attempt: JoinHandle<io::Result<TcpStream>>,
+    },
+    Open(Link),
+}
+
+/// The connection to the other game instance and what it told us
+#[derive(Resource, Default)]
+pub(crate) struct OnlineSession {
+    connection: Connection,
+    /// Lets the hosted game be found on the local network until an opponent connects
+    beacon: Option<Beacon>,
+    hosting: bool,
+    /// Garbage rules of the games played: the host's choice
+    pub rules: RuleSet,
+    /// Address of the opponent, kept for the results after they leave
+    peer: String,
+    /// Why the last connection attempt or connection ended
+    problem: Option<String>,
+    local_seed: u64,
+    /// Both halves of the seed combined, once the handshake is done
+    shared_seed: Option<u64>,
+    games_started: u64,
+    /// Piece seed of the game being played, the same on both sides
+    game_seed: u64,
+    ready: bool,
+    ready_sent: bool,
+    peer_ready: bool,
+    /// Game messages sent by an opponent whose next game began before ours, kept until ours begins too
+    early: Vec<Message>,
+    pub opponent_left: bool,
+}
+
+impl OnlineSession {
+    /// Waits for an opponent on `port` on every network interface, playing by `rules`
+    pub fn host(&mut self, port: u16, rules: RuleSet) {
+        self.reset();
+        self.hosting = true;
+        self.rules = rules;
+        // Without discovery the game can still be joined by address
+        self.beacon = Beacon::new(port, rules, &player_name()).ok();
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            Ok(listener)
+        });
+        match listener {
+            Ok(listener) => self.connection = Connection::Listening(listener),
+            Err(e) => self.fail(format!("Cannot host on port {port}: {e}")),
+        }
+    }
+
+    /// Connects to a hosting game at `address` (`host:port`)
+    pub fn join(&mut self, address: &str) {
+        self.reset();
+        match connect_in_background(address) {
+            Ok(attempt) => {
+                self.connection = Connection::Connecting {
+                    address: address.to_string(),
+                    attempt,
+                }
+            }
+            Err(e) => self.fail(format!("Cannot connect to {address}: {e}")),
+        }
+    }
+
+    /// Hangs up, or stops waiting for an opponent
+    pub fn close(&mut self) {
+        self.reset();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(self.connection, Connection::Closed)
+    }
+
+    pub fn is_open(&self) -> bool {
+        matches!(self.connection, Connection::Open(_))
+    }
+
+    /// The player wants to start the next game; it starts once the opponent wants to too
+    pub fn set_ready(&mut self) {
+        if !self.is_closed() {
+            self.ready = true;
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self {
+            local_seed: rand::random(),
+            ..Default::default()
+        };
+    }
+
+    /// Ends the connection, keeping who the opponent was and how they did for the results
+    fn fail(&mut self, problem: String) {
+        self.connection = Connection::Closed;
+        self.beacon = None;
+        self.shared_seed = None;
+        self.ready = false;
+        self.ready_sent = false;
+        self.peer_ready = false;
+        self.early.clear();
+        self.problem = Some(problem);
+    }
+
+    /// Port the hosting game listens on
+    fn listening_port(&self) -> Option<u16> {
+        match &self.connection {
+            Connection::Listening(listener) => listener.local_addr().ok().map(|address| address.port()),
+            _ => None,
+        }
+    }
+
+    fn open(&mut self, stream: TcpStream) {
+        let peer = stream.peer_addr().map(|address| address.to_string()).unwrap_or_default();
+        let hello = Message::Hello {
+            version: PROTOCOL_VERSION,
+            seed: self.local_seed,
+            rules: self.rules.name().to_string(),
+        };
+        self.beacon = None;
+        match Link::new(stream).and_then(|mut link| link.send_line(&hello.encode()).map(|_| link)) {
+            Ok(link) => {
+                self.connection = Connection::Open(link);
+                self.peer = peer;
+            }
+            Err(e) => self.fail(format!("Lost the connection: {e}")),
+        }
+    }
+
+    /// Sends a message if connected; a failed send closes the connection
+    pub fn send(&mut self, message: &Message) {
+        if let Connection::Open(link) = &mut self.connection {
+            if let Err(e) = link.send_line(&message.encode()) {
+                self.fail(format!("Lost the connection: {e}"));
+            }
+        }
+    }
+
+    /// Moves the connection along and returns the game messages that arrived. The handshake (where
+    /// the guest takes on the host's rules) and `Ready` are handled here.
+    fn poll(&mut self) -> Vec<Message> {
+        if let Some(beacon) = &self.beacon {
+            beacon.answer_searches();
+        }
+        match std::mem::take(&mut self.connection) {
+            Connection::Listening(listener) => match listener.accept() {
+                Ok((stream, _)) => self.open(stream),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => self.connection = Connection::Listening(listener),
+                Err(e) => self.fail(format!("Stopped hosting: {e}")),
+            },
+            Connection::Connecting { address, attempt } if attempt.is_finished() => match attempt.join() {
+                Ok(Ok(stream)) => self.open(stream),
+                Ok(Err(e)) => self.fail(format!("Cannot connect to {address}: {e}")),
+                Err(_) => self.fail(format!("Cannot connect to {address}")),
+            },
+            connection => self.connection = connection,
+        }
+
+        let Connection::Open(link) = &mut self.connection else {
+            return Vec::new();
+        };
+        let received = match link.receive_lines().and_then(|lines| {
+            lines
+                .iter()
+                .map(|line| Message::decode(line))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown message"))
+        }) {
+            Ok(received) => received,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                self.fail("The opponent left".to_string());
+                return Vec::new();
+            }
+            Err(e) => {
+                self.fail(format!("Lost the connection: {e}"));
+                return Vec::new();
+            }
+        };
+        let mut messages = Vec::new();
+        for message in received {
+            match message {
+                Message::Hello { version, .. } if version != PROTOCOL_VERSION => {
+                    self.fail("The opponent runs a different version".to_string());
+                    return Vec::new();
+                }
+                Message::Hello { seed, rules, .. } => {
+                    if !self.hosting {
+                        let Some(rules) = RuleSet::from_name(&rules) else {
+                            self.fail(format!("The host plays {rules} rules, which this version does not know"));
+                            return Vec::new();
+                        };
+                        self.rules = rules;
+                    }
+                    self.shared_seed = Some(self.local_seed ^ seed);
+                }
+                Message::Ready => self.peer_ready = true,
+                // The opponent only plays after its Ready, so this is their next game already
+                message if self.peer_ready => self.early.push(message),
+                message => messages.push(message),
+            }
+        }
+        if !self.peer_ready && !self.early.is_empty() {
+            messages.splice(0..0, std::mem::take(&mut self.early));
+        }
+        if self.ready && !self.ready_sent && self.shared_seed.is_some() {
+            self.send(&Message::Ready);
+            self.ready_sent = true;
+        }
+        messages
+    }
+
+    /// Once both players are ready, begins the next game and returns its piece seed, the same on both sides
+    fn start_game(&mut self) -> Option<u64> {
+        if !(self.ready_sent && self.peer_ready) {
+            return None;
+        }
+        let seed = self.shared_seed?;
+        self.ready = false;
+        self.ready_sent = false;
+        self.peer_ready = false;
+        self.opponent_left = false;
+        self.games_started += 1;
+        self.game_seed = seed.wrapping_add(self.games_started);
+        Some(self.game_seed)
+    }
+
+    /// One line about the connection for the lobby
+    fn status(&self, role: OnlineRole) -> String {
+        let status = match &self.connection {
+            Connection::Closed => {
+                let action = match role {
+                    OnlineRole::Host => "host",
+                    OnlineRole::Join => "join",
+                };
+                let hint = format!("Press Enter to {action}");
+                return match &self.problem {
+                    Some(problem) => format!("{problem}\n{hint}"),
+                    None => hint,
+                };
+            }
+            Connection::Listening(_) => {
+                format!("Waiting for an opponent on port {}", self.listening_port().unwrap_or_default())
+            }
+            Connection::Connecting { address, .. } => format!("Connecting to {address}..."),
+            Connection::Open(_) if self.shared_seed.is_none() => format!("Connected to {}, comparing rules", self.peer),
+            Connection::Open(_) if !self.ready => {
+                format!("Connected to {} ({} rules). Press Enter to play", self.peer, self.rules.name())
+            }
+            Connection::Open(_) => format!("Connected to {}. Waiting for them to press Enter", self.peer),
+        };
+        format!("{status}\nEsc: disconnect")
+    }
+}
+
+/// Connects, hosts and starts online games from the mode menu. Enter in Online makes the player
+/// ready (hosting or joining first if needed), Esc hangs up, and starting another mode hangs up too.
+pub(crate) fn online_lobby_system(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    (mode, settings, mut table): (Res<GameMode>, Res<ModeSettings>, ResMut<AttackTable>),
+    mut session: ResMut<OnlineSession>,
+    overlay: Query<Entity, With<StartScreenOverlay>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut boards: Query<&mut GameData>,
+) {
+    if *mode != GameMode::Online {
+        if input.just_pressed(KeyCode::Enter) {
+            session.close();
+            // Only online games play by other garbage rules
+            table.set_if_neq(AttackTable::default());
+        }
+    } else {
+        if input.just_pressed(KeyCode::Enter) {
+            if session.is_closed() {
+                match settings.online_role {
+                    OnlineRole::Host => session.host(settings.online_port(), settings.rule_set),
+                    OnlineRole::Join => session.join(&settings.online_address),
+                }
+            }
+            session.set_ready();
+        }
+        if input.just_pressed(KeyCode::Escape) {
+            session.close();
+        }
+    }
+
+    // Anything left over from the last game is of no use any more; what the opponent sends once their next
+    // game has begun is kept by the session for online_sync_system
+    session.poll();
+    if session.start_game().is_some() {
+        start_game(&mut commands, &overlay, &mut next_state, &mut boards, 1);
+        table.set_if_neq(session.rules.table());
+    }
+}
+
+/// Shows the connection status under the Online mode description, and the games found while joining
+pub(crate) fn online_lobby_display_system(
+    mode: Res<GameMode>,
+    settings: Res<ModeSettings>,
+    session: Res<OnlineSession>,
+    discovery: Res<LanDiscovery>,
+    mut description: Query<&mut Text, With<ModeDescriptionText>>,
+) {
+    if *mode != GameMode::Online {
+        return;
+    }
+    let Ok(mut text) = description.get_single_mut() else {
+        return;
+    };
+    let mut content = format!("{}\n\n{}", mode.description(), session.status(settings.online_role));
+    if settings.online_role == OnlineRole::Join && session.is_closed() {
+        content = format!("{content}\n\n{}", lan_games_text(&discovery.games, &settings.online_address));
+    }
+    if text.0 != content {
+        text.0 = content;
+    }
+}
+
+/// Seed of the garbage holes of the host's board (`true`) or the guest's, the same in both games
+fn hole_seed(game_seed: u64, host: bool) -> u64 {
+    game_seed.wrapping_add(1 + u64::from(host))
+}
+
+/// Seats the opponent on the second board at the start of every online game. Both games play both boards,
+/// so each board starts out the same in both: the same pieces, and garbage holes seeded by whose board it is.
+pub(crate) fn online_setup_system(
+    mut commands: Commands,
+    mut main_board: Query<(&mut GameData, &mut Randomizer7Bag), With<MainBoard>>,
+    other_boards: Query<Entity, (With<PlayerBoard>, Without<MainBoard>)>,
+    view: Query<Entity, With<SideBoardView>>,
+    session: Res<OnlineSession>,
+    mut rollback: ResMut<Rollback>,
+) {
+    let Ok((mut game_data, mut queue)) = main_board.get_single_mut() else {
+        return;
+    };
+    if game_data.initial_board_placed {
+        return;
+    }
+    game_data.initial_board_placed = true;
+    game_data.attack = AttackState::with_hole_seed(hole_seed(session.game_seed, session.hosting));
+    *queue = Randomizer7Bag::seeded(session.game_seed);
+
+    let opponent = replace_second_board(&mut commands, &other_boards, &view, format!("vs {}", session.peer));
+    commands.entity(opponent).insert((
+        GameData {
+            initial_board_placed: true,
+            attack: AttackState::with_hole_seed(hole_seed(session.game_seed, !session.hosting)),
+            ..GameData::with_start_level(game_data.start_level)
+        },
+        Randomizer7Bag::seeded(session.game_seed),
+    ));
+    *rollback = Rollback::start();
+}
+
+/// Hands the opponent's inputs and the player's own to the rollback. The opponent leaving wins the game.
+pub(crate) fn online_sync_system(
+    mut session: ResMut<OnlineSession>,
+    mut rollback: ResMut<Rollback>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut main_board: Query<(&mut GameData, &Controls), With<MainBoard>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Ok((mut game_data, controls)) = main_board.get_single_mut() else {
+        return;
+    };
+    for message in session.poll() {
+        if let Message::Input { frame, input } = message {
+            if !rollback.add_remote(frame, input) {
+                session.fail(format!("The opponent's input for frame {frame} came out of order"));
+            }
+        }
+    }
+    if !session.is_open() {
+        session.opponent_left = true;
+        game_data.outcome = GameOutcome::Won;
+        next_state.set(GameState::GameOver);
+        return;
+    }
+    rollback.press(controls.read(&keyboard, gamepads.iter().next()));
+}
+
+/// Sends the opponent the inputs the player's frames were just played with
+pub(crate) fn online_send_system(mut session: ResMut<OnlineSession>, mut rollback: ResMut<Rollback>) {
+    for (frame, input) in rollback.take_outgoing() {
+        session.send(&Message::Input { frame, input });
+    }
+}
+
+/// On the results screen Enter asks the opponent for a rematch (played from the lobby) and Esc hangs up
+pub(crate) fn online_rematch_system(input: Res<ButtonInput<KeyCode>>, mut session: ResMut<OnlineSession>) {
+    if input.just_pressed(KeyCode::Enter) {
+        session.set_ready();
+    }
+    if input.just_pressed(KeyCode::Escape) {
+        session.close();
+    }
+}
+
+pub(crate) fn online_result_lines(
+    game_data: &GameData,
+    other_boards: &[&GameData],
+    session: &OnlineSession,
+) -> Vec<String> {
+    let (their_sent, their_lines) = other_boards
+        .first()
+        .map_or((0, 0), |opponent| (opponent.attack.lines_sent, opponent.lines_cleared));
+    let mut lines = vec![
+        format!("vs {}", session.peer),
+        format!("Lines sent: {} - {their_sent}", game_data.attack.lines_sent),
+        format!("Lines: {} - {their_lines}", game_data.lines_cleared),
+        format!("Time: {}", format_time(game_data.play_time.elapsed())),
+    ];
+    if session.opponent_left {
+        lines.insert(1, "The opponent disconnected".to_string());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Polls both sessions until `done` holds, failing after a few seconds
+    fn poll_until(
+        a: &mut OnlineSession,
+        b: &mut OnlineSession,
+        mut done: impl FnMut(&mut OnlineSession, &mut OnlineSession, Vec<Message>) -> bool,
+    ) {
+        for _ in 0..500 {
+            a.poll();
+            let received = b.poll();
+            if done(a, b, received) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("the sessions never got there");
+    }
+
+    /// A host playing by `rules` and a guest that joined it, both past the handshake
+    fn connected_pair(rules: RuleSet) -> (OnlineSession, OnlineSession) {
+        let mut host = OnlineSession::default();
+        host.host(0, rules);
+        let port = host.listening_port().expect("a free port");
+        let mut guest = OnlineSession::default();
+        guest.join(&format!("127.0.0.1:{port}"));
+        poll_until(&mut host, &mut guest, |host, guest, _| {
+            host.shared_seed.is_some() && guest.shared_seed.is_some()
+        });
+        (host, guest)
+    }
+
+    /// A held left with fresh rotate and hold presses
+    fn some_input() -> PieceInput {
+        PieceInput {
+            left: true,
+            rotate_ccw: true,
+            hold: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lines_wait_for_room_in_a_full_socket_buffer() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("a free port");
+        let stream = TcpStream::connect(listener.local_addr().expect("an address")).expect("a connection");
+        let mut sender = Link::new(stream).expect("a link");
+        let mut receiver = Link::new(listener.accept().expect("the connection").0).expect("a link");
+
+        // Far more than the socket buffers hold while nobody reads
+        let line = "x".repeat(1000);
+        let count = 10_000;
+        for _ in 0..count {
+            sender.send_line(&line).expect("a full buffer is no error");
+        }
+        assert!(!sender.unsent.is_empty());
+
+        let mut received = 0;
+        for _ in 0..1_000_000 {
+            sender.flush().expect("the receiver is still there");
+            let lines = receiver.receive_lines().expect("the sender is still there");
+            assert!(lines.iter().all(|received| *received == line));
+            received += lines.len();
+            if received == count {
+                break;
+            }
+        }
+        assert_eq!(received, count);
+        assert!(sender.unsent.is_empty());
+    }
+
+    #[test]
+    fn messages_survive_encoding() {
+        let messages = [
+            Message::Hello {
+                version: PROTOCOL_VERSION,
+                seed: u64::MAX,
+                rules: RuleSet::Guideline.name().to_string(),
+            },
+            Message::Ready,
+            Message::Input {
+                frame: 1200,
+                input: some_input(),
+            },
+            Message::Input {
+                frame: 0,
+                input: PieceInput::default(),
+            },
+        ];
+        for message in messages {
+            assert_eq!(Message::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn unknown_or_truncated_lines_are_rejected() {
+        assert_eq!(Message::decode("HELLO 1"), None);
+        assert_eq!(Message::decode("INPUT four 0"), None);
+        assert_eq!(Message::decode("INPUT 4 1024"), None);
+        assert_eq!(Message::decode("READY now"), None);
+        assert_eq!(Message::decode("JUMP"), None);
+    }
+
+    #[test]
+    fn two_sessions_on_one_machine_agree_on_the_seed_and_trade_inputs() {
+        let (mut host, mut guest) = connected_pair(RuleSet::Guideline);
+        host.set_ready();
+        guest.set_ready();
+        let mut seeds = (None, None);
+        poll_until(&mut host, &mut guest, |host, guest, _| {
+            seeds.0 = seeds.0.or_else(|| host.start_game());
+            seeds.1 = seeds.1.or_else(|| guest.start_game());
+            seeds.0.is_some() && seeds.1.is_some()
+        });
+        assert_eq!(seeds.0, seeds.1);
+
+        let input = Message::Input {
+            frame: 3,
+            input: some_input(),
+        };
+        host.send(&input);
+        let mut inputs = Vec::new();
+        poll_until(&mut host, &mut guest, |_, _, received| {
+            inputs.extend(received);
+            !inputs.is_empty()
+        });
+        assert_eq!(inputs, vec![input]);
+
+        host.close();
+        poll_until(&mut host, &mut guest, |_, guest, _| !guest.is_open());
+        assert_eq!(guest.problem.as_deref(), Some("The opponent left"));
+    }
+
+    #[test]
+    fn what_arrives_before_this_side_starts_is_kept_for_the_game() {
+        let (mut host, mut guest) = connected_pair(RuleSet::Guideline);
+        guest.set_ready();
+        host.set_ready();
+        poll_until(&mut guest, &mut host, |_, host, _| host.start_game().is_some());
+
+        // The host already plays and sends its input while the guest is still in the lobby
+        let input = Message::Input {
+            frame: 3,
+            input: some_input(),
+        };
+        host.send(&input);
+        poll_until(&mut host, &mut guest, |_, guest, received| {
+            assert!(received.is_empty());
+            !guest.early.is_empty()
+        });
+        assert!(guest.start_game().is_some());
+        assert_eq!(guest.poll(), vec![input]);
+    }
+
+    #[test]
+    fn the_guest_plays_by_the_hosts_rules() {
+        let (host, guest) = connected_pair(RuleSet::Classic);
+        assert_eq!(host.rules, RuleSet::Classic);
+        assert_eq!(guest.rules, RuleSet::Classic);
+    }
+}
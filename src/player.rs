@@ -0,0 +1,223 @@
+use bevy::input::gamepad::{Gamepad, GamepadButton};
+use bevy::input::ButtonInput;
+use bevy::prelude::{
+    Commands, Component, DespawnRecursiveExt, Entity, KeyCode, Query, Res, Transform, Visibility, With, Without,
+};
+
+use crate::common_component::GameData;
+use crate::ghost::GhostTracker;
+use crate::history::History;
+use crate::spawn_block_system::Randomizer7Bag;
+
+/// A playing field. Its `GameData`, queue, ghost and history are components on the same entity;
+/// the falling piece, settled cells and ghost are its children, positioned in board space.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PlayerBoard {
+    /// 1 for the main board, 2 for the second player's
+    pub player: u8,
+}
+
+/// The board the side panels (score, hold, next, finesse) describe and the mode rules watch
+#[derive(Component)]
+pub(crate) struct MainBoard;
+
+/// What a board's player does this frame: held buttons plus fresh presses. Keyboard and gamepad
+/// controls fill it in; anything else driving a board (a bot, a remote player) can too.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PieceInput {
+    pub left: bool,
+    pub right: bool,
+    pub soft_drop: bool,
+    pub left_pressed: bool,
+    pub right_pressed: bool,
+    pub hard_drop: bool,
+    pub rotate_cw: bool,
+    pub rotate_ccw: bool,
+    pub hold: bool,
+}
+
+impl PieceInput {
+    /// -1 or 1 on a fresh left/right press (right wins), 0 otherwise
+    pub fn shift_pressed(&self) -> i8 {
+        if self.right_pressed {
+            1
+        } else if self.left_pressed {
+            -1
+        } else {
+            0
+        }
+    }
+
+    /// Whether the direction of an ongoing auto-shift is still held
+    pub fn shift_held(&self, direction: i8) -> bool {
+        match direction {
+            -1 => self.left,
+            1 => self.right,
+            _ => false,
+        }
+    }
+
+    /// The buttons held, without the fresh presses: what a player goes on doing until told otherwise
+    pub fn held(&self) -> PieceInput {
+        PieceInput {
+            left: self.left,
+            right: self.right,
+            soft_drop: self.soft_drop,
+            ..Default::default()
+        }
+    }
+
+    /// These held buttons with the presses of both inputs, for an earlier input that was never played
+    pub fn with_presses_of(&self, earlier: &PieceInput) -> PieceInput {
+        PieceInput {
+            left_pressed: self.left_pressed || earlier.left_pressed,
+            right_pressed: self.right_pressed || earlier.right_pressed,
+            hard_drop: self.hard_drop || earlier.hard_drop,
+            rotate_cw: self.rotate_cw || earlier.rotate_cw,
+            rotate_ccw: self.rotate_ccw || earlier.rotate_ccw,
+            hold: self.hold || earlier.hold,
+            ..*self
+        }
+    }
+}
+
+/// Keys (and optionally the first gamepad) that play a board
+#[derive(Component, Debug, Clone, Copy)]
+pub(crate) struct Controls {
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub soft_drop: KeyCode,
+    pub hard_drop: KeyCode,
+    pub rotate_cw: KeyCode,
+    pub rotate_ccw: KeyCode,
+    pub hold: KeyCode,
+    /// The D-pad moves and drops, East/South rotate and either trigger holds
+    pub gamepad: bool,
+}
+
+impl Controls {
+    /// Arrows move and drop (Up hard drops), E/Q rotate, C holds
+    pub fn player_one() -> Self {
+        Self {
+            left: KeyCode::ArrowLeft,
+            right: KeyCode::ArrowRight,
+            soft_drop: KeyCode::ArrowDown,
+            hard_drop: KeyCode::ArrowUp,
+            rotate_cw: KeyCode::KeyE,
+            rotate_ccw: KeyCode::KeyQ,
+            hold: KeyCode::KeyC,
+            gamepad: false,
+        }
+    }
+
+    /// J/L move, K soft drops, I hard drops, O/U rotate, H holds, plus the first gamepad
+    pub fn player_two() -> Self {
+        Self {
+            left: KeyCode::KeyJ,
+            right: KeyCode::KeyL,
+            soft_drop: KeyCode::KeyK,
+            hard_drop: KeyCode::KeyI,
+            rotate_cw: KeyCode::KeyO,
+            rotate_ccw: KeyCode::KeyU,
+            hold: KeyCode::KeyH,
+            gamepad: true,
+        }
+    }
+
+    pub fn read(&self, keyboard: &ButtonInput<KeyCode>, gamepad: Option<&Gamepad>) -> PieceInput {
+        let pad = gamepad.filter(|_| self.gamepad);
+        let held = |key: KeyCode, buttons: &[GamepadButton]| {
+            keyboard.pressed(key) || pad.is_some_and(|pad| buttons.iter().any(|&button| pad.pressed(button)))
+        };
+        let pressed = |key: KeyCode, buttons: &[GamepadButton]| {
+            keyboard.just_pressed(key) || pad.is_some_and(|pad| buttons.iter().any(|&button| pad.just_pressed(button)))
+        };
+        PieceInput {
+            left: held(self.left, &[GamepadButton::DPadLeft]),
+            right: held(self.right, &[GamepadButton::DPadRight]),
+            soft_drop: held(self.soft_drop, &[GamepadButton::DPadDown]),
+            left_pressed: pressed(self.left, &[GamepadButton::DPadLeft]),
+            right_pressed: pressed(self.right, &[GamepadButton::DPadRight]),
+            hard_drop: pressed(self.hard_drop, &[GamepadButton::DPadUp]),
+            rotate_cw: pressed(self.rotate_cw, &[GamepadButton::East]),
+            rotate_ccw: pressed(self.rotate_ccw, &[GamepadButton::South]),
+            hold: pressed(self.hold, &[GamepadButton::LeftTrigger, GamepadButton::RightTrigger]),
+        }
+    }
+}
+
+/// Turns each board's controls into its `PieceInput` for this frame
+pub(crate) fn read_controls_system(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut boards: Query<(&Controls, &mut PieceInput)>,
+) {
+    let gamepad = gamepads.iter().next();
+    for (controls, mut input) in boards.iter_mut() {
+        let read = controls.read(&keyboard, gamepad);
+        if *input != read {
+            *input = read;
+        }
+    }
+}
+
+/// Spawns an empty board for `player`; `transform` places (and scales) it in the world
+pub(crate) fn spawn_board(commands: &mut Commands, player: u8, transform: Transform, controls: Controls) -> Entity {
+    commands
+        .spawn((
+            PlayerBoard { player },
+            GameData::default(),
+            Randomizer7Bag::default(),
+            GhostTracker::default(),
+            History::default(),
+            PieceInput::default(),
+            controls,
+            transform,
+            Visibility::default(),
+        ))
+        .id()
+}
+
+/// The main board sits at the center of the world, under the side panels
+pub(crate) fn spawn_main_board(mut commands: Commands) {
+    let board = spawn_board(&mut commands, 1, Transform::default(), Controls::player_one());
+    commands.entity(board).insert(MainBoard);
+}
+
+/// Going back to the mode menu leaves only the main board
+pub(crate) fn extra_board_cleanup_system(
+    mut commands: Commands,
+    boards: Query<Entity, (With<PlayerBoard>, Without<MainBoard>)>,
+) {
+    for entity in boards.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn controls_read_held_and_fresh_presses() {
+        let mut keyboard = ButtonInput::<KeyCode>::default();
+        keyboard.press(KeyCode::ArrowLeft);
+        keyboard.press(KeyCode::KeyE);
+        let input = Controls::player_one().read(&keyboard, None);
+        assert!(input.left && input.left_pressed && input.rotate_cw);
+        assert_eq!(input.shift_pressed(), -1);
+
+        keyboard.clear();
+        let input = Controls::player_one().read(&keyboard, None);
+        assert!(input.left && !input.left_pressed && !input.rotate_cw);
+        assert!(input.shift_held(-1) && !input.shift_held(1));
+    }
+
+    #[test]
+    fn each_player_has_their_own_keys() {
+        let mut keyboard = ButtonInput::<KeyCode>::default();
+        keyboard.press(KeyCode::KeyI);
+        assert!(!Controls::player_one().read(&keyboard, None).hard_drop);
+        assert!(Controls::player_two().read(&keyboard, None).hard_drop);
+    }
+}
@@ -0,0 +1,196 @@
+use bevy::color::Color;
+use bevy::input::ButtonInput;
+use bevy::math::Vec2;
+use bevy::prelude::{
+    Camera, Commands, DespawnRecursiveExt, Entity, GlobalTransform, KeyCode, MouseButton, Parent, Query, Res,
+    Transform, With,
+};
+use bevy::window::{PrimaryWindow, Window};
+
+use crate::board::{get_dot_position_in_board, spawn_board_dot, BoardDot, BLOCK_CELL, EMPTY_CELL};
+use crate::common_component::{ActiveBlock, GameData, BOARD_COLS, BOARD_ROWS, DOT_SIZE};
+use crate::player::MainBoard;
+use crate::spawn_block_system::{preview_slot_at, Randomizer7Bag};
+use crate::tetromino;
+
+/// Color of cells painted with the mouse
+const PAINT_COLOR: Color = Color::srgb(0.55, 0.55, 0.65);
+
+/// Pieces in the order digit keys 1-7 pick them and preview clicks cycle through them
+const PIECE_LETTERS: [char; 7] = ['I', 'O', 'T', 'S', 'Z', 'J', 'L'];
+const PIECE_KEYS: [KeyCode; 7] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+];
+
+/// Garbage rows queued by one press of G
+const PRACTICE_GARBAGE_LINES: u32 = 4;
+
+type WindowQuery<'w, 's> = Query<'w, 's, &'static Window, With<PrimaryWindow>>;
+type CameraQuery<'w, 's> = Query<'w, 's, (&'static Camera, &'static GlobalTransform)>;
+
+/// World position under the mouse cursor, if it is inside the window
+fn cursor_world_position(windows: &WindowQuery, cameras: &CameraQuery) -> Option<Vec2> {
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = cameras.get_single().ok()?;
+    camera.viewport_to_world_2d(camera_transform, cursor).ok()
+}
+
+/// Board cell (column, row) covering a world position
+pub(crate) fn board_cell_at(position: Vec2) -> Option<(i8, i8)> {
+    let board_x = (position.x / DOT_SIZE + BOARD_COLS as f32 / 2.0).floor();
+    let board_y = (BOARD_ROWS as f32 / 2.0 - position.y / DOT_SIZE).floor();
+    let on_board = (0.0..BOARD_COLS as f32).contains(&board_x) && (0.0..BOARD_ROWS as f32).contains(&board_y);
+    on_board.then_some((board_x as i8, board_y as i8))
+}
+
+/// The piece `step` places after `letter` in `PIECE_LETTERS`, wrapping around
+fn cycle_letter(letter: char, step: i32) -> char {
+    let index = PIECE_LETTERS.iter().position(|&l| l == letter).unwrap_or(0) as i32;
+    PIECE_LETTERS[(index + step).rem_euclid(PIECE_LETTERS.len() as i32) as usize]
+}
+
+/// Left mouse paints cells, right mouse erases them (hold and drag to paint several),
+/// Backspace clears the whole board. Cells under the active piece are left alone.
+pub(crate) fn practice_board_editor_system(
+    mut commands: Commands,
+    mut boards: Query<(Entity, &mut GameData), With<MainBoard>>,
+    (mouse, keyboard): (Res<ButtonInput<MouseButton>>, Res<ButtonInput<KeyCode>>),
+    (windows, cameras): (WindowQuery, CameraQuery),
+    board_dots: Query<(Entity, &Parent, &BoardDot)>,
+    active_block: Query<(&Parent, &Transform, &tetromino::Block), With<ActiveBlock>>,
+) {
+    let Ok((board, mut game_data)) = boards.get_single_mut() else {
+        return;
+    };
+    // The stack is about to collapse; edits would land on the wrong rows
+    if !game_data.clearing_rows.is_empty() {
+        return;
+    }
+
+    if keyboard.just_pressed(KeyCode::Backspace) {
+        game_data.board_matrix = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+        for (entity, parent, _) in board_dots.iter() {
+            if parent.get() == board {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        return;
+    }
+
+    let paint = mouse.pressed(MouseButton::Left);
+    if !paint && !mouse.pressed(MouseButton::Right) {
+        return;
+    }
+    let Some((board_x, board_y)) = cursor_world_position(&windows, &cameras).and_then(board_cell_at) else {
+        return;
+    };
+    let under_active_piece = active_block.iter().any(|(parent, transform, block)| {
+        parent.get() == board
+            && block.dots_by_state().iter().any(|dot| {
+                get_dot_position_in_board(transform.translation.x, transform.translation.y, dot.x, dot.y)
+                    == (board_x, board_y)
+            })
+    });
+    if under_active_piece {
+        return;
+    }
+
+    let cell = &mut game_data.board_matrix[board_y as usize][board_x as usize];
+    if paint && *cell == EMPTY_CELL {
+        *cell = BLOCK_CELL;
+        spawn_board_dot(&mut commands, board, board_x, board_y, PAINT_COLOR);
+    } else if !paint && *cell != EMPTY_CELL {
+        *cell = EMPTY_CELL;
+        for (entity, parent, board_dot) in board_dots.iter() {
+            if parent.get() == board && (board_dot.board_x, board_dot.board_y) == (board_x, board_y) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Keys 1-7 pick the next piece (I, O, T, S, Z, J, L); clicking a piece in the NEXT preview
+/// cycles it forward (left mouse) or back (right mouse)
+pub(crate) fn practice_queue_editor_system(
+    mut randomizer: Query<&mut Randomizer7Bag, With<MainBoard>>,
+    (mouse, keyboard): (Res<ButtonInput<MouseButton>>, Res<ButtonInput<KeyCode>>),
+    (windows, cameras): (WindowQuery, CameraQuery),
+) {
+    let Ok(mut randomizer) = randomizer.get_single_mut() else {
+        return;
+    };
+    if let Some(index) = PIECE_KEYS.iter().position(|&key| keyboard.just_pressed(key)) {
+        if let Some(block) = tetromino::Block::from_letter(PIECE_LETTERS[index]) {
+            randomizer.set(0, block);
+        }
+        return;
+    }
+
+    let step = if mouse.just_pressed(MouseButton::Left) {
+        1
+    } else if mouse.just_pressed(MouseButton::Right) {
+        -1
+    } else {
+        return;
+    };
+    let Some(position) = cursor_world_position(&windows, &cameras) else {
+        return;
+    };
+    let Some(slot) = preview_slot_at(position.x, position.y) else {
+        return;
+    };
+    let Some(letter) = randomizer.peek(slot + 1).get(slot).map(|block| block.letter()) else {
+        return;
+    };
+    if let Some(block) = tetromino::Block::from_letter(cycle_letter(letter, step)) {
+        randomizer.set(slot, block);
+    }
+}
+
+/// G queues incoming garbage, to practise cancelling and digging it out
+pub(crate) fn practice_garbage_system(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut game_data: Query<&mut GameData, With<MainBoard>>,
+) {
+    if !keyboard.just_pressed(KeyCode::KeyG) {
+        return;
+    }
+    if let Ok(mut game_data) = game_data.get_single_mut() {
+        game_data.attack.receive_lines(PRACTICE_GARBAGE_LINES);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::board_cell_position;
+
+    #[test]
+    fn cell_centers_map_back_to_their_cell() {
+        for (board_x, board_y) in [(0, 0), (9, 0), (0, 19), (9, 19), (4, 9)] {
+            let (x, y) = board_cell_position(board_x, board_y);
+            assert_eq!(board_cell_at(Vec2::new(x, y)), Some((board_x, board_y)));
+        }
+    }
+
+    #[test]
+    fn positions_off_the_board_have_no_cell() {
+        assert_eq!(board_cell_at(Vec2::new(-126.0, 0.0)), None);
+        assert_eq!(board_cell_at(Vec2::new(125.0, 0.0)), None);
+        assert_eq!(board_cell_at(Vec2::new(0.0, 251.0)), None);
+        assert_eq!(board_cell_at(Vec2::new(0.0, -250.5)), None);
+    }
+
+    #[test]
+    fn piece_cycle_wraps_both_ways() {
+        assert_eq!(cycle_letter('I', 1), 'O');
+        assert_eq!(cycle_letter('L', 1), 'I');
+        assert_eq!(cycle_letter('I', -1), 'L');
+    }
+}
@@ -0,0 +1,404 @@
+use bevy::prelude::{Commands, Entity, NextState, Parent, Query, Res, ResMut, Resource, With};
+
+use crate::board::{spawn_board_dot, BLOCK_CELL, EMPTY_CELL, GARBAGE_CELL, GARBAGE_COLOR};
+use crate::common_component::{ActiveBlock, GameData, GameOutcome, GameState, LineClear, BOARD_COLS, BOARD_ROWS};
+use crate::game_mode::{format_time, ModeSettings};
+use crate::player::MainBoard;
+use crate::spawn_block_system::Randomizer7Bag;
+use crate::tetromino;
+
+/// Puzzle files (`*.txt`) are read from here at startup, in file name order
+#[cfg(not(target_arch = "wasm32"))]
+const PUZZLE_DIR: &str = "assets/puzzles";
+
+/// Shipped puzzles, used on the web and whenever the puzzle directory has nothing to offer
+const BUILT_IN_PUZZLES: [&str; 3] = [
+    include_str!("../assets/puzzles/01_tetris.txt"),
+    include_str!("../assets/puzzles/02_t_spin_double.txt"),
+    include_str!("../assets/puzzles/03_perfect_clear.txt"),
+];
+
+/// What a puzzle asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PuzzleObjective {
+    /// Clear at least this many lines in total (`goal = lines 4`)
+    Lines(u32),
+    /// Clear exactly this many lines with one T-spin (`goal = tspin 2`)
+    TSpin(u32),
+    /// Leave the board empty after a clear (`goal = perfect_clear`)
+    PerfectClear,
+}
+
+impl PuzzleObjective {
+    fn parse(value: &str) -> Option<Self> {
+        let mut words = value.split_whitespace();
+        let objective = match (words.next()?, words.next()) {
+            ("lines", Some(count)) => Self::Lines(count.parse().ok()?),
+            ("tspin", Some(count)) => Self::TSpin(count.parse().ok()?),
+            ("perfect_clear", None) => Self::PerfectClear,
+            _ => return None,
+        };
+        words.next().is_none().then_some(objective)
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Self::Lines(1) => "Clear a line".to_string(),
+            Self::Lines(count) => format!("Clear {count} lines"),
+            Self::TSpin(0) => "T-spin".to_string(),
+            Self::TSpin(1) => "T-spin single".to_string(),
+            Self::TSpin(2) => "T-spin double".to_string(),
+            Self::TSpin(3) => "T-spin triple".to_string(),
+            Self::TSpin(count) => format!("T-spin clearing {count} lines"),
+            Self::PerfectClear => "Perfect clear".to_string(),
+        }
+    }
+
+    /// Whether `clear`, bringing the game's total to `total_lines`, completes the objective
+    fn is_met(&self, clear: &LineClear, total_lines: u32) -> bool {
+        match self {
+            Self::Lines(count) => total_lines >= *count,
+            Self::TSpin(count) => clear.t_spin && clear.lines == *count,
+            Self::PerfectClear => clear.perfect_clear,
+        }
+    }
+}
+
+/// A starting board, the exact pieces to play it with and the objective.
+///
+/// File format: `key = value` lines (`name`, `goal`, `pieces`, optional `hold`), then a
+/// `board:` line followed by up to 20 rows of 10 cells, top to bottom, resting on the floor.
+/// `.` is empty, a piece letter is a cell in that piece's color, anything else is garbage.
+/// Lines starting with `#` before the board are comments.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Puzzle {
+    pub name: String,
+    pub objective: PuzzleObjective,
+    /// Piece letters, dealt in order
+    pub pieces: Vec<char>,
+    pub hold: Option<char>,
+    /// Cell characters of the whole board, `.` for empty
+    pub board: [[char; BOARD_COLS]; BOARD_ROWS],
+}
+
+impl Puzzle {
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut name = None;
+        let mut objective = None;
+        let mut pieces = Vec::new();
+        let mut hold = None;
+        let mut rows: Vec<[char; BOARD_COLS]> = Vec::new();
+        let mut in_board = false;
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if in_board {
+                if line.is_empty() {
+                    continue;
+                }
+                let cells: Vec<char> = line.chars().collect();
+                let row: [char; BOARD_COLS] = cells
+                    .try_into()
+                    .map_err(|_| format!("board row \"{line}\" should be {BOARD_COLS} cells wide"))?;
+                rows.push(row);
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line == "board:" {
+                in_board = true;
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("expected `key = value`, got \"{line}\""));
+            };
+            let value = value.trim();
+            match key.trim() {
+                "name" => name = Some(value.to_string()),
+                "goal" => {
+                    objective = Some(PuzzleObjective::parse(value).ok_or_else(|| format!("unknown goal \"{value}\""))?);
+                }
+                "pieces" => pieces = parse_pieces(value)?,
+                "hold" => hold = parse_pieces(value)?.first().copied(),
+                key => return Err(format!("unknown key \"{key}\"")),
+            }
+        }
+
+        if rows.len() > BOARD_ROWS {
+            return Err(format!("board has {} rows, at most {BOARD_ROWS} fit", rows.len()));
+        }
+        if pieces.is_empty() {
+            return Err("no pieces".to_string());
+        }
+        let mut board = [['.'; BOARD_COLS]; BOARD_ROWS];
+        board[BOARD_ROWS - rows.len()..].copy_from_slice(&rows);
+
+        Ok(Self {
+            name: name.ok_or("missing name")?,
+            objective: objective.ok_or("missing goal")?,
+            pieces,
+            hold,
+            board,
+        })
+    }
+
+    /// Cell values for `GameData::board_matrix`
+    pub fn board_matrix(&self) -> [[i8; BOARD_COLS]; BOARD_ROWS] {
+        self.board.map(|row| row.map(cell_value))
+    }
+
+    pub fn label(&self) -> String {
+        format!("{} ({})", self.name, self.objective.description())
+    }
+}
+
+fn parse_pieces(value: &str) -> Result<Vec<char>, String> {
+    value
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ',')
+        .map(|c| {
+            tetromino::Block::from_letter(c)
+                .map(|block| block.letter())
+                .ok_or_else(|| format!("unknown piece \"{c}\""))
+        })
+        .collect()
+}
+
+fn cell_value(cell: char) -> i8 {
+    match cell {
+        '.' => EMPTY_CELL,
+        cell if tetromino::Block::from_letter(cell).is_some() => BLOCK_CELL,
+        _ => GARBAGE_CELL,
+    }
+}
+
+#[derive(Resource, Default, Debug)]
+pub(crate) struct PuzzlePack {
+    pub puzzles: Vec<Puzzle>,
+}
+
+impl PuzzlePack {
+    pub fn load() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let pack = Self::load_dir(PUZZLE_DIR);
+            if !pack.puzzles.is_empty() {
+                return pack;
+            }
+        }
+        Self::parse_all(BUILT_IN_PUZZLES.iter().map(|contents| ("built-in puzzle".to_string(), *contents)))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_dir(dir: &str) -> Self {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Self::default();
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+            .collect();
+        paths.sort();
+        let files: Vec<(String, String)> = paths
+            .iter()
+            .filter_map(|path| Some((path.display().to_string(), std::fs::read_to_string(path).ok()?)))
+            .collect();
+        Self::parse_all(files.iter().map(|(path, contents)| (path.clone(), contents.as_str())))
+    }
+
+    /// Parses `(source, contents)` pairs, skipping (and logging) puzzles that fail to parse
+    fn parse_all<'a>(files: impl Iterator<Item = (String, &'a str)>) -> Self {
+        let puzzles = files
+            .filter_map(|(source, contents)| match Puzzle::parse(contents) {
+                Ok(puzzle) => Some(puzzle),
+                Err(err) => {
+                    bevy::log::warn!("skipping {source}: {err}");
+                    None
+                }
+            })
+            .collect();
+        Self { puzzles }
+    }
+
+    pub fn labels(&self) -> Vec<String> {
+        self.puzzles.iter().map(Puzzle::label).collect()
+    }
+}
+
+/// Lays out the selected puzzle: its board, its piece sequence in place of the 7-bag and its hold piece
+pub(crate) fn puzzle_setup_system(
+    mut commands: Commands,
+    mut boards: Query<(Entity, &mut GameData, &mut Randomizer7Bag), With<MainBoard>>,
+    settings: Res<ModeSettings>,
+    pack: Res<PuzzlePack>,
+) {
+    let Ok((board, mut game_data, mut randomizer)) = boards.get_single_mut() else {
+        return;
+    };
+    if game_data.initial_board_placed {
+        return;
+    }
+    game_data.initial_board_placed = true;
+    let Some(puzzle) = pack.puzzles.get(settings.puzzle) else {
+        return;
+    };
+
+    game_data.board_matrix = puzzle.board_matrix();
+    for (board_y, row) in puzzle.board.iter().enumerate() {
+        for (board_x, cell) in row.iter().enumerate() {
+            let color = match cell {
+                '.' => continue,
+                cell => tetromino::Block::from_letter(*cell).map_or(GARBAGE_COLOR, |block| block.color()),
+            };
+            spawn_board_dot(&mut commands, board, board_x as i8, board_y as i8, color);
+        }
+    }
+
+    *randomizer =
+        Randomizer7Bag::from_sequence(puzzle.pieces.iter().filter_map(|c| tetromino::Block::from_letter(*c)).collect());
+    game_data.held_block = puzzle.hold.and_then(tetromino::Block::from_letter);
+}
+
+/// Solves the puzzle on the clear that meets its objective, fails it once the last piece is down without one
+pub(crate) fn puzzle_progress_system(
+    mut boards: Query<(Entity, &mut GameData, &Randomizer7Bag), With<MainBoard>>,
+    settings: Res<ModeSettings>,
+    pack: Res<PuzzlePack>,
+    active_blocks: Query<&Parent, With<ActiveBlock>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(puzzle) = pack.puzzles.get(settings.puzzle) else {
+        return;
+    };
+    let Ok((board, mut game_data, randomizer)) = boards.get_single_mut() else {
+        return;
+    };
+
+    if let Some(clear) = game_data.last_clear.take() {
+        if puzzle.objective.is_met(&clear, game_data.lines_cleared) {
+            game_data.outcome = GameOutcome::Cleared;
+            next_state.set(GameState::GameOver);
+            return;
+        }
+    }
+
+    let piece_out = active_blocks.iter().any(|parent| parent.get() == board);
+    let out_of_pieces = !piece_out && randomizer.peek(1).is_empty();
+    if out_of_pieces && game_data.clearing_rows.is_empty() {
+        game_data.outcome = GameOutcome::Failed;
+        next_state.set(GameState::GameOver);
+    }
+}
+
+pub(crate) fn puzzle_result_lines(game_data: &GameData, puzzle: Option<&Puzzle>) -> Vec<String> {
+    let mut lines = Vec::new();
+    if let Some(puzzle) = puzzle {
+        lines.push(puzzle.name.clone());
+        lines.push(format!("Goal: {}", puzzle.objective.description()));
+    }
+    if game_data.outcome == GameOutcome::Cleared {
+        lines.push(format!("Solved in {}", format_time(game_data.play_time.elapsed())));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_clear::{collapse_full_rows, leaves_empty_board};
+    use crate::move_gen::reachable_placements;
+    use crate::board::{place, Board};
+
+    /// Whether some way of playing `queue`, holding or not, meets the objective. Every placement each piece can
+    /// reach is tried; `lines` is what the placements so far have cleared.
+    fn solvable(objective: PuzzleObjective, board: &Board, queue: &[char], hold: Option<char>, lines: u32) -> bool {
+        let Some((&current, rest)) = queue.split_first() else {
+            return false;
+        };
+        // Play the piece dealt, or hold it and play what the hold gives back (the next piece when it is empty)
+        let mut choices = vec![(current, rest, hold)];
+        match (hold, rest.split_first()) {
+            (Some(held), _) => choices.push((held, rest, Some(current))),
+            (None, Some((&next, after))) => choices.push((next, after, Some(current))),
+            (None, None) => {}
+        }
+        choices.into_iter().any(|(piece, queue, hold)| {
+            let block = tetromino::Block::from_letter(piece).expect("a puzzle piece");
+            reachable_placements(board, &block).into_iter().any(|reachable| {
+                let Some(mut after) = place(board, &reachable.placement) else {
+                    return false;
+                };
+                let perfect_clear = leaves_empty_board(&after);
+                let cleared = collapse_full_rows(&mut after) as u32;
+                let clear = LineClear {
+                    lines: cleared,
+                    t_spin: reachable.t_spin,
+                    perfect_clear,
+                };
+                (cleared > 0 && objective.is_met(&clear, lines + cleared))
+                    || solvable(objective, &after, queue, hold, lines + cleared)
+            })
+        })
+    }
+
+    #[test]
+    fn built_in_puzzles_can_be_solved() {
+        let pack = PuzzlePack::parse_all(BUILT_IN_PUZZLES.iter().map(|contents| (String::new(), *contents)));
+        assert_eq!(pack.puzzles.len(), BUILT_IN_PUZZLES.len());
+        assert_eq!(pack.puzzles[0].objective, PuzzleObjective::Lines(4));
+        assert_eq!(pack.puzzles[2].hold, Some('O'));
+        for puzzle in &pack.puzzles {
+            assert!(
+                solvable(puzzle.objective, &puzzle.board_matrix(), &puzzle.pieces, puzzle.hold, 0),
+                "{} has no solution",
+                puzzle.name
+            );
+        }
+    }
+
+    #[test]
+    fn board_rests_on_the_floor() {
+        let puzzle = Puzzle::parse("name = x\ngoal = lines 1\npieces = I, o\nboard:\nT........#\n").unwrap();
+        assert_eq!(puzzle.pieces, vec!['I', 'O']);
+        let board = puzzle.board_matrix();
+        assert_eq!(board[19][0], BLOCK_CELL);
+        assert_eq!(board[19][9], GARBAGE_CELL);
+        assert_eq!(board[18], [EMPTY_CELL; BOARD_COLS]);
+    }
+
+    #[test]
+    fn parse_rejects_bad_puzzles() {
+        assert!(Puzzle::parse("name = x\npieces = I\n").is_err(), "missing goal");
+        assert!(Puzzle::parse("name = x\ngoal = lines 1\n").is_err(), "no pieces");
+        assert!(Puzzle::parse("name = x\ngoal = lines 1\npieces = Q\n").is_err(), "unknown piece");
+        assert!(Puzzle::parse("name = x\ngoal = win\npieces = I\n").is_err(), "unknown goal");
+        assert!(Puzzle::parse("name = x\ngoal = lines 1\npieces = I\nboard:\nXX\n").is_err(), "short row");
+    }
+
+    #[test]
+    fn objectives() {
+        let t_spin_double = LineClear {
+            lines: 2,
+            t_spin: true,
+            perfect_clear: false,
+        };
+        assert!(PuzzleObjective::TSpin(2).is_met(&t_spin_double, 2));
+        assert!(!PuzzleObjective::TSpin(3).is_met(&t_spin_double, 2));
+        assert!(!PuzzleObjective::PerfectClear.is_met(&t_spin_double, 2));
+        assert!(!PuzzleObjective::Lines(4).is_met(&t_spin_double, 3));
+        assert!(PuzzleObjective::Lines(4).is_met(&t_spin_double, 4));
+    }
+
+    #[test]
+    fn t_spin_double_puzzle_has_a_t_spin_slot() {
+        let puzzle = Puzzle::parse(BUILT_IN_PUZZLES[1]).unwrap();
+        let board = puzzle.board_matrix();
+        let t_spin_doubles = reachable_placements(&board, &tetromino::Block::new_t())
+            .into_iter()
+            .filter(|reachable| reachable.t_spin)
+            .filter_map(|reachable| place(&board, &reachable.placement))
+            .filter(|after| collapse_full_rows(&mut after.clone()) == 2);
+        assert_eq!(t_spin_doubles.count(), 1);
+    }
+}
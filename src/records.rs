@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use bevy::prelude::Resource;
+
+/// Personal bests, stored as `key=value` lines next to the executable's working directory.
+/// On the web there is no file system, so records only last for the session.
+#[cfg(not(target_arch = "wasm32"))]
+const RECORDS_FILE: &str = "tetris_records.txt";
+
+#[derive(Resource, Default, Debug, Clone, PartialEq)]
+pub(crate) struct Records {
+    pub sprint_best: Option<Duration>,
+    pub sprint_best_splits: Vec<Duration>,
+    /// Best Ultra score per time limit in seconds
+    pub ultra_best: BTreeMap<u32, u32>,
+    /// Best Dig time per starting garbage row count
+    pub dig_best: BTreeMap<u32, Duration>,
+}
+
+impl Records {
+    pub fn load() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Ok(contents) = std::fs::read_to_string(RECORDS_FILE) {
+            return Self::parse(&contents);
+        }
+        Self::default()
+    }
+
+    pub fn save(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(err) = std::fs::write(RECORDS_FILE, self.serialize()) {
+            bevy::log::warn!("failed to save records to {RECORDS_FILE}: {err}");
+        }
+    }
+
+    /// Records a finished sprint and returns the personal best it was compared against
+    pub fn submit_sprint(&mut self, time: Duration, splits: &[Duration]) -> Option<Duration> {
+        let previous = self.sprint_best;
+        if previous.is_none_or(|best| time < best) {
+            self.sprint_best = Some(time);
+            self.sprint_best_splits = splits.to_vec();
+            self.save();
+        }
+        previous
+    }
+
+    /// Records a finished Ultra game and returns the best score it was compared against
+    pub fn submit_ultra(&mut self, duration_secs: u32, score: u32) -> Option<u32> {
+        let previous = self.ultra_best.get(&duration_secs).copied();
+        if previous.is_none_or(|best| score > best) {
+            self.ultra_best.insert(duration_secs, score);
+            self.save();
+        }
+        previous
+    }
+
+    /// Records a finished Dig race and returns the personal best it was compared against
+    pub fn submit_dig(&mut self, rows: u32, time: Duration) -> Option<Duration> {
+        let previous = self.dig_best.get(&rows).copied();
+        if previous.is_none_or(|best| time < best) {
+            self.dig_best.insert(rows, time);
+            self.save();
+        }
+        previous
+    }
+
+    fn parse(contents: &str) -> Self {
+        let mut records = Self::default();
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "sprint_best_ms" => {
+                    records.sprint_best = value.trim().parse().ok().map(Duration::from_millis);
+                }
+                "sprint_best_splits_ms" => {
+                    records.sprint_best_splits = value
+                        .split(',')
+                        .filter_map(|ms| ms.trim().parse().ok())
+                        .map(Duration::from_millis)
+                        .collect();
+                }
+                key => {
+                    let Ok(value) = value.trim().parse::<u64>() else {
+                        continue;
+                    };
+                    if let Some(secs) = key.strip_prefix("ultra_best_").and_then(|secs| secs.parse().ok()) {
+                        records.ultra_best.insert(secs, value as u32);
+                    } else if let Some(rows) = key
+                        .strip_prefix("dig_best_")
+                        .and_then(|rows| rows.strip_suffix("_ms"))
+                        .and_then(|rows| rows.parse().ok())
+                    {
+                        records.dig_best.insert(rows, Duration::from_millis(value));
+                    }
+                }
+            }
+        }
+        records
+    }
+
+    fn serialize(&self) -> String {
+        let mut contents = String::new();
+        if let Some(best) = self.sprint_best {
+            contents.push_str(&format!("sprint_best_ms={}\n", best.as_millis()));
+        }
+        if !self.sprint_best_splits.is_empty() {
+            let splits: Vec<String> = self
+                .sprint_best_splits
+                .iter()
+                .map(|split| split.as_millis().to_string())
+                .collect();
+            contents.push_str(&format!("sprint_best_splits_ms={}\n", splits.join(",")));
+        }
+        for (secs, score) in self.ultra_best.iter() {
+            contents.push_str(&format!("ultra_best_{secs}={score}\n"));
+        }
+        for (rows, time) in self.dig_best.iter() {
+            contents.push_str(&format!("dig_best_{rows}_ms={}\n", time.as_millis()));
+        }
+        contents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_round_trips() {
+        let records = Records {
+            sprint_best: Some(Duration::from_millis(61_234)),
+            sprint_best_splits: vec![Duration::from_millis(15_000), Duration::from_millis(30_500)],
+            ultra_best: BTreeMap::from([(120, 45_000), (300, 98_100)]),
+            dig_best: BTreeMap::from([(10, Duration::from_millis(42_042))]),
+        };
+        assert_eq!(Records::parse(&records.serialize()), records);
+    }
+
+    #[test]
+    fn parse_ignores_unknown_and_malformed_lines() {
+        let records = Records::parse("garbage\nunknown=1\nsprint_best_ms=abc\n");
+        assert_eq!(records, Records::default());
+    }
+}
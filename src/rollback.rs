@@ -0,0 +1,450 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use bevy::color::Color;
+use bevy::ecs::system::RunSystemOnce;
+use bevy::math::Vec3;
+use bevy::prelude::{
+    Commands, DespawnRecursiveExt, Entity, Has, In, Mut, NextState, Parent, Query, Resource, Transform, With, World,
+};
+use bevy::sprite::Sprite;
+use bevy::time::Time;
+
+use crate::board::{spawn_board_dot, BoardDot};
+use crate::common_component::{ActiveBlock, GameData, GameOutcome, GameState};
+use crate::player::{MainBoard, PieceInput, PlayerBoard};
+use crate::spawn_block_system::{spawn_block, Randomizer7Bag};
+use crate::step::{run_fixed_step, STEP};
+use crate::tetromino;
+
+/// Frames a player's input waits before it is played, so that the opponent's copy usually arrives in time
+pub(crate) const INPUT_DELAY: u32 = 3;
+/// Frames the game may run ahead of the opponent's last input before it waits for them
+const MAX_PREDICTION: u32 = 30;
+/// Most frames caught up on in one rendered frame, after a slow one
+const MAX_CATCH_UP: u32 = 4;
+
+/// One board as it was at the start of a frame
+struct BoardState {
+    board: Entity,
+    game_data: GameData,
+    queue: Randomizer7Bag,
+    /// Color of every settled cell, to rebuild the rendered board
+    cells: Vec<(i8, i8, Color)>,
+    /// The falling piece and where it was
+    piece: Option<(tetromino::Block, Vec3)>,
+}
+
+/// Every board at the start of `frame`
+struct Snapshot {
+    frame: u32,
+    boards: Vec<BoardState>,
+}
+
+/// Both players' inputs of an online game, frame by frame, and the recent states of the boards. The local input
+/// is played `INPUT_DELAY` frames after it is read; the opponent's is guessed until it arrives, and a wrong
+/// guess takes the boards back to the frame it was made for and plays the frames since again.
+#[derive(Resource, Default)]
+pub(crate) struct Rollback {
+    /// Frames played so far
+    frame: u32,
+    /// The local player's input of every frame, read `INPUT_DELAY` frames ahead
+    local: Vec<PieceInput>,
+    /// The opponent's input of every frame, as far as it has arrived
+    remote: Vec<PieceInput>,
+    /// The opponent's input each played frame was played with, guessed where it had not arrived
+    played: Vec<PieceInput>,
+    /// Frames played with the opponent's real input, which are never played again
+    confirmed: u32,
+    /// State at the start of every frame since `confirmed`
+    snapshots: VecDeque<Snapshot>,
+    /// What the player did since the last frame played, so that no press is lost on a rendered frame without one
+    pending: PieceInput,
+    /// Local inputs read since the last send, with their frames
+    outgoing: Vec<(u32, PieceInput)>,
+    /// Real time not played yet
+    behind: Duration,
+}
+
+impl Rollback {
+    /// A new game, where neither player does anything in the first `INPUT_DELAY` frames
+    pub fn start() -> Self {
+        let idle = vec![PieceInput::default(); INPUT_DELAY as usize];
+        Self {
+            local: idle.clone(),
+            remote: idle,
+            ..Default::default()
+        }
+    }
+
+    /// Takes what the player does on this rendered frame; the presses wait for the next frame played
+    pub fn press(&mut self, input: PieceInput) {
+        self.pending = input.with_presses_of(&self.pending);
+    }
+
+    /// Stores the opponent's input of `frame`. Inputs arrive in order, so any other frame is refused.
+    pub fn add_remote(&mut self, frame: u32, input: PieceInput) -> bool {
+        if frame as usize != self.remote.len() {
+            return false;
+        }
+        self.remote.push(input);
+        true
+    }
+
+    /// The local inputs read since the last call, for the opponent
+    pub fn take_outgoing(&mut self) -> Vec<(u32, PieceInput)> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    /// The opponent's input of `frame`: theirs once it arrived, until then the buttons they held last
+    fn remote_input(&self, frame: u32) -> PieceInput {
+        match self.remote.get(frame as usize) {
+            Some(input) => *input,
+            None => self.remote.last().map(PieceInput::held).unwrap_or_default(),
+        }
+    }
+
+    /// The first frame played with a guess that the opponent's input has since proven wrong
+    fn misprediction(&self) -> Option<u32> {
+        let arrived = self.remote.len().min(self.played.len());
+        (self.confirmed as usize..arrived)
+            .find(|&frame| self.played[frame] != self.remote[frame])
+            .map(|frame| frame as u32)
+    }
+
+    /// Whether another frame may be played without getting too far ahead of the opponent
+    fn can_advance(&self) -> bool {
+        self.frame < self.remote.len() as u32 + MAX_PREDICTION
+    }
+
+    /// Whether every frame played so far was played with the opponent's real input
+    fn settled(&self) -> bool {
+        self.remote.len() as u32 >= self.frame
+    }
+
+    /// Forgets the states of frames that will not be played again
+    fn confirm(&mut self) {
+        self.confirmed = self.frame.min(self.remote.len() as u32);
+        while self.snapshots.front().is_some_and(|snapshot| snapshot.frame < self.confirmed) {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Plays the next frame: saves the boards, hands both players' inputs to them and runs a fixed step
+    fn step(&mut self, world: &mut World) {
+        let frame = self.frame;
+        // A frame played again after a wrong guess already has its local input
+        if self.local.len() as u32 <= frame + INPUT_DELAY {
+            let input = self.pending;
+            self.pending = input.held();
+            self.outgoing.push((self.local.len() as u32, input));
+            self.local.push(input);
+        }
+        let local = self.local[frame as usize];
+        let remote = self.remote_input(frame);
+
+        let boards = world.run_system_once(capture_boards).expect("capturing the boards needs no resources");
+        self.snapshots.push_back(Snapshot { frame, boards });
+        world
+            .run_system_once_with((local, remote), play_inputs)
+            .expect("handing out inputs needs no resources");
+        self.played.truncate(frame as usize);
+        self.played.push(remote);
+        run_fixed_step(world, frame);
+        self.frame += 1;
+        // A top-out on a guess may not stand, so `rollback_system` decides when the game ends
+        world.resource_mut::<NextState<GameState>>().reset();
+    }
+
+    /// Puts the boards back the way they were at the start of `frame`
+    fn rewind(&mut self, world: &mut World, frame: u32) {
+        while let Some(snapshot) = self.snapshots.pop_back() {
+            if snapshot.frame == frame {
+                world
+                    .run_system_once_with(snapshot.boards, restore_boards)
+                    .expect("restoring the boards needs no resources");
+                self.frame = frame;
+                return;
+            }
+        }
+    }
+}
+
+fn game_ended(world: &mut World) -> bool {
+    world
+        .query_filtered::<&GameData, With<PlayerBoard>>()
+        .iter(world)
+        .any(|game_data| game_data.outcome != GameOutcome::Playing)
+}
+
+/// Plays an online game at the fixed rate of `step::STEP`. A wrong guess of the opponent's input is put right
+/// first, by going back to its frame and playing the frames since again; then the frames due are played. A
+/// top-out ends the game once every frame up to it was played with both players' real inputs, as both sides
+/// have then seen the same game.
+pub(crate) fn rollback_system(world: &mut World) {
+    let delta = world.resource::<Time>().delta();
+    world.resource_scope(|world, mut rollback: Mut<Rollback>| {
+        if let Some(frame) = rollback.misprediction() {
+            let now = rollback.frame;
+            rollback.rewind(world, frame);
+            while rollback.frame < now && !game_ended(world) {
+                rollback.step(world);
+            }
+        }
+        rollback.confirm();
+
+        if !game_ended(world) {
+            rollback.behind = (rollback.behind + delta).min(STEP * MAX_CATCH_UP);
+            while rollback.behind >= STEP && rollback.can_advance() && !game_ended(world) {
+                rollback.behind -= STEP;
+                rollback.step(world);
+            }
+        }
+        if game_ended(world) && rollback.settled() {
+            world.resource_mut::<NextState<GameState>>().set(GameState::GameOver);
+        }
+    });
+}
+
+/// Every board as it is now
+fn capture_boards(
+    boards: Query<(Entity, &GameData, &Randomizer7Bag), With<PlayerBoard>>,
+    dots: Query<(&Parent, &BoardDot, &Sprite)>,
+    pieces: Query<(&Parent, &tetromino::Block, &Transform), With<ActiveBlock>>,
+) -> Vec<BoardState> {
+    boards
+        .iter()
+        .map(|(board, game_data, queue)| BoardState {
+            board,
+            game_data: game_data.clone(),
+            queue: queue.clone(),
+            cells: dots
+                .iter()
+                .filter(|(parent, ..)| parent.get() == board)
+                .map(|(_, board_dot, sprite)| (board_dot.board_x, board_dot.board_y, sprite.color))
+                .collect(),
+            piece: pieces
+                .iter()
+                .find(|(parent, ..)| parent.get() == board)
+                .map(|(_, block, transform)| (block.clone(), transform.translation)),
+        })
+        .collect()
+}
+
+/// The main board plays the local input, the other one the opponent's
+fn play_inputs(
+    In((local, remote)): In<(PieceInput, PieceInput)>,
+    mut boards: Query<(&mut PieceInput, Has<MainBoard>), With<PlayerBoard>>,
+) {
+    for (mut input, main) in boards.iter_mut() {
+        *input = if main { local } else { remote };
+    }
+}
+
+/// Puts every board back the way it was, rebuilding its settled cells and falling piece
+fn restore_boards(
+    In(states): In<Vec<BoardState>>,
+    mut commands: Commands,
+    mut boards: Query<(&mut GameData, &mut Randomizer7Bag)>,
+    dots: Query<(Entity, &Parent), With<BoardDot>>,
+    pieces: Query<(Entity, &Parent), With<ActiveBlock>>,
+) {
+    for state in states {
+        let Ok((mut game_data, mut queue)) = boards.get_mut(state.board) else {
+            continue;
+        };
+        *game_data = state.game_data;
+        *queue = state.queue;
+        for (entity, parent) in dots.iter().chain(pieces.iter()) {
+            if parent.get() == state.board {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        for (board_x, board_y, color) in state.cells {
+            spawn_board_dot(&mut commands, state.board, board_x, board_y, color);
+        }
+        if let Some((block, translation)) = state.piece {
+            let piece = spawn_block(&mut commands, state.board, block, 0.0, 0.0);
+            commands.entity(piece).insert(Transform::from_translation(translation));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attack::{AttackState, AttackTable};
+    use crate::game_mode::{GameMode, ModeSettings};
+    use crate::step::game_step_schedule;
+    use crate::board::Board;
+    use bevy::prelude::{State, Visibility};
+
+    fn tap(frame: u32) -> PieceInput {
+        PieceInput {
+            right: true,
+            right_pressed: frame.is_multiple_of(2),
+            hard_drop: frame.is_multiple_of(3),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_wrong_guess_is_found_once_the_real_input_arrives() {
+        let mut rollback = Rollback::start();
+        rollback.frame = 6;
+        rollback.played = (0..6).map(|frame| rollback.remote_input(frame)).collect();
+        assert_eq!(rollback.misprediction(), None);
+
+        // Idle frames were guessed right, the press of frame 4 was not
+        assert!(rollback.add_remote(3, PieceInput::default()));
+        assert!(rollback.add_remote(4, tap(4)));
+        assert!(!rollback.add_remote(6, tap(6)));
+        assert_eq!(rollback.misprediction(), Some(4));
+    }
+
+    #[test]
+    fn guesses_keep_held_buttons_but_not_presses() {
+        let mut rollback = Rollback::start();
+        rollback.add_remote(INPUT_DELAY, tap(0));
+        let guess = rollback.remote_input(INPUT_DELAY + 1);
+        assert!(guess.right && !guess.right_pressed && !guess.hard_drop);
+    }
+
+    #[test]
+    fn presses_wait_for_the_next_frame_played() {
+        let mut rollback = Rollback::start();
+        rollback.press(tap(0));
+        rollback.press(PieceInput::default());
+        assert_eq!(rollback.pending.shift_pressed(), 1);
+        assert!(rollback.pending.hard_drop && !rollback.pending.right);
+    }
+
+    /// What both players do on `frame`: slide to a wall or tap twice to one side, sometimes rotate or hold,
+    /// then hard drop, so the stacks spread over the board
+    fn script(player: u8, frame: u32) -> PieceInput {
+        let piece = frame / 30 + u32::from(player) * 2;
+        let phase = frame % 30;
+        let kind = piece % 5;
+        let direction = [-1, 1, -1, 1, 0][kind as usize];
+        let holding = kind < 2 && (8..24).contains(&phase);
+        let tapped = phase == 8 || (kind >= 2 && phase == 12);
+        PieceInput {
+            left: direction == -1 && holding,
+            right: direction == 1 && holding,
+            left_pressed: direction == -1 && tapped,
+            right_pressed: direction == 1 && tapped,
+            rotate_cw: piece % 2 == 1 && phase == 6,
+            hold: piece % 7 == 3 && phase == 0,
+            hard_drop: phase == 25,
+            soft_drop: false,
+            rotate_ccw: false,
+        }
+    }
+
+    /// The opponent's input of `frame` as their game sends it: nothing during the input delay, then their script
+    fn remote(frame: u32) -> PieceInput {
+        frame.checked_sub(INPUT_DELAY).map(|frame| script(2, frame)).unwrap_or_default()
+    }
+
+    fn online_world() -> World {
+        let mut world = World::new();
+        world.insert_resource(State::new(GameState::Playing));
+        world.init_resource::<NextState<GameState>>();
+        world.insert_resource(GameMode::Online);
+        world.init_resource::<ModeSettings>();
+        world.init_resource::<AttackTable>();
+        world.init_resource::<Time>();
+        world.insert_resource(Rollback::start());
+        world.add_schedule(game_step_schedule());
+        for player in 1..=2 {
+            let board = world
+                .spawn((
+                    PlayerBoard { player },
+                    GameData {
+                        attack: AttackState::with_hole_seed(u64::from(player)),
+                        ..Default::default()
+                    },
+                    Randomizer7Bag::seeded(99),
+                    PieceInput::default(),
+                    Transform::default(),
+                    Visibility::default(),
+                ))
+                .id();
+            if player == 1 {
+                world.entity_mut(board).insert(MainBoard);
+            }
+        }
+        world
+    }
+
+    /// Plays `frames` rendered frames, handing over the opponent's inputs `lag` frames late
+    fn play(world: &mut World, frames: u32, lag: u32) {
+        for rendered in 0..frames + lag {
+            if rendered < frames {
+                world.resource_mut::<Time>().advance_by(STEP);
+                world.resource_mut::<Rollback>().press(script(1, rendered));
+            } else {
+                world.resource_mut::<Time>().advance_by(Duration::ZERO);
+            }
+            let arrived = (rendered + 1).saturating_sub(lag) + INPUT_DELAY;
+            {
+                let mut rollback = world.resource_mut::<Rollback>();
+                while (rollback.remote.len() as u32) < arrived {
+                    let frame = rollback.remote.len() as u32;
+                    rollback.add_remote(frame, remote(frame));
+                }
+            }
+            world.run_system_once(rollback_system).expect("the system runs");
+        }
+    }
+
+    /// Where a board got to
+    #[derive(Debug, PartialEq)]
+    struct Played {
+        player: u8,
+        board: Board,
+        score: u32,
+        pieces: u32,
+        sent: u32,
+        /// Drawn cells, sorted
+        cells: Vec<(i8, i8)>,
+    }
+
+    fn outcome(world: &mut World) -> Vec<Played> {
+        let mut dots: Vec<_> = world
+            .query::<(&Parent, &BoardDot)>()
+            .iter(world)
+            .map(|(parent, dot)| (parent.get(), dot.board_x, dot.board_y))
+            .collect();
+        dots.sort();
+        let mut outcome: Vec<_> = world
+            .query::<(Entity, &PlayerBoard, &GameData)>()
+            .iter(world)
+            .map(|(entity, board, game_data)| Played {
+                player: board.player,
+                board: game_data.board_matrix,
+                score: game_data.score,
+                pieces: game_data.pieces_placed,
+                sent: game_data.attack.lines_sent,
+                cells: dots.iter().filter(|dot| dot.0 == entity).map(|&(_, x, y)| (x, y)).collect(),
+            })
+            .collect();
+        outcome.sort_by_key(|played| played.player);
+        outcome
+    }
+
+    #[test]
+    fn playing_on_guesses_and_correcting_them_ends_where_playing_on_real_inputs_does() {
+        let mut on_time = online_world();
+        play(&mut on_time, 300, 0);
+        let mut late = online_world();
+        play(&mut late, 300, 12);
+
+        let rollback = late.resource::<Rollback>();
+        assert_eq!(rollback.frame, 300);
+        assert_eq!(rollback.played, on_time.resource::<Rollback>().played[..300]);
+        let expected = outcome(&mut on_time);
+        assert!(expected.iter().all(|played| played.pieces >= 9));
+        assert_eq!(outcome(&mut late), expected);
+    }
+}
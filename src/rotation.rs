@@ -1,11 +1,9 @@
-use bevy::input::ButtonInput;
 use bevy::math::Vec3;
-use bevy::prelude::{
-    Children, Commands, Entity, KeyCode, Query, Res, ResMut, Transform, With,
-};
+use bevy::prelude::{Children, Commands, Entity, Parent, Query, Transform, With};
 
 use crate::board::board_check_block_position;
-use crate::common_component::{ActiveDot, GameData, DOT_SIZE, MAX_LOCK_RESETS};
+use crate::common_component::{ActiveDot, DropType, GameData, DOT_SIZE, MAX_LOCK_RESETS};
+use crate::player::PieceInput;
 use crate::tetromino;
 
 /// Returns kick offset table for SRS (Super Rotation System)
@@ -53,18 +51,23 @@ pub(crate) fn get_kick_offsets(
 
 pub(crate) fn block_rotation_system(
     mut commands: Commands,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut block_query: Query<(Entity, &Children, &mut tetromino::Block, &Transform), With<tetromino::Rotation>>,
-    mut game_data: ResMut<GameData>,
+    mut block_query: Query<(Entity, &Parent, &Children, &mut tetromino::Block, &Transform), With<tetromino::Rotation>>,
+    mut boards: Query<(&mut GameData, &PieceInput)>,
     mut child_query: Query<&mut Transform, (With<ActiveDot>, bevy::prelude::Without<tetromino::Rotation>)>,
 ) {
-    for (entity, children, mut block, transform) in block_query.iter_mut() {
+    for (entity, parent, children, mut block, transform) in block_query.iter_mut() {
+        let Ok((mut game_data, input)) = boards.get_mut(parent.get()) else {
+            continue;
+        };
+        if game_data.drop_type == DropType::Hard {
+            continue;
+        }
         let (from, to);
         let original_state = *block.state();
 
-        if keyboard_input.just_pressed(KeyCode::KeyE) {
+        if input.rotate_cw {
             (from, to) = tetromino::Rotation::rotate_right(&mut block);
-        } else if keyboard_input.just_pressed(KeyCode::KeyQ) {
+        } else if input.rotate_ccw {
             (from, to) = tetromino::Rotation::rotate_left(&mut block);
         } else {
             continue;
@@ -107,6 +110,10 @@ pub(crate) fn block_rotation_system(
                 }
             }
 
+            game_data.last_move_rotation = true;
+            // Only rotations that happen count towards finesse
+            game_data.piece_inputs += 1;
+
             // Reset lock delay on successful rotation (up to 15 resets)
             if game_data.lock_delay_active {
                 game_data.lock_move_count += 1;
@@ -124,8 +131,60 @@ pub(crate) fn block_rotation_system(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::{BuildChildren, ChildBuild, World};
+
+    use crate::board::{board_cell_position, BLOCK_CELL, EMPTY_CELL};
+    use crate::common_component::{BOARD_COLS, BOARD_ROWS};
     use crate::tetromino::{Block, State};
 
+    /// Turns a flat I with its origin at (3, 10) clockwise; with `walled_in` every other cell is filled.
+    /// Returns the inputs counted for the piece and its state afterwards.
+    fn rotate_i(walled_in: bool) -> (u32, State) {
+        let block = Block::new_i();
+        let cells = block.dots_by_state().map(|dot| (3 + dot.x, 10 + dot.y));
+        let mut board_matrix = [[if walled_in { BLOCK_CELL } else { EMPTY_CELL }; BOARD_COLS]; BOARD_ROWS];
+        for &(x, y) in &cells {
+            board_matrix[y as usize][x as usize] = EMPTY_CELL;
+        }
+        let mut world = World::new();
+        let input = PieceInput {
+            rotate_cw: true,
+            ..Default::default()
+        };
+        let board = world
+            .spawn((
+                GameData {
+                    board_matrix,
+                    ..Default::default()
+                },
+                input,
+            ))
+            .id();
+        let (x, y) = board_cell_position(3, 10);
+        let dots = block.dots_by_state();
+        let piece = world
+            .spawn((Transform::from_xyz(x, y, 1.0), block, tetromino::Rotation))
+            .set_parent(board)
+            .with_children(|parent| {
+                for dot in dots {
+                    let (x, y) = (dot.x as f32 * DOT_SIZE, -dot.y as f32 * DOT_SIZE);
+                    parent.spawn((Transform::from_xyz(x, y, 0.0), ActiveDot));
+                }
+            })
+            .id();
+        world.run_system_once(block_rotation_system).expect("the system runs");
+        let inputs = world.get::<GameData>(board).expect("a board").piece_inputs;
+        (inputs, *world.get::<Block>(piece).expect("a piece").state())
+    }
+
+    #[test]
+    fn only_rotations_that_happen_count_as_inputs() {
+        assert_eq!(rotate_i(false), (1, State::One));
+        // Nowhere to turn: the piece stays flat and no input is counted, so finesse sees no fault
+        assert_eq!(rotate_i(true), (0, State::Zero));
+    }
+
     #[test]
     fn i_piece_has_5_kick_offsets() {
         let block = Block::new_i();
@@ -0,0 +1,119 @@
+use bevy::color::Color;
+use bevy::math::Vec2;
+use bevy::prelude::{Commands, Component, Entity, Query, Transform, With};
+use bevy::sprite::Sprite;
+use bevy::text::{Text2d, TextColor, TextFont};
+use bevy::utils::default;
+
+use crate::background::{GARBAGE_METER_COLOR, INNER_WINDOW_COLOR, MAIN_COLOR};
+use crate::common_component::{BOARD_COLS, BOARD_ROWS};
+
+const BORDER_WIDTH: f32 = 6.0;
+const METER_WIDTH: f32 = 4.0;
+
+/// Where a second board (the Versus CPU, player 2) is drawn, beside the main one
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SideBoardLayout {
+    /// World position of the board's center
+    pub x: f32,
+    pub y: f32,
+    pub dot_size: f32,
+}
+
+impl SideBoardLayout {
+    fn width(&self) -> f32 {
+        self.dot_size * BOARD_COLS as f32
+    }
+
+    fn height(&self) -> f32 {
+        self.dot_size * BOARD_ROWS as f32
+    }
+}
+
+/// Everything drawn for a side board, removed when going back to the mode menu
+#[derive(Component)]
+pub(crate) struct SideBoardView;
+
+#[derive(Component)]
+pub(crate) struct SideGarbageMeter;
+
+/// Status text under a side board
+#[derive(Component)]
+pub(crate) struct SideBoardInfo;
+
+/// Frame, background, title, garbage meter and status text of a side board
+pub(crate) fn spawn_side_board_view(commands: &mut Commands, layout: SideBoardLayout, title: String) {
+    commands.spawn((
+        Sprite {
+            color: MAIN_COLOR,
+            custom_size: Some(Vec2::new(layout.width() + 2.0 * BORDER_WIDTH, layout.height() + 2.0 * BORDER_WIDTH)),
+            ..default()
+        },
+        Transform::from_xyz(layout.x, layout.y, 0.0),
+        SideBoardView,
+    ));
+    commands.spawn((
+        Sprite {
+            color: INNER_WINDOW_COLOR,
+            custom_size: Some(Vec2::new(layout.width(), layout.height())),
+            ..default()
+        },
+        Transform::from_xyz(layout.x, layout.y, 0.1),
+        SideBoardView,
+    ));
+    commands.spawn((
+        Text2d::new(title),
+        TextColor(Color::WHITE),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        Transform::from_xyz(layout.x, layout.y + layout.height() / 2.0 + BORDER_WIDTH + 12.0, 1.0),
+        SideBoardView,
+    ));
+    commands.spawn((
+        Text2d::new(""),
+        TextColor(Color::WHITE),
+        TextFont {
+            font_size: 12.0,
+            ..default()
+        },
+        Transform::from_xyz(layout.x, layout.y - layout.height() / 2.0 - BORDER_WIDTH - 20.0, 1.0),
+        SideBoardInfo,
+        SideBoardView,
+    ));
+    commands.spawn((
+        Sprite {
+            color: GARBAGE_METER_COLOR,
+            custom_size: Some(Vec2::ZERO),
+            ..default()
+        },
+        Transform::from_xyz(layout.x - layout.width() / 2.0 - BORDER_WIDTH / 2.0, layout.y, 1.0),
+        SideGarbageMeter,
+        SideBoardView,
+    ));
+}
+
+/// Sizes the side board's meter to `lines` rows of incoming garbage
+pub(crate) fn size_side_garbage_meter(
+    layout: SideBoardLayout,
+    lines: u32,
+    meter: &mut Query<(&mut Sprite, &mut Transform), With<SideGarbageMeter>>,
+) {
+    let Ok((mut sprite, mut transform)) = meter.get_single_mut() else {
+        return;
+    };
+    let height = lines.min(BOARD_ROWS as u32) as f32 * layout.dot_size;
+    let size = Some(Vec2::new(METER_WIDTH, height));
+    if sprite.custom_size != size {
+        sprite.custom_size = size;
+        transform.translation.y = layout.y - layout.height() / 2.0 + height / 2.0;
+    }
+}
+
+/// Removes the side board when going back to the mode menu
+pub(crate) fn side_board_cleanup_system(mut commands: Commands, view: Query<Entity, With<SideBoardView>>) {
+    for entity in view.iter() {
+        commands.entity(entity).despawn();
+    }
+}
@@ -4,24 +4,31 @@ use bevy::{
     color::Color,
     math::{Vec2, Vec3},
     prelude::{
-        BuildChildren, ChildBuild, Commands, DetectChanges, Entity, NextState, Query, Res, ResMut, Resource,
-        Transform, With,
+        BuildChildren, ChildBuild, Commands, Component, DetectChanges, Entity, NextState, Parent, Query, Ref, Res,
+        ResMut, Transform, With,
     },
     sprite::Sprite,
+    time::Time,
     utils::default,
 };
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::SeedableRng;
 
 use crate::{
-    board::board_check_block_position,
-    common_component::{ActiveBlock, ActiveDot, DropType, GameData, GameState, PreviewDot, DOT_SIZE, SPAWN_X},
+    board::{board_check_block_position, landing_y},
+    common_component::{
+        ActiveBlock, ActiveDot, DropType, GameData, GameOutcome, GameState, PreviewDot, BOARD_COLS, BOARD_ROWS,
+        DOT_SIZE, MAX_GRAVITY_G, SPAWN_X,
+    },
+    player::MainBoard,
     tetromino,
 };
 
 /// https://simon.lc/the-history-of-tetris-randomizers
 /// Guideline Tetris 7-bag randomizer: shuffles all 7 pieces, dispenses one by one,
 /// then refills. Uses a queue so we can peek ahead for the NEXT preview.
-fn new_shuffled_bag() -> Vec<tetromino::Block> {
+fn new_shuffled_bag(rng: &mut StdRng) -> Vec<tetromino::Block> {
     let mut bag = vec![
         tetromino::Block::new_i(),
         tetromino::Block::new_o(),
@@ -31,29 +38,53 @@ fn new_shuffled_bag() -> Vec<tetromino::Block> {
         tetromino::Block::new_j(),
         tetromino::Block::new_l(),
     ];
-    let mut rng = rand::thread_rng();
-    bag.shuffle(&mut rng);
+    bag.shuffle(rng);
     bag
 }
 
-#[derive(Resource)]
+#[derive(Component, Clone)]
 pub struct Randomizer7Bag {
     queue: VecDeque<tetromino::Block>,
+    /// A fixed sequence (puzzles) runs out instead of refilling from new bags
+    fixed: bool,
+    rng: StdRng,
 }
 
 impl Default for Randomizer7Bag {
     fn default() -> Self {
-        let mut queue = VecDeque::with_capacity(14);
-        queue.extend(new_shuffled_bag());
-        queue.extend(new_shuffled_bag());
-        Self { queue }
+        Self::with_rng(StdRng::from_entropy())
     }
 }
 
 impl Randomizer7Bag {
-    pub fn pop_next(&mut self) -> tetromino::Block {
-        let block = self.queue.pop_front()
-            .expect("randomizer queue should never be empty");
+    /// Two randomizers with the same seed deal the same pieces
+    pub fn seeded(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(mut rng: StdRng) -> Self {
+        let mut queue = VecDeque::with_capacity(14);
+        queue.extend(new_shuffled_bag(&mut rng));
+        queue.extend(new_shuffled_bag(&mut rng));
+        Self {
+            queue,
+            fixed: false,
+            rng,
+        }
+    }
+
+    /// Deals exactly `pieces`, in order, and nothing after them
+    pub fn from_sequence(pieces: Vec<tetromino::Block>) -> Self {
+        Self {
+            queue: pieces.into(),
+            fixed: true,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Next piece; only a fixed sequence can run out
+    pub fn pop_next(&mut self) -> Option<tetromino::Block> {
+        let block = self.queue.pop_front();
         self.ensure_minimum();
         block
     }
@@ -62,50 +93,100 @@ impl Randomizer7Bag {
         self.queue.iter().take(count).collect()
     }
 
+    /// Replaces the piece at `index` in the queue (0 is the next piece); no-op past the end
+    pub fn set(&mut self, index: usize, block: tetromino::Block) {
+        if let Some(slot) = self.queue.get_mut(index) {
+            *slot = block;
+        }
+    }
+
     fn ensure_minimum(&mut self) {
-        if self.queue.len() < 7 {
-            self.queue.extend(new_shuffled_bag());
+        if !self.fixed && self.queue.len() < 7 {
+            self.queue.extend(new_shuffled_bag(&mut self.rng));
         }
     }
 }
 
+/// Deals the next piece on every board that has none falling
 pub fn spawn_block_system(
     mut commands: Commands,
-    mut randomizer: ResMut<Randomizer7Bag>,
-    query: Query<Entity, With<ActiveBlock>>,
-    mut state: ResMut<NextState<DropType>>,
-    mut game_data: ResMut<GameData>,
+    mut boards: Query<(Entity, &mut GameData, &mut Randomizer7Bag)>,
+    active_blocks: Query<&Parent, With<ActiveBlock>>,
     mut game_state: ResMut<NextState<GameState>>,
+    time: Res<Time>,
+) {
+    for (board, mut game_data, mut randomizer) in boards.iter_mut() {
+        if active_blocks.iter().any(|parent| parent.get() == board) {
+            continue;
+        }
+
+        // Gameplay is suspended while cleared rows animate out and during ARE
+        if !game_data.clearing_rows.is_empty() {
+            continue;
+        }
+        if game_data.entry_delay_active {
+            if !game_data.entry_delay_timer.tick(time.delta()).finished() {
+                continue;
+            }
+            game_data.entry_delay_active = false;
+        }
+
+        let Some(block) = randomizer.pop_next() else {
+            continue;
+        };
+        deal_block(&mut commands, board, &mut game_data, block, &mut game_state);
+    }
+}
+
+/// Puts `block` into play as the falling piece of `board`, fresh from the queue or out of hold. A piece that
+/// does not fit at its spawn position tops the board out.
+pub(crate) fn deal_block(
+    commands: &mut Commands,
+    board: Entity,
+    game_data: &mut GameData,
+    block: tetromino::Block,
+    game_state: &mut NextState<GameState>,
 ) {
-    if query.iter().count() > 0 {
+    if !can_spawn(&game_data.board_matrix, &block) {
+        game_data.outcome = GameOutcome::TopOut;
+        game_state.set(GameState::GameOver);
         return;
     }
 
-    let block = randomizer.pop_next();
+    game_data.drop_type = DropType::Normal;
+    game_data.gravity_progress = 0.0;
+    game_data.piece_inputs = 0;
+    let mut offset_y = spawn_offset_y(&block);
+    // At 20G the piece never shows at the top, it appears already on the floor
+    if game_data.gravity >= MAX_GRAVITY_G {
+        let spawn_y = DOT_SIZE * 1.5 + offset_y;
+        offset_y += landing_y(&game_data.board_matrix, SPAWN_X, spawn_y, &block) - spawn_y;
+    }
+    spawn_block(commands, board, block, 0.0, offset_y);
+}
+
+/// Vertical offset from the board center at which a new piece appears
+pub(crate) fn spawn_offset_y(block: &tetromino::Block) -> f32 {
     let mut transform_y_times: f32 = 8.0;
     if let tetromino::Block::I { .. } = block {
         transform_y_times = 9.0
     };
+    DOT_SIZE * transform_y_times
+}
 
-    let spawn_x = SPAWN_X;
-    let spawn_y = DOT_SIZE * 1.5 + DOT_SIZE * transform_y_times;
-
-    if !board_check_block_position(&game_data.board_matrix, spawn_x, spawn_y, &block) {
-        game_state.set(GameState::GameOver);
-        return;
-    }
-
-    state.set(DropType::Normal);
-    game_data.drop_timer.reset();
-    spawn_block(&mut commands, block, 0.0, DOT_SIZE * transform_y_times);
+/// Whether `block` fits at its spawn position
+pub(crate) fn can_spawn(board: &[[i8; BOARD_COLS]; BOARD_ROWS], block: &tetromino::Block) -> bool {
+    board_check_block_position(board, SPAWN_X, DOT_SIZE * 1.5 + spawn_offset_y(block), block)
 }
 
+/// Spawns `block` as the falling piece of `board`, offset from the spawn position
 pub fn spawn_block(
     commands: &mut Commands,
+    board: Entity,
     block: tetromino::Block,
     transform_x: f32,
     transform_y: f32,
-) {
+) -> Entity {
     let dots: [tetromino::Dot; 4] = block.dots_by_state();
     let color: Color = block.color();
 
@@ -123,6 +204,7 @@ pub fn spawn_block(
             tetromino::Rotation {},
             ActiveBlock,
         ))
+        .set_parent(board)
         .with_children(|parent| {
             for dot in dots.iter() {
                 parent.spawn((
@@ -138,7 +220,8 @@ pub fn spawn_block(
                     ActiveDot,
                 ));
             }
-        });
+        })
+        .id()
 }
 
 // NEXT box world coordinates (right panel center x=165, inner box center y=70)
@@ -147,11 +230,26 @@ const NEXT_BOX_CENTER_Y: f32 = 70.0;
 const PREVIEW_DOT_SIZE: f32 = 11.25;
 const PREVIEW_SLOT_HEIGHT: f32 = 50.0;
 
-pub fn update_preview_system(
+pub const PREVIEW_SLOTS: usize = 6;
+
+/// Preview slot (0 is the next piece) shown at a world position
+pub(crate) fn preview_slot_at(x: f32, y: f32) -> Option<usize> {
+    if (x - NEXT_BOX_CENTER_X).abs() > PREVIEW_SLOT_HEIGHT / 2.0 {
+        return None;
+    }
+    let first_slot_top = NEXT_BOX_CENTER_Y + 125.0 + PREVIEW_SLOT_HEIGHT / 2.0;
+    let slot = (first_slot_top - y) / PREVIEW_SLOT_HEIGHT;
+    (slot >= 0.0 && slot < PREVIEW_SLOTS as f32).then_some(slot as usize)
+}
+
+pub(crate) fn update_preview_system(
     mut commands: Commands,
-    randomizer: Res<Randomizer7Bag>,
+    randomizer: Query<Ref<Randomizer7Bag>, With<MainBoard>>,
     preview_dots: Query<Entity, With<PreviewDot>>,
 ) {
+    let Ok(randomizer) = randomizer.get_single() else {
+        return;
+    };
     if !randomizer.is_changed() {
         return;
     }
@@ -160,7 +258,7 @@ pub fn update_preview_system(
         commands.entity(entity).despawn();
     }
 
-    let upcoming = randomizer.peek(6);
+    let upcoming = randomizer.peek(PREVIEW_SLOTS);
     for (i, block) in upcoming.iter().enumerate() {
         let dots = block.dots_by_state();
         let color = block.color();
@@ -233,12 +331,55 @@ mod tests {
         let mut r = Randomizer7Bag::default();
         let mut types = HashSet::new();
         for _ in 0..7 {
-            let block = r.pop_next();
+            let block = r.pop_next().expect("bag never runs out");
             types.insert(block_type_name(&block));
         }
         assert_eq!(types.len(), 7, "First 7 pieces should contain all 7 types");
     }
 
+    #[test]
+    fn same_seed_deals_the_same_pieces() {
+        let mut a = Randomizer7Bag::seeded(42);
+        let mut b = Randomizer7Bag::seeded(42);
+        for _ in 0..30 {
+            assert_eq!(a.pop_next().map(|p| p.letter()), b.pop_next().map(|p| p.letter()));
+        }
+    }
+
+    #[test]
+    fn fixed_sequence_runs_out() {
+        let mut r = Randomizer7Bag::from_sequence(vec![tetromino::Block::new_t(), tetromino::Block::new_i()]);
+        assert_eq!(r.pop_next().map(|b| b.letter()), Some('T'));
+        assert_eq!(r.pop_next().map(|b| b.letter()), Some('I'));
+        assert!(r.pop_next().is_none());
+        assert!(r.peek(1).is_empty());
+    }
+
+    #[test]
+    fn set_replaces_a_queued_piece() {
+        let mut r = Randomizer7Bag::from_sequence(vec![tetromino::Block::new_t(), tetromino::Block::new_i()]);
+        r.set(1, tetromino::Block::new_o());
+        r.set(5, tetromino::Block::new_s());
+        let letters: Vec<char> = r.peek(6).iter().map(|b| b.letter()).collect();
+        assert_eq!(letters, vec!['T', 'O']);
+    }
+
+    #[test]
+    fn preview_slots_from_world_position() {
+        assert_eq!(preview_slot_at(NEXT_BOX_CENTER_X, NEXT_BOX_CENTER_Y + 125.0), Some(0));
+        assert_eq!(preview_slot_at(NEXT_BOX_CENTER_X + 10.0, NEXT_BOX_CENTER_Y - 125.0), Some(5));
+        assert_eq!(preview_slot_at(NEXT_BOX_CENTER_X, NEXT_BOX_CENTER_Y - 160.0), None);
+        assert_eq!(preview_slot_at(0.0, NEXT_BOX_CENTER_Y), None);
+    }
+
+    #[test]
+    fn spawn_blocked_by_stack_at_the_top() {
+        let mut board = [[0i8; BOARD_COLS]; BOARD_ROWS];
+        assert!(can_spawn(&board, &tetromino::Block::new_t()));
+        board[0] = [1; BOARD_COLS];
+        assert!(!can_spawn(&board, &tetromino::Block::new_t()));
+    }
+
     #[test]
     fn auto_refills_after_depletion() {
         let mut r = Randomizer7Bag::default();
@@ -249,6 +390,6 @@ mod tests {
         // Should have auto-refilled
         assert!(r.queue.len() >= 7);
         // Can still pop
-        let _block = r.pop_next();
+        assert!(r.pop_next().is_some());
     }
 }
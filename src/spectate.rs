@@ -0,0 +1,764 @@
+use std::io;
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use bevy::color::{Alpha, Color};
+use bevy::input::ButtonInput;
+use bevy::prelude::{
+    Commands, Component, DespawnRecursiveExt, Entity, KeyCode, NextState, Parent, Query, Res, ResMut, Resource,
+    State, Text, TextColor, TextFont, Timer, TimerMode, Transform, With,
+};
+use bevy::sprite::Sprite;
+use bevy::text::Text2d;
+use bevy::time::Time;
+use bevy::utils::default;
+
+use crate::board::{spawn_board_dot, BoardDot, BLOCK_CELL, EMPTY_CELL, GARBAGE_CELL, GARBAGE_COLOR};
+use crate::common_component::{
+    ActiveBlock, GameData, GameState, GhostDot, HoldDot, ModeDescriptionText, PreviewDot, StartScreenOverlay,
+    BOARD_COLS, BOARD_ROWS, DOT_SIZE, SPAWN_X,
+};
+use crate::game_mode::{GameMode, ModeSettings};
+use crate::ghost::GhostTracker;
+use crate::hold::HoldTracker;
+use crate::online::{connect_in_background, Link};
+use crate::player::MainBoard;
+use crate::spawn_block_system::{spawn_block, Randomizer7Bag};
+use crate::tetromino::{self, Block};
+
+/// Port a game that allows spectators listens on
+pub(crate) const SPECTATOR_PORT: u16 = 7900;
+/// Bumped whenever the view changes, so mismatched builds refuse to be watched
+const VIEW_VERSION: u32 = 1;
+const GREETING: &str = "SPECTATE";
+/// As many upcoming pieces as the NEXT box shows
+const NEXT_SHOWN: usize = 6;
+/// Shortest time between two views sent, so spectators get at most 20 a second
+const VIEW_INTERVAL: Duration = Duration::from_millis(50);
+/// A spectator whose connection falls this many bytes behind is dropped rather than queued for without end
+const MAX_UNSENT: usize = 64 * 1024;
+
+/// The falling piece as a spectator sees it, in cells from the spawn position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PieceView {
+    pub letter: char,
+    pub state: u8,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Everything shown of the watched player's board, sent again whenever any of it changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct View {
+    /// `Menu`, `Playing`, `Paused`, `Over` or `Watching`
+    pub state: String,
+    pub score: u32,
+    pub lines: u32,
+    pub level: u32,
+    pub time_ms: u64,
+    pub held: Option<char>,
+    pub hold_used: bool,
+    /// Letters of the upcoming pieces
+    pub next: String,
+    pub piece: Option<PieceView>,
+    /// Cells row by row from the top: `.` empty, `G` garbage, or the letter of the piece that left it
+    pub cells: String,
+    pub mode: String,
+}
+
+impl View {
+    /// The main board as it is rendered right now
+    fn capture(
+        state: &GameState,
+        mode: GameMode,
+        game_data: &GameData,
+        queue: &Randomizer7Bag,
+        cells: impl Iterator<Item = (i8, i8, Color)>,
+        piece: Option<(&Block, &Transform)>,
+    ) -> Self {
+        let mut grid = vec!['.'; BOARD_COLS * BOARD_ROWS];
+        for (x, y, color) in cells {
+            if (0..BOARD_COLS as i8).contains(&x) && (0..BOARD_ROWS as i8).contains(&y) {
+                grid[y as usize * BOARD_COLS + x as usize] = cell_letter(color);
+            }
+        }
+        Self {
+            state: match state {
+                GameState::StartScreen => "Menu",
+                GameState::Playing => "Playing",
+                GameState::Paused => "Paused",
+                GameState::GameOver => "Over",
+                GameState::Watching => "Watching",
+            }
+            .to_string(),
+            score: game_data.score,
+            lines: game_data.lines_cleared,
+            level: game_data.level,
+            time_ms: game_data.play_time.elapsed().as_millis() as u64,
+            held: game_data.held_block.as_ref().map(Block::letter),
+            hold_used: game_data.hold_used,
+            next: queue.peek(NEXT_SHOWN).into_iter().map(Block::letter).collect(),
+            piece: piece.map(|(block, transform)| PieceView {
+                letter: block.letter(),
+                state: state_index(block.state()),
+                x: ((transform.translation.x - SPAWN_X) / DOT_SIZE).round() as i32,
+                y: ((transform.translation.y - DOT_SIZE * 1.5) / DOT_SIZE).round() as i32,
+            }),
+            cells: grid.into_iter().collect(),
+            mode: mode.name().to_string(),
+        }
+    }
+
+    fn encode(&self) -> String {
+        let piece = match &self.piece {
+            Some(piece) => format!("{}:{}:{}:{}", piece.letter, piece.state, piece.x, piece.y),
+            None => "-".to_string(),
+        };
+        format!(
+            "VIEW {} {} {} {} {} {} {} {} {piece} {} {}",
+            self.state,
+            self.score,
+            self.lines,
+            self.level,
+            self.time_ms,
+            self.held.unwrap_or('-'),
+            u8::from(self.hold_used),
+            if self.next.is_empty() { "-" } else { &self.next },
+            self.cells,
+            self.mode,
+        )
+    }
+
+    fn decode(line: &str) -> Option<View> {
+        let mut words = line.splitn(12, ' ');
+        if words.next()? != "VIEW" {
+            return None;
+        }
+        let state = words.next()?.to_string();
+        let score = words.next()?.parse().ok()?;
+        let lines = words.next()?.parse().ok()?;
+        let level = words.next()?.parse().ok()?;
+        let time_ms = words.next()?.parse().ok()?;
+        let held = match words.next()? {
+            "-" => None,
+            letter => Some(piece_letter(letter)?),
+        };
+        let hold_used = match words.next()? {
+            "0" => false,
+            "1" => true,
+            _ => return None,
+        };
+        let next = match words.next()? {
+            "-" => String::new(),
+            letters => letters.chars().map(|c| Block::from_letter(c).map(|_| c)).collect::<Option<String>>()?,
+        };
+        let piece = match words.next()? {
+            "-" => None,
+            piece => {
+                let mut parts = piece.split(':');
+                let view = PieceView {
+                    letter: piece_letter(parts.next()?)?,
+                    state: parts.next()?.parse().ok().filter(|state| *state < 4)?,
+                    x: parts.next()?.parse().ok()?,
+                    y: parts.next()?.parse().ok()?,
+                };
+                Some(parts.next().is_none().then_some(view)?)
+            }
+        };
+        let cells = words.next()?.to_string();
+        let valid_cells = cells.len() == BOARD_COLS * BOARD_ROWS
+            && cells.chars().all(|c| c == '.' || c == 'G' || Block::from_letter(c).is_some());
+        if !valid_cells {
+            return None;
+        }
+        Some(View {
+            state,
+            score,
+            lines,
+            level,
+            time_ms,
+            held,
+            hold_used,
+            next,
+            piece,
+            cells,
+            mode: words.next().unwrap_or_default().to_string(),
+        })
+    }
+
+    fn board_matrix(&self) -> [[i8; BOARD_COLS]; BOARD_ROWS] {
+        let mut board = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+        for (i, c) in self.cells.chars().enumerate() {
+            board[i / BOARD_COLS][i % BOARD_COLS] = match c {
+                '.' => EMPTY_CELL,
+                'G' => GARBAGE_CELL,
+                _ => BLOCK_CELL,
+            };
+        }
+        board
+    }
+}
+
+fn piece_letter(word: &str) -> Option<char> {
+    let mut chars = word.chars();
+    let letter = chars.next()?;
+    (chars.next().is_none() && Block::from_letter(letter).is_some()).then_some(letter)
+}
+
+/// The piece whose color a settled cell has; anything else (garbage) is `G`. Cells fading out of a
+/// line clear keep their piece.
+fn cell_letter(color: Color) -> char {
+    let color = color.to_srgba().with_alpha(1.0);
+    "IOTSZJL"
+        .chars()
+        .find(|&letter| Block::from_letter(letter).is_some_and(|block| block.color().to_srgba() == color))
+        .unwrap_or('G')
+}
+
+fn cell_color(letter: char) -> Color {
+    Block::from_letter(letter).map_or(GARBAGE_COLOR, |block| block.color())
+}
+
+fn state_index(state: &tetromino::State) -> u8 {
+    match state {
+        tetromino::State::Zero => 0,
+        tetromino::State::One => 1,
+        tetromino::State::Two => 2,
+        tetromino::State::Three => 3,
+    }
+}
+
+fn state_from_index(index: u8) -> tetromino::State {
+    match index {
+        1 => tetromino::State::One,
+        2 => tetromino::State::Two,
+        3 => tetromino::State::Three,
+        _ => tetromino::State::Zero,
+    }
+}
+
+/// Lets spectators connect while the player allows it, and keeps them up to date
+#[derive(Resource)]
+pub(crate) struct SpectatorServer {
+    listener: Option<TcpListener>,
+    spectators: Vec<Link>,
+    /// The last view sent, so an unchanged board is not sent again and newcomers see the board at once
+    last_view: Option<String>,
+    /// Time left before the next view may be sent
+    view_timer: Timer,
+}
+
+impl Default for SpectatorServer {
+    fn default() -> Self {
+        Self {
+            listener: None,
+            spectators: Vec::new(),
+            last_view: None,
+            view_timer: Timer::new(VIEW_INTERVAL, TimerMode::Once),
+        }
+    }
+}
+
+impl SpectatorServer {
+    pub fn open(&mut self, port: u16) -> io::Result<()> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        listener.set_nonblocking(true)?;
+        self.listener = Some(listener);
+        Ok(())
+    }
+
+    pub fn close(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.listener.is_some()
+    }
+
+    /// Takes in new spectators and drops those who left
+    fn accept(&mut self) {
+        let Some(listener) = &self.listener else {
+            return;
+        };
+        while let Ok((stream, _)) = listener.accept() {
+            let Ok(mut link) = Link::new(stream) else {
+                continue;
+            };
+            let greeted = link.send_line(&format!("{GREETING} {VIEW_VERSION}")).is_ok()
+                && self.last_view.as_ref().is_none_or(|view| link.send_line(view).is_ok());
+            if greeted {
+                self.spectators.push(link);
+            }
+        }
+        // Spectators have nothing to say, so anything but silence means they are gone
+        self.spectators.retain_mut(|link| link.receive_lines().is_ok());
+    }
+
+    /// Whether the next view may be sent, `delta` after the last call
+    fn view_due(&mut self, delta: Duration) -> bool {
+        self.view_timer.tick(delta).finished()
+    }
+
+    /// Sends `view` to every spectator if it differs from the last one sent
+    fn broadcast(&mut self, view: &View) {
+        let line = view.encode();
+        if self.last_view.as_ref() == Some(&line) {
+            return;
+        }
+        self.spectators.retain_mut(|link| link.send_line(&line).is_ok() && link.unsent_len() <= MAX_UNSENT);
+        self.last_view = Some(line);
+        self.view_timer.reset();
+    }
+}
+
+/// Opens or closes the spectator port as the player allows spectators (F2 on the menu), and sends
+/// the main board to everyone watching whenever it changes, at most every `VIEW_INTERVAL`
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn spectator_broadcast_system(
+    mut server: ResMut<SpectatorServer>,
+    mut settings: ResMut<ModeSettings>,
+    (state, mode, time): (Res<State<GameState>>, Res<GameMode>, Res<Time>),
+    boards: Query<(Entity, &GameData, &Randomizer7Bag), With<MainBoard>>,
+    board_dots: Query<(&Parent, &BoardDot, &Sprite)>,
+    active_blocks: Query<(&Parent, &Block, &Transform), With<ActiveBlock>>,
+) {
+    if settings.allow_spectators != server.is_open() {
+        if !settings.allow_spectators {
+            server.close();
+        } else if let Err(err) = server.open(SPECTATOR_PORT) {
+            bevy::log::warn!("cannot allow spectators on port {SPECTATOR_PORT}: {err}");
+            settings.allow_spectators = false;
+        }
+    }
+    if !server.is_open() {
+        return;
+    }
+    server.accept();
+    if !server.view_due(time.delta()) {
+        return;
+    }
+
+    let Ok((board, game_data, queue)) = boards.get_single() else {
+        return;
+    };
+    let cells = board_dots
+        .iter()
+        .filter(|(parent, ..)| parent.get() == board)
+        .map(|(_, board_dot, sprite)| (board_dot.board_x, board_dot.board_y, sprite.color));
+    let piece = active_blocks
+        .iter()
+        .find(|(parent, ..)| parent.get() == board)
+        .map(|(_, block, transform)| (block, transform));
+    server.broadcast(&View::capture(state.get(), *mode, game_data, queue, cells, piece));
+}
+
+#[derive(Default)]
+enum Watch {
+    #[default]
+    Closed,
+    Connecting {
+        address: String,
+        attempt: JoinHandle<io::Result<TcpStream>>,
+    },
+    Open {
+        address: String,
+        link: Link,
+        greeted: bool,
+    },
+}
+
+/// The connection to a watched game and the last view it sent
+#[derive(Resource, Default)]
+pub(crate) struct Spectator {
+    connection: Watch,
+    /// Why the last connection attempt or connection ended
+    problem: Option<String>,
+    /// A view that arrived and is not on the board yet
+    pending: Option<View>,
+    /// The view on the board, so only what changed is rebuilt
+    shown: Option<View>,
+}
+
+impl Spectator {
+    /// Connects to a game allowing spectators at `address` (`host:port`)
+    pub fn watch(&mut self, address: &str) {
+        self.close();
+        match connect_in_background(address) {
+            Ok(attempt) => {
+                self.connection = Watch::Connecting {
+                    address: address.to_string(),
+                    attempt,
+                }
+            }
+            Err(e) => self.problem = Some(format!("Cannot connect to {address}: {e}")),
+        }
+    }
+
+    pub fn close(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        matches!(self.connection, Watch::Closed)
+    }
+
+    fn address(&self) -> &str {
+        match &self.connection {
+            Watch::Closed => "",
+            Watch::Connecting { address, .. } | Watch::Open { address, .. } => address,
+        }
+    }
+
+    fn fail(&mut self, problem: String) {
+        self.connection = Watch::Closed;
+        self.problem = Some(problem);
+    }
+
+    /// Moves the connection along; true once the watched game has sent a view
+    fn poll(&mut self) -> bool {
+        match std::mem::take(&mut self.connection) {
+            Watch::Connecting { address, attempt } if attempt.is_finished() => {
+                match attempt.join() {
+                    Ok(Ok(stream)) => match Link::new(stream) {
+                        Ok(link) => {
+                            self.connection = Watch::Open {
+                                address,
+                                link,
+                                greeted: false,
+                            }
+                        }
+                        Err(e) => self.fail(format!("Cannot connect to {address}: {e}")),
+                    },
+                    Ok(Err(e)) => self.fail(format!("Cannot connect to {address}: {e}")),
+                    Err(_) => self.fail(format!("Cannot connect to {address}")),
+                }
+            }
+            connection => self.connection = connection,
+        }
+
+        let Watch::Open { link, greeted, .. } = &mut self.connection else {
+            return false;
+        };
+        let lines = match link.receive_lines() {
+            Ok(lines) => lines,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                self.fail("The game stopped allowing spectators".to_string());
+                return false;
+            }
+            Err(e) => {
+                self.fail(format!("Lost the connection: {e}"));
+                return false;
+            }
+        };
+        for line in lines {
+            if !*greeted {
+                if line != format!("{GREETING} {VIEW_VERSION}") {
+                    self.fail("The game runs a different version".to_string());
+                    return false;
+                }
+                *greeted = true;
+                continue;
+            }
+            let Some(view) = View::decode(&line) else {
+                self.fail("The game sent something unexpected".to_string());
+                return false;
+            };
+            self.pending = Some(view);
+        }
+        self.pending.is_some() || self.shown.is_some()
+    }
+
+    /// One line about the connection for the lobby
+    fn status(&self) -> String {
+        match &self.connection {
+            Watch::Closed => {
+                let hint = "Press Enter to watch";
+                match &self.problem {
+                    Some(problem) => format!("{problem}\n{hint}"),
+                    None => hint.to_string(),
+                }
+            }
+            Watch::Connecting { address, .. } => format!("Connecting to {address}...\nEsc: stop"),
+            Watch::Open { address, .. } => format!("Connected to {address}, waiting for the board\nEsc: stop"),
+        }
+    }
+}
+
+/// Connects to a watched game from the mode menu: Enter in Watch connects, Esc hangs up, and
+/// starting another mode hangs up too. The board appears once the game sends it.
+pub(crate) fn watch_lobby_system(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    (mode, settings): (Res<GameMode>, Res<ModeSettings>),
+    mut spectator: ResMut<Spectator>,
+    overlay: Query<Entity, With<StartScreenOverlay>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if *mode != GameMode::Watch {
+        if input.just_pressed(KeyCode::Enter) {
+            spectator.close();
+        }
+        return;
+    }
+    if input.just_pressed(KeyCode::Enter) && spectator.is_closed() {
+        spectator.watch(&settings.watch_address);
+    }
+    if input.just_pressed(KeyCode::Escape) {
+        spectator.close();
+    }
+    if spectator.poll() {
+        for entity in overlay.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        next_state.set(GameState::Watching);
+    }
+}
+
+/// Shows the connection status under the Watch mode description
+pub(crate) fn watch_lobby_display_system(
+    mode: Res<GameMode>,
+    spectator: Res<Spectator>,
+    mut description: Query<&mut Text, With<ModeDescriptionText>>,
+) {
+    if *mode != GameMode::Watch {
+        return;
+    }
+    let Ok(mut text) = description.get_single_mut() else {
+        return;
+    };
+    let content = format!("{}\n\n{}", mode.description(), spectator.status());
+    if text.0 != content {
+        text.0 = content;
+    }
+}
+
+/// Line above the board telling whose game is being watched
+#[derive(Component)]
+pub(crate) struct SpectatorBanner;
+
+pub(crate) fn spawn_spectator_banner(mut commands: Commands) {
+    commands.spawn((
+        Text2d::new(""),
+        TextColor(Color::WHITE),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        Transform::from_xyz(0.0, 280.0, 3.0),
+        SpectatorBanner,
+    ));
+}
+
+/// Puts every view the watched game sends on the main board, rendered like a local game.
+/// Esc, or the game going away, goes back to the menu.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn spectate_system(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    mut spectator: ResMut<Spectator>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut boards: Query<(Entity, &mut GameData, &mut Randomizer7Bag), With<MainBoard>>,
+    board_dots: Query<(Entity, &Parent), With<BoardDot>>,
+    active_blocks: Query<(Entity, &Parent), With<ActiveBlock>>,
+    mut banner: Query<&mut Text2d, With<SpectatorBanner>>,
+) {
+    if input.just_pressed(KeyCode::Escape) {
+        spectator.close();
+    }
+    spectator.poll();
+    if spectator.is_closed() {
+        next_state.set(GameState::StartScreen);
+        return;
+    }
+    let Some(view) = spectator.pending.take() else {
+        return;
+    };
+    let Ok((board, mut game_data, mut queue)) = boards.get_single_mut() else {
+        return;
+    };
+    let shown = spectator.shown.take();
+
+    game_data.board_matrix = view.board_matrix();
+    game_data.score = view.score;
+    game_data.lines_cleared = view.lines;
+    game_data.level = view.level;
+    game_data.play_time.set_elapsed(Duration::from_millis(view.time_ms));
+    game_data.held_block = view.held.and_then(Block::from_letter);
+    game_data.hold_used = view.hold_used;
+    if shown.as_ref().is_none_or(|shown| shown.next != view.next) {
+        *queue = Randomizer7Bag::from_sequence(view.next.chars().filter_map(Block::from_letter).collect());
+    }
+    if shown.as_ref().is_none_or(|shown| shown.cells != view.cells) {
+        for (entity, parent) in board_dots.iter() {
+            if parent.get() == board {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        for (i, letter) in view.cells.chars().enumerate().filter(|(_, letter)| *letter != '.') {
+            spawn_board_dot(&mut commands, board, (i % BOARD_COLS) as i8, (i / BOARD_COLS) as i8, cell_color(letter));
+        }
+    }
+    if shown.as_ref().is_none_or(|shown| shown.piece != view.piece) {
+        for (entity, parent) in active_blocks.iter() {
+            if parent.get() == board {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        if let Some(piece) = &view.piece {
+            if let Some(mut block) = Block::from_letter(piece.letter) {
+                block.set_state(state_from_index(piece.state));
+                spawn_block(&mut commands, board, block, piece.x as f32 * DOT_SIZE, piece.y as f32 * DOT_SIZE);
+            }
+        }
+    }
+    if let Ok(mut text) = banner.get_single_mut() {
+        text.0 = format!("Watching {}: {} - {}", spectator.address(), view.mode, view.state);
+    }
+    spectator.shown = Some(view);
+}
+
+/// Clears the watched board off the main board when going back to the menu
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn stop_watching_system(
+    mut commands: Commands,
+    mut spectator: ResMut<Spectator>,
+    mut boards: Query<(&mut GameData, &mut Randomizer7Bag, &mut GhostTracker), With<MainBoard>>,
+    board_dots: Query<Entity, With<BoardDot>>,
+    active_blocks: Query<Entity, With<ActiveBlock>>,
+    ghost_dots: Query<Entity, With<GhostDot>>,
+    preview_dots: Query<Entity, With<PreviewDot>>,
+    hold_dots: Query<Entity, With<HoldDot>>,
+    banner: Query<Entity, With<SpectatorBanner>>,
+    mut hold_tracker: ResMut<HoldTracker>,
+) {
+    spectator.close();
+    for (mut game_data, mut queue, mut ghost_tracker) in boards.iter_mut() {
+        *game_data = GameData::default();
+        *queue = Randomizer7Bag::default();
+        *ghost_tracker = GhostTracker::default();
+    }
+    *hold_tracker = HoldTracker::default();
+    for entity in board_dots.iter().chain(active_blocks.iter()).chain(ghost_dots.iter()).chain(banner.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    for entity in preview_dots.iter().chain(hold_dots.iter()) {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_view() -> View {
+        let mut cells = vec!['.'; BOARD_COLS * BOARD_ROWS];
+        cells[BOARD_COLS * BOARD_ROWS - 1] = 'G';
+        cells[BOARD_COLS * BOARD_ROWS - 2] = 'T';
+        View {
+            state: "Playing".to_string(),
+            score: 1200,
+            lines: 8,
+            level: 2,
+            time_ms: 61_250,
+            held: Some('I'),
+            hold_used: true,
+            next: "SZJLOT".to_string(),
+            piece: Some(PieceView {
+                letter: 'L',
+                state: 3,
+                x: -2,
+                y: 5,
+            }),
+            cells: cells.into_iter().collect(),
+            mode: "Sprint 40".to_string(),
+        }
+    }
+
+    #[test]
+    fn views_survive_encoding() {
+        let view = sample_view();
+        assert_eq!(View::decode(&view.encode()), Some(view.clone()));
+
+        let empty = View {
+            held: None,
+            hold_used: false,
+            next: String::new(),
+            piece: None,
+            ..view
+        };
+        assert_eq!(View::decode(&empty.encode()), Some(empty));
+    }
+
+    #[test]
+    fn malformed_views_are_rejected() {
+        let line = sample_view().encode();
+        assert_eq!(View::decode(&line.replace("VIEW", "BOARD")), None);
+        assert_eq!(View::decode(&line.replace("L:3", "L:4")), None);
+        assert_eq!(View::decode(&line.replace("SZJLOT", "SZJLOX")), None);
+        assert_eq!(View::decode(&line.replace("TG ", "TX ")), None);
+        assert_eq!(View::decode("VIEW Playing 1 2"), None);
+    }
+
+    #[test]
+    fn cells_keep_the_color_of_their_piece() {
+        let faded = Block::new_s().color().with_alpha(0.3);
+        assert_eq!(cell_letter(faded), 'S');
+        assert_eq!(cell_letter(GARBAGE_COLOR), 'G');
+        assert_eq!(cell_color('G'), GARBAGE_COLOR);
+        assert_eq!(cell_color('J'), Block::new_j().color());
+        assert_eq!(sample_view().board_matrix()[BOARD_ROWS - 1][BOARD_COLS - 2..], [BLOCK_CELL, GARBAGE_CELL]);
+    }
+
+    #[test]
+    fn views_are_sent_at_a_capped_rate() {
+        let mut server = SpectatorServer::default();
+        assert!(server.view_due(VIEW_INTERVAL));
+        server.broadcast(&sample_view());
+        assert!(!server.view_due(VIEW_INTERVAL / 2));
+        assert!(server.view_due(VIEW_INTERVAL / 2));
+    }
+
+    #[test]
+    fn a_spectator_on_this_machine_sees_the_board() {
+        let mut server = SpectatorServer::default();
+        server.open(0).expect("a free port");
+        let port = server.listener.as_ref().and_then(|listener| listener.local_addr().ok()).unwrap().port();
+        let view = sample_view();
+        server.broadcast(&view);
+
+        let mut spectator = Spectator::default();
+        spectator.watch(&format!("127.0.0.1:{port}"));
+        for _ in 0..500 {
+            server.accept();
+            if spectator.poll() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(spectator.pending.take(), Some(view.clone()));
+
+        // Later changes reach spectators already watching
+        let later = View { score: 1500, ..view };
+        server.broadcast(&later);
+        for _ in 0..500 {
+            spectator.poll();
+            if spectator.pending.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(spectator.pending, Some(later));
+
+        // Closing the game tells the spectator
+        server.close();
+        for _ in 0..500 {
+            spectator.poll();
+            if spectator.is_closed() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(spectator.is_closed());
+    }
+}
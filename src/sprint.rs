@@ -0,0 +1,161 @@
+use std::time::Duration;
+
+use bevy::prelude::{Has, NextState, Query, Res, ResMut, Resource, Text, With};
+
+use crate::bot::Bot;
+use crate::common_component::{GameData, GameOutcome, GameState, SplitsText};
+use crate::game_mode::{format_delta, format_time, GameMode};
+use crate::player::MainBoard;
+use crate::records::Records;
+
+pub const SPRINT_LINES: u32 = 40;
+pub const SPRINT_SPLIT_LINES: u32 = 10;
+
+/// Final time of the last finished sprint and the personal best it was compared against
+#[derive(Resource, Default)]
+pub(crate) struct SprintResult {
+    pub time: Duration,
+    pub previous_best: Option<Duration>,
+    pub previous_best_splits: Vec<Duration>,
+}
+
+/// Records a split every 10 lines and finishes the run once 40 lines are cleared. A bot's run (the F3 demo)
+/// is compared against the personal best but never becomes one.
+pub(crate) fn sprint_progress_system(
+    mut boards: Query<(&mut GameData, Has<Bot>)>,
+    mut records: ResMut<Records>,
+    mut result: ResMut<SprintResult>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (mut game_data, bot) in boards.iter_mut() {
+        let elapsed = game_data.play_time.elapsed();
+        let reached = (game_data.lines_cleared.min(SPRINT_LINES) / SPRINT_SPLIT_LINES) as usize;
+        while game_data.splits.len() < reached {
+            game_data.splits.push(elapsed);
+        }
+
+        if game_data.lines_cleared < SPRINT_LINES {
+            continue;
+        }
+
+        game_data.outcome = GameOutcome::Cleared;
+        let previous_best_splits = records.sprint_best_splits.clone();
+        let previous_best = if bot {
+            records.sprint_best
+        } else {
+            records.submit_sprint(elapsed, &game_data.splits)
+        };
+        *result = SprintResult {
+            time: elapsed,
+            previous_best,
+            previous_best_splits,
+        };
+        next_state.set(GameState::GameOver);
+    }
+}
+
+/// Lists the splits the main board reached so far next to it, with the difference to the personal best
+pub(crate) fn update_splits_display(
+    game_data: Query<&GameData, With<MainBoard>>,
+    mode: Res<GameMode>,
+    records: Res<Records>,
+    mut query: Query<&mut Text, With<SplitsText>>,
+) {
+    let (Ok(game_data), Ok(mut text)) = (game_data.get_single(), query.get_single_mut()) else {
+        return;
+    };
+    if *mode != GameMode::Sprint {
+        if !text.is_empty() {
+            text.clear();
+        }
+        return;
+    }
+    // The side panel is narrow, so the difference goes on its own line
+    let content = splits_lines(&game_data.splits, &records.sprint_best_splits, "\n").join("\n");
+    if **text != content {
+        **text = content;
+    }
+}
+
+pub(crate) fn sprint_result_lines(result: &SprintResult, splits: &[Duration]) -> Vec<String> {
+    let mut lines = vec![format!("Time: {}", format_time(result.time))];
+    match result.previous_best {
+        Some(best) if result.time >= best => {
+            lines.push(format!(
+                "Personal best: {} ({})",
+                format_time(best),
+                format_delta(result.time, best)
+            ));
+        }
+        Some(best) => {
+            lines.push(format!("New personal best! ({})", format_delta(result.time, best)));
+        }
+        None => lines.push("New personal best!".to_string()),
+    }
+    lines.extend(splits_lines(splits, &result.previous_best_splits, " "));
+    lines
+}
+
+fn splits_lines(splits: &[Duration], best_splits: &[Duration], delta_separator: &str) -> Vec<String> {
+    splits
+        .iter()
+        .enumerate()
+        .map(|(i, split)| {
+            let lines = (i as u32 + 1) * SPRINT_SPLIT_LINES;
+            match best_splits.get(i) {
+                Some(best) => format!(
+                    "{lines}: {}{delta_separator}{}",
+                    format_time(*split),
+                    format_delta(*split, *best)
+                ),
+                None => format!("{lines}: {}", format_time(*split)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::World;
+
+    #[test]
+    fn splits_compare_against_best_when_available() {
+        let splits = [Duration::from_secs(10), Duration::from_secs(21)];
+        let best = [Duration::from_secs(11)];
+        let lines = splits_lines(&splits, &best, " ");
+        assert_eq!(lines, vec!["10: 0:10.000 -1.000", "20: 0:21.000"]);
+    }
+
+    #[test]
+    fn result_reports_new_best() {
+        let result = SprintResult {
+            time: Duration::from_secs(50),
+            previous_best: Some(Duration::from_secs(60)),
+            previous_best_splits: Vec::new(),
+        };
+        let lines = sprint_result_lines(&result, &[]);
+        assert_eq!(lines[1], "New personal best! (-10.000)");
+    }
+
+    #[test]
+    fn a_bots_finished_run_is_not_a_record() {
+        let mut world = World::new();
+        world.init_resource::<Records>();
+        world.init_resource::<SprintResult>();
+        world.init_resource::<NextState<GameState>>();
+        let board = world
+            .spawn((
+                GameData {
+                    lines_cleared: SPRINT_LINES,
+                    ..Default::default()
+                },
+                Bot::default(),
+            ))
+            .id();
+        world.run_system_once(sprint_progress_system).expect("the system runs");
+        assert_eq!(world.get::<GameData>(board).expect("a board").outcome, GameOutcome::Cleared);
+        assert_eq!(*world.resource::<Records>(), Records::default());
+    }
+}
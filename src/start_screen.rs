@@ -1,8 +1,21 @@
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
 use bevy::prelude::*;
 
-use crate::common_component::{GameState, StartScreenOverlay};
+use crate::common_component::{
+    GameData, GameState, ModeDescriptionText, ModeMenuEntry, ModeOptionText, TogglesText, StartScreenOverlay,
+};
+use crate::game_mode::{Delays, GameMode, ModeSettings};
+use crate::spectate::SPECTATOR_PORT;
 
-pub fn start_screen_display_system(mut commands: Commands) {
+const MENU_SELECTED_COLOR: Color = Color::srgba(1.0, 1.0, 0.4, 1.0);
+const MENU_IDLE_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.6);
+
+pub fn start_screen_display_system(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    settings: Res<ModeSettings>,
+) {
     commands
         .spawn((
             StartScreenOverlay,
@@ -26,55 +39,275 @@ pub fn start_screen_display_system(mut commands: Commands) {
                 },
                 TextColor(Color::srgba(0.2, 0.8, 1.0, 1.0)),
             ));
-            parent.spawn((
-                Text::new("Controls"),
-                TextFont {
-                    font_size: 22.0,
-                    ..default()
-                },
-                TextColor(Color::srgba(1.0, 1.0, 1.0, 0.9)),
-                Node {
-                    margin: UiRect::top(Val::Px(30.0)),
+            // Modes on the left, the selected mode's details and the controls on the right
+            parent
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::FlexStart,
+                    margin: UiRect::top(Val::Px(10.0)),
                     ..default()
-                },
-            ));
+                })
+                .with_children(|columns| {
+                    columns
+                        .spawn(Node {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            margin: UiRect::right(Val::Px(40.0)),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for menu_mode in GameMode::ALL {
+                                parent.spawn((
+                                    Text::new(menu_entry_label(menu_mode, *mode)),
+                                    TextFont {
+                                        font_size: 22.0,
+                                        ..default()
+                                    },
+                                    TextColor(menu_entry_color(menu_mode, *mode)),
+                                    Node {
+                                        margin: UiRect::top(Val::Px(6.0)),
+                                        ..default()
+                                    },
+                                    ModeMenuEntry(menu_mode),
+                                ));
+                            }
+                        });
+                    columns
+                        .spawn(Node {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::FlexStart,
+                            width: Val::Px(340.0),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn((
+                                Text::new(mode.description()),
+                                TextFont {
+                                    font_size: 16.0,
+                                    ..default()
+                                },
+                                TextColor(Color::srgba(1.0, 1.0, 1.0, 0.7)),
+                                Node {
+                                    margin: UiRect::top(Val::Px(10.0)),
+                                    ..default()
+                                },
+                                ModeDescriptionText,
+                            ));
+                            parent.spawn((
+                                Text::new(settings.option_label(*mode).unwrap_or_default()),
+                                TextFont {
+                                    font_size: 16.0,
+                                    ..default()
+                                },
+                                TextColor(MENU_SELECTED_COLOR),
+                                Node {
+                                    margin: UiRect::top(Val::Px(6.0)),
+                                    ..default()
+                                },
+                                ModeOptionText,
+                            ));
+                            parent.spawn((
+                                Text::new("Controls"),
+                                TextFont {
+                                    font_size: 22.0,
+                                    ..default()
+                                },
+                                TextColor(Color::srgba(1.0, 1.0, 1.0, 0.9)),
+                                Node {
+                                    margin: UiRect::top(Val::Px(30.0)),
+                                    ..default()
+                                },
+                            ));
+                            parent.spawn((
+                                Text::new("Left/Right  Move\nUp          Hard Drop\nDown        Soft Drop\nQ/E         Rotate\nC           Hold\nP           Pause"),
+                                TextFont {
+                                    font_size: 18.0,
+                                    ..default()
+                                },
+                                TextColor(Color::srgba(1.0, 1.0, 1.0, 0.7)),
+                                Node {
+                                    margin: UiRect::top(Val::Px(12.0)),
+                                    ..default()
+                                },
+                            ));
+                        });
+                });
             parent.spawn((
-                Text::new("Left/Right  Move\nUp          Hard Drop\nDown        Soft Drop\nQ/E         Rotate\nC           Hold\nP           Pause"),
+                Text::new("Up/Down: mode, Tab/Left/Right: options, Enter: start"),
                 TextFont {
-                    font_size: 18.0,
+                    font_size: 24.0,
                     ..default()
                 },
-                TextColor(Color::srgba(1.0, 1.0, 1.0, 0.7)),
+                TextColor(Color::srgba(1.0, 1.0, 0.4, 1.0)),
                 Node {
-                    margin: UiRect::top(Val::Px(12.0)),
+                    margin: UiRect::top(Val::Px(24.0)),
                     ..default()
                 },
             ));
             parent.spawn((
-                Text::new("Press Enter to Start"),
+                Text::new(toggles_label(&settings)),
                 TextFont {
-                    font_size: 24.0,
+                    font_size: 16.0,
                     ..default()
                 },
-                TextColor(Color::srgba(1.0, 1.0, 0.4, 1.0)),
+                TextColor(Color::srgba(1.0, 1.0, 1.0, 0.7)),
                 Node {
-                    margin: UiRect::top(Val::Px(36.0)),
+                    margin: UiRect::top(Val::Px(8.0)),
                     ..default()
                 },
+                TogglesText,
             ));
         });
 }
 
+fn toggles_label(settings: &ModeSettings) -> String {
+    let spectators = if settings.allow_spectators {
+        format!("F2: spectators allowed (port {SPECTATOR_PORT})")
+    } else {
+        "F2: allow spectators".to_string()
+    };
+    let bot = if settings.external_bot.is_some() { "external bot" } else { "bot" };
+    let demo = if settings.demo { format!("F3: demo on, the {bot} plays") } else { format!("F3: demo ({bot} plays)") };
+    let hint = if settings.hint { "F4: placement hint on" } else { "F4: placement hint" };
+    let Delays { lock, line_clear, entry } = settings.delays;
+    let delays = format!(
+        "F5: lock delay {} ms    F6: line clear {} ms    F7: ARE {} ms (not in Master or online)",
+        lock.as_millis(),
+        line_clear.as_millis(),
+        entry.as_millis()
+    );
+    format!("{spectators}    {demo}    {hint}\n{delays}")
+}
+
+fn menu_entry_label(entry: GameMode, selected: GameMode) -> String {
+    if entry == selected {
+        format!("> {} <", entry.name())
+    } else {
+        entry.name().to_string()
+    }
+}
+
+fn menu_entry_color(entry: GameMode, selected: GameMode) -> Color {
+    if entry == selected {
+        MENU_SELECTED_COLOR
+    } else {
+        MENU_IDLE_COLOR
+    }
+}
+
 pub fn start_screen_input_system(
     mut commands: Commands,
     input: Res<ButtonInput<KeyCode>>,
     overlay: Query<Entity, With<StartScreenOverlay>>,
     mut next_state: ResMut<NextState<GameState>>,
+    mut mode: ResMut<GameMode>,
+    mut settings: ResMut<ModeSettings>,
+    mut boards: Query<&mut GameData>,
 ) {
-    if input.just_pressed(KeyCode::Enter) {
-        for entity in &overlay {
-            commands.entity(entity).despawn_recursive();
+    if input.just_pressed(KeyCode::ArrowDown) {
+        *mode = mode.next();
+    }
+    if input.just_pressed(KeyCode::ArrowUp) {
+        *mode = mode.previous();
+    }
+    if input.just_pressed(KeyCode::ArrowRight) {
+        settings.adjust(*mode, 1);
+    }
+    if input.just_pressed(KeyCode::ArrowLeft) {
+        settings.adjust(*mode, -1);
+    }
+    if input.just_pressed(KeyCode::Tab) {
+        settings.focus_next_option(*mode);
+    }
+    if input.just_pressed(KeyCode::F2) {
+        settings.allow_spectators = !settings.allow_spectators;
+    }
+    if input.just_pressed(KeyCode::F3) {
+        settings.demo = !settings.demo;
+    }
+    if input.just_pressed(KeyCode::F4) {
+        settings.hint = !settings.hint;
+    }
+    if input.just_pressed(KeyCode::F5) {
+        settings.delays.cycle_lock();
+    }
+    if input.just_pressed(KeyCode::F6) {
+        settings.delays.cycle_line_clear();
+    }
+    if input.just_pressed(KeyCode::F7) {
+        settings.delays.cycle_entry();
+    }
+
+    // Online games start once both players are ready (`online::online_lobby_system`), watching once
+    // the watched game answers (`spectate::watch_lobby_system`)
+    if input.just_pressed(KeyCode::Enter) && !mode.starts_over_network() {
+        start_game(&mut commands, &overlay, &mut next_state, &mut boards, settings.start_level_for(*mode));
+    }
+}
+
+/// Typing edits the focused address option (Online, Watch); Backspace erases
+pub(crate) fn address_input_system(
+    mode: Res<GameMode>,
+    mut settings: ResMut<ModeSettings>,
+    mut typed: EventReader<KeyboardInput>,
+) {
+    if !settings.editing_address(*mode) {
+        typed.clear();
+        return;
+    }
+    for event in typed.read().filter(|event| event.state == ButtonState::Pressed) {
+        match &event.logical_key {
+            Key::Character(text) => settings.type_address(*mode, text),
+            Key::Backspace => settings.erase_address_char(*mode),
+            _ => {}
+        }
+    }
+}
+
+/// Resets every board to a new game at `start_level`, removes the menu and starts playing
+pub(crate) fn start_game(
+    commands: &mut Commands,
+    overlay: &Query<Entity, With<StartScreenOverlay>>,
+    next_state: &mut NextState<GameState>,
+    boards: &mut Query<&mut GameData>,
+    start_level: u32,
+) {
+    for mut game_data in boards.iter_mut() {
+        *game_data = GameData::with_start_level(start_level);
+    }
+    for entity in overlay {
+        commands.entity(entity).despawn_recursive();
+    }
+    next_state.set(GameState::Playing);
+}
+
+#[allow(clippy::type_complexity)]
+pub fn start_screen_menu_display_system(
+    mode: Res<GameMode>,
+    settings: Res<ModeSettings>,
+    mut entries: Query<(&ModeMenuEntry, &mut Text, &mut TextColor)>,
+    mut description: Query<&mut Text, (With<ModeDescriptionText>, Without<ModeMenuEntry>, Without<ModeOptionText>)>,
+    mut option: Query<&mut Text, (With<ModeOptionText>, Without<ModeMenuEntry>, Without<ModeDescriptionText>)>,
+    mut toggles: Query<
+        &mut Text,
+        (With<TogglesText>, Without<ModeMenuEntry>, Without<ModeDescriptionText>, Without<ModeOptionText>),
+    >,
+) {
+    if mode.is_changed() {
+        for (entry, mut text, mut color) in entries.iter_mut() {
+            **text = menu_entry_label(entry.0, *mode);
+            color.0 = menu_entry_color(entry.0, *mode);
+        }
+        if let Ok(mut text) = description.get_single_mut() {
+            **text = mode.description().to_string();
+        }
+    }
+    if mode.is_changed() || settings.is_changed() {
+        if let Ok(mut text) = option.get_single_mut() {
+            **text = settings.option_label(*mode).unwrap_or_default();
+        }
+        if let Ok(mut text) = toggles.get_single_mut() {
+            **text = toggles_label(&settings);
         }
-        next_state.set(GameState::Playing);
     }
 }
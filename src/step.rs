@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use bevy::ecs::schedule::{ExecutorKind, Schedule, ScheduleLabel};
+use bevy::prelude::{in_state, not, resource_equals, Condition, IntoSystemConfigs, World};
+use bevy::time::Time;
+
+use crate::common_component::{GameState, FRAME_RATE};
+use crate::game_mode::GameMode;
+use crate::spawn_block_system::{spawn_block_system, update_preview_system};
+use crate::{
+    attack, bot, dig, drop, finesse, game_mode, history, hold, line_clear, marathon, master, movement, player, practice,
+    puzzle, rotation, sprint, survival, two_player, ultra, versus, zen,
+};
+
+/// The game simulation: every system that deals, moves and locks pieces, clears lines, trades garbage or ends
+/// a game. Local games run it once per frame; online games step it at a fixed rate and replay it on corrections.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct GameStep;
+
+/// One fixed step of an online game, a frame at the rate gravity is measured in
+pub(crate) const STEP: Duration = Duration::from_nanos((1_000_000_000.0 / FRAME_RATE) as u64);
+
+/// The systems of one step of the game, in the order they run
+pub(crate) fn game_step_schedule() -> Schedule {
+    let mut schedule = Schedule::new(GameStep);
+    // One system at a time, in the same order every run, so both sides of an online game get the same result
+    schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+    schedule
+        .add_systems(
+            zen::zen_top_out_system.before(spawn_block_system).run_if(
+                in_state(GameState::Playing)
+                    .and(resource_equals(GameMode::Zen).or(resource_equals(GameMode::Practice))),
+            ),
+        )
+        .add_systems(
+            (
+                practice::practice_board_editor_system,
+                practice::practice_queue_editor_system,
+                practice::practice_garbage_system,
+            )
+                .before(spawn_block_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Practice))),
+        )
+        .add_systems(
+            dig::dig_setup_system
+                .before(spawn_block_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Dig))),
+        )
+        .add_systems(
+            dig::dig_progress_system
+                .after(line_clear::line_clear_delay_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Dig))),
+        )
+        .add_systems(
+            survival::survival_rise_system
+                .after(line_clear::line_clear_delay_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Survival))),
+        )
+        // Both sides of an online game must play by the same delays, so it keeps the standard ones
+        .add_systems(
+            game_mode::delay_settings_system
+                .before(spawn_block_system)
+                .before(drop::block_drop_system)
+                .run_if(
+                    in_state(GameState::Playing)
+                        .and(not(resource_equals(GameMode::Master)))
+                        .and(not(resource_equals(GameMode::Online))),
+                ),
+        )
+        .add_systems(
+            master::master_timing_system
+                .before(spawn_block_system)
+                .before(drop::block_drop_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Master))),
+        )
+        .add_systems(
+            master::master_level_system
+                .after(spawn_block_system)
+                .after(line_clear::eliminate_line_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Master))),
+        )
+        .add_systems(
+            puzzle::puzzle_setup_system
+                .before(spawn_block_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Puzzle))),
+        )
+        .add_systems(
+            puzzle::puzzle_progress_system
+                .after(spawn_block_system)
+                .after(line_clear::eliminate_line_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Puzzle))),
+        )
+        .add_systems((spawn_block_system, update_preview_system).chain().run_if(in_state(GameState::Playing)))
+        .add_systems(
+            (
+                history::history_undo_system.before(spawn_block_system),
+                history::history_record_system.after(spawn_block_system),
+            )
+                .run_if(
+                    in_state(GameState::Playing)
+                        .and(resource_equals(GameMode::Practice).or(resource_equals(GameMode::Puzzle))),
+                ),
+        )
+        // Online, both boards play the inputs the rollback hands them, never the keyboard or a bot directly
+        .add_systems(
+            player::read_controls_system
+                .before(rotation::block_rotation_system)
+                .before(movement::block_movement_system)
+                .before(hold::hold_block_system)
+                .before(drop::block_drop_type_system)
+                .run_if(in_state(GameState::Playing).and(not(resource_equals(GameMode::Online)))),
+        )
+        .add_systems(
+            bot::bot_input_system
+                .after(player::read_controls_system)
+                .before(rotation::block_rotation_system)
+                .before(movement::block_movement_system)
+                .before(hold::hold_block_system)
+                .before(drop::block_drop_type_system)
+                .run_if(in_state(GameState::Playing).and(not(resource_equals(GameMode::Online)))),
+        )
+        .add_systems(
+            (rotation::block_rotation_system, movement::block_movement_system, hold::hold_block_system)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            (
+                drop::block_drop_type_system,
+                drop::block_drop_system,
+                line_clear::eliminate_line_system,
+                line_clear::line_clear_delay_system,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            finesse::finesse_check_system
+                .after(drop::block_drop_system)
+                .before(line_clear::eliminate_line_system)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            attack::attack_system
+                .after(line_clear::eliminate_line_system)
+                .before(puzzle::puzzle_progress_system)
+                .run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(
+            versus::versus_setup_system
+                .before(spawn_block_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Versus))),
+        )
+        .add_systems(
+            two_player::two_player_setup_system
+                .before(spawn_block_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::TwoPlayer))),
+        )
+        .add_systems(
+            two_player::two_player_exchange_system.after(attack::attack_system).run_if(
+                in_state(GameState::Playing).and(
+                    resource_equals(GameMode::TwoPlayer)
+                        .or(resource_equals(GameMode::Versus))
+                        .or(resource_equals(GameMode::Online)),
+                ),
+            ),
+        )
+        .add_systems(game_mode::game_clock_system.run_if(in_state(GameState::Playing)))
+        .add_systems(
+            sprint::sprint_progress_system
+                .after(line_clear::eliminate_line_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Sprint))),
+        )
+        .add_systems(
+            marathon::marathon_goal_system
+                .after(line_clear::eliminate_line_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Marathon))),
+        )
+        .add_systems(
+            ultra::ultra_countdown_system
+                .after(game_mode::game_clock_system)
+                .run_if(in_state(GameState::Playing).and(resource_equals(GameMode::Ultra))),
+        );
+    schedule
+}
+
+/// Runs one step of the game with the frame's own time
+pub(crate) fn game_step_system(world: &mut World) {
+    world.run_schedule(GameStep);
+}
+
+/// Runs fixed step `frame` (counted from 0) of the game: every timer in it sees exactly `STEP` go by,
+/// whatever the frame rate
+pub(crate) fn run_fixed_step(world: &mut World, frame: u32) {
+    let mut time = Time::<()>::default();
+    time.advance_to(STEP * frame);
+    time.advance_by(STEP);
+    let real = std::mem::replace(world.resource_mut::<Time>().as_mut(), time);
+    world.run_schedule(GameStep);
+    *world.resource_mut::<Time>() = real;
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use bevy::prelude::{Commands, Entity, NextState, Query, Res, ResMut};
+use bevy::time::Time;
+use rand::Rng;
+
+use crate::board::garbage_row;
+use crate::common_component::{GameData, GameOutcome, GameState, BOARD_COLS};
+use crate::game_mode::format_time;
+use crate::garbage::{raise_garbage, BoardDotQuery, LiftedBlockQuery};
+
+const RISE_INTERVAL_LEVEL_1_SECS: f32 = 8.0;
+const RISE_INTERVAL_FLOOR_SECS: f32 = 1.0;
+const RISE_INTERVAL_FACTOR: f32 = 0.85;
+
+/// Seconds between rising garbage rows: 8s at level 1, 15% faster each level, never below 1s
+pub(crate) fn rise_interval_secs(level: u32) -> f32 {
+    (RISE_INTERVAL_LEVEL_1_SECS * RISE_INTERVAL_FACTOR.powi(level.saturating_sub(1) as i32))
+        .max(RISE_INTERVAL_FLOOR_SECS)
+}
+
+/// Pushes a garbage row up from the bottom every time the rise timer fires
+pub(crate) fn survival_rise_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut boards: Query<(Entity, &mut GameData)>,
+    mut board_dots: BoardDotQuery,
+    mut active_block: LiftedBlockQuery,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (board, mut game_data) in boards.iter_mut() {
+        let interval = Duration::from_secs_f32(rise_interval_secs(game_data.level));
+        if game_data.garbage_rise_timer.duration() != interval {
+            game_data.garbage_rise_timer.set_duration(interval);
+        }
+        // Row indices must stay put while cleared rows are animating out
+        if !game_data.clearing_rows.is_empty() {
+            continue;
+        }
+        if !game_data.garbage_rise_timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        let row = garbage_row(game_data.attack.holes.gen_range(0..BOARD_COLS));
+        if !raise_garbage(&mut commands, board, &mut game_data, &mut board_dots, &mut active_block, &[row]) {
+            game_data.outcome = GameOutcome::TopOut;
+            next_state.set(GameState::GameOver);
+        }
+    }
+}
+
+pub(crate) fn survival_result_lines(game_data: &GameData) -> Vec<String> {
+    vec![
+        format!("Survived: {}", format_time(game_data.play_time.elapsed())),
+        format!("Lines: {}", game_data.lines_cleared),
+        format!("Level: {}", game_data.level),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rise_interval_level_1_is_8_seconds() {
+        assert!((rise_interval_secs(1) - 8.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn rise_interval_accelerates_with_level() {
+        for level in 1..30 {
+            assert!(rise_interval_secs(level + 1) <= rise_interval_secs(level));
+        }
+        assert!(rise_interval_secs(2) < rise_interval_secs(1));
+    }
+
+    #[test]
+    fn rise_interval_has_floor() {
+        assert!((rise_interval_secs(100) - RISE_INTERVAL_FLOOR_SECS).abs() < f32::EPSILON);
+    }
+}
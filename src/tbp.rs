@@ -0,0 +1,493 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::{place, Board, Placement, EMPTY_CELL};
+use crate::bot::Plan;
+use crate::common_component::{GameData, BOARD_COLS, BOARD_ROWS};
+use crate::line_clear::collapse_full_rows;
+use crate::move_gen::{path_rows, reachable_placements};
+use crate::spawn_block_system::{Randomizer7Bag, PREVIEW_SLOTS};
+use crate::tetromino;
+
+/// Environment variable holding the command that starts an external bot, e.g. `TETRIS_TBP_BOT=cold-clear`
+pub(crate) const BOT_COMMAND_VAR: &str = "TETRIS_TBP_BOT";
+/// Rows of the board the protocol describes; the ones above the visible 20 are always empty here
+const TBP_ROWS: usize = 40;
+
+/// The command that starts the external bot, if one is set
+pub(crate) fn bot_command() -> Option<String> {
+    std::env::var(BOT_COMMAND_VAR).ok().filter(|command| !command.trim().is_empty())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Spin {
+    #[default]
+    None,
+    Mini,
+    Full,
+}
+
+/// Where a piece ends up: `x` and `y` are its centre in SRS terms, counted from the bottom left cell
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PieceLocation {
+    #[serde(rename = "type")]
+    pub piece: char,
+    pub orientation: Orientation,
+    pub x: i8,
+    pub y: i8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Move {
+    pub location: PieceLocation,
+    #[serde(default)]
+    pub spin: Spin,
+}
+
+impl Move {
+    /// The move that puts the piece where `placement` has it, without a spin
+    pub fn from_placement(placement: &Placement) -> Self {
+        let cells = placement_cells(placement);
+        let orientations = [Orientation::North, Orientation::East, Orientation::South, Orientation::West];
+        // Every piece turns around one of its own cells
+        orientations
+            .into_iter()
+            .flat_map(|orientation| cells.map(|(x, row)| (orientation, x, BOARD_ROWS as i8 - 1 - row)))
+            .map(|(orientation, x, y)| Move {
+                location: PieceLocation {
+                    piece: placement.block.letter(),
+                    orientation,
+                    x,
+                    y,
+                },
+                spin: Spin::None,
+            })
+            .find(|candidate| candidate.cells() == Some(cells))
+            .expect("a piece of the game")
+    }
+
+    /// The board cells (column, row counting down) the piece covers, `None` for a piece this game does not have
+    pub fn cells(&self) -> Option<[(i8, i8); 4]> {
+        let location = &self.location;
+        let north: [(i8, i8); 4] = match location.piece {
+            'I' => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+            'O' => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            'T' => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+            'S' => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+            'Z' => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+            'J' => [(-1, 1), (-1, 0), (0, 0), (1, 0)],
+            'L' => [(1, 1), (-1, 0), (0, 0), (1, 0)],
+            _ => return None,
+        };
+        let mut cells = north.map(|(dx, dy)| {
+            // Turning clockwise takes (x, y) to (y, -x) around the centre; y points up
+            let (dx, dy) = match location.orientation {
+                Orientation::North => (dx, dy),
+                Orientation::East => (dy, -dx),
+                Orientation::South => (-dx, -dy),
+                Orientation::West => (-dy, dx),
+            };
+            (location.x + dx, BOARD_ROWS as i8 - 1 - (location.y + dy))
+        });
+        cells.sort();
+        Some(cells)
+    }
+}
+
+fn placement_cells(placement: &Placement) -> [(i8, i8); 4] {
+    let mut cells = placement.block.dots_by_state().map(|dot| (placement.column + dot.x, placement.row + dot.y));
+    cells.sort();
+    cells
+}
+
+/// The game's messages to the bot
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum FrontendMessage<'a> {
+    Rules {},
+    Start {
+        hold: Option<char>,
+        queue: &'a [char],
+        combo: u32,
+        back_to_back: bool,
+        board: Vec<[Option<char>; BOARD_COLS]>,
+    },
+    Stop,
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        played: &'a Move,
+    },
+    NewPiece {
+        piece: char,
+    },
+    Quit,
+}
+
+/// The bot's messages to the game
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BotMessage {
+    Info {
+        name: String,
+        #[serde(default)]
+        version: String,
+    },
+    Ready,
+    Error {
+        reason: String,
+    },
+    Suggestion {
+        moves: Vec<Move>,
+    },
+}
+
+/// The game as the bot gets to see it when asked for a move
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub board: Board,
+    /// The piece to play, then the next pieces
+    pub queue: Vec<char>,
+    pub hold: Option<char>,
+    pub combo: u32,
+    pub back_to_back: bool,
+}
+
+impl Snapshot {
+    pub fn new(game_data: &GameData, current: &tetromino::Block, queue: &Randomizer7Bag) -> Self {
+        Self {
+            board: game_data.board_matrix,
+            queue: std::iter::once(current)
+                .chain(queue.peek(PREVIEW_SLOTS))
+                .map(|block| block.letter())
+                .collect(),
+            hold: game_data.held_block.as_ref().map(|block| block.letter()),
+            // The protocol counts clears in a row, the game one less
+            combo: game_data.attack.combo.map_or(0, |combo| combo + 1),
+            back_to_back: game_data.attack.back_to_back,
+        }
+    }
+
+    /// Bottom row first; the game keeps no piece letters on its board, so every filled cell is garbage
+    fn tbp_board(&self) -> Vec<[Option<char>; BOARD_COLS]> {
+        let mut rows: Vec<_> = self
+            .board
+            .iter()
+            .rev()
+            .map(|row| row.map(|cell| (cell != EMPTY_CELL).then_some('G')))
+            .collect();
+        rows.resize(TBP_ROWS, [None; BOARD_COLS]);
+        rows
+    }
+}
+
+/// What the bot believes the game looks like after the moves it was told about
+struct Known {
+    queue: VecDeque<char>,
+    hold: Option<char>,
+    board: Board,
+}
+
+fn same_cells(a: &Board, b: &Board) -> bool {
+    a.iter().flatten().zip(b.iter().flatten()).all(|(a, b)| (*a != EMPTY_CELL) == (*b != EMPTY_CELL))
+}
+
+/// An external bot speaking the Tetris Bot Protocol: JSON messages, one per line, over the standard
+/// input and output of a child process
+pub struct TbpBot {
+    child: Child,
+    stdin: ChildStdin,
+    /// Messages read from the bot's output by a helper thread, so a slow bot never stalls a frame
+    replies: Mutex<Receiver<BotMessage>>,
+    ready: bool,
+    /// A suggestion was asked for and has not come yet
+    asked: bool,
+    /// `None` until the bot is started, and whenever the game went somewhere the bot could not foresee
+    known: Option<Known>,
+    /// Times the bot was (re)started on a new game state
+    starts: u32,
+}
+
+impl TbpBot {
+    /// Starts `command` (a program and its arguments, split on whitespace)
+    pub fn spawn(command: &str) -> io::Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no bot command given"))?;
+        let mut child = Command::new(program).args(words).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, replies) = mpsc::channel();
+        let reader = std::thread::Builder::new().name("tbp-bot".to_string()).spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                // Messages this side does not know are skipped, as the protocol asks
+                if let Ok(message) = serde_json::from_str(&line) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        if let Err(err) = reader {
+            let _ = child.kill();
+            return Err(err);
+        }
+        Ok(Self {
+            child,
+            stdin,
+            replies: Mutex::new(replies),
+            ready: false,
+            asked: false,
+            known: None,
+            starts: 0,
+        })
+    }
+
+    fn send(&mut self, message: &FrontendMessage) -> io::Result<()> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        self.stdin.write_all(line.as_bytes())?;
+        self.stdin.flush()
+    }
+
+    /// Handles what the bot said since the last call; returns the moves of the suggestion asked for
+    fn receive(&mut self) -> io::Result<Option<Vec<Move>>> {
+        loop {
+            let received = self.replies.get_mut().expect("the reader never panics holding the lock").try_recv();
+            let message = match received {
+                Ok(message) => message,
+                Err(TryRecvError::Empty) => return Ok(None),
+                Err(TryRecvError::Disconnected) => {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the bot quit"));
+                }
+            };
+            match message {
+                BotMessage::Info { name, version } => {
+                    bevy::log::info!("playing with the bot {name} {version}");
+                    self.send(&FrontendMessage::Rules {})?;
+                }
+                BotMessage::Ready => self.ready = true,
+                BotMessage::Error { reason } => {
+                    return Err(io::Error::other(format!("the bot turned down the rules: {reason}")));
+                }
+                BotMessage::Suggestion { moves } if self.asked => {
+                    self.asked = false;
+                    return Ok(Some(moves));
+                }
+                BotMessage::Suggestion { .. } => {}
+            }
+        }
+    }
+
+    /// Brings the bot up to `snapshot`: new pieces when the game went the way it was told, otherwise a restart
+    fn catch_up(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        if let Some(known) = &self.known {
+            let follows = same_cells(&known.board, &snapshot.board)
+                && known.hold == snapshot.hold
+                && known.queue.len() <= snapshot.queue.len()
+                && known.queue.iter().eq(&snapshot.queue[..known.queue.len()]);
+            if follows {
+                let new_pieces = snapshot.queue[known.queue.len()..].to_vec();
+                for piece in new_pieces {
+                    self.send(&FrontendMessage::NewPiece { piece })?;
+                }
+                self.known.as_mut().expect("checked above").queue = snapshot.queue.iter().copied().collect();
+                return Ok(());
+            }
+            self.send(&FrontendMessage::Stop)?;
+        }
+        self.send(&FrontendMessage::Start {
+            hold: snapshot.hold,
+            queue: &snapshot.queue,
+            combo: snapshot.combo,
+            back_to_back: snapshot.back_to_back,
+            board: snapshot.tbp_board(),
+        })?;
+        self.known = Some(Known {
+            queue: snapshot.queue.iter().copied().collect(),
+            hold: snapshot.hold,
+            board: snapshot.board,
+        });
+        self.starts += 1;
+        Ok(())
+    }
+
+    /// Asks for moves for the first piece of `snapshot` once the bot is ready, and returns them when they come.
+    /// Call it every frame until it returns moves, then tell the bot which one was played.
+    pub fn suggest(&mut self, snapshot: &Snapshot) -> io::Result<Option<Vec<Move>>> {
+        let moves = self.receive()?;
+        if moves.is_none() && self.ready && !self.asked {
+            self.catch_up(snapshot)?;
+            self.send(&FrontendMessage::Suggest)?;
+            self.asked = true;
+        }
+        Ok(moves)
+    }
+
+    /// Tells the bot `played` was made, `placement` being where it put the piece on the game's board
+    pub fn play(&mut self, played: &Move, placement: &Placement) -> io::Result<()> {
+        self.send(&FrontendMessage::Play { played })?;
+        let Some(known) = &mut self.known else {
+            return Ok(());
+        };
+        // A piece other than the first one comes out of hold, or from behind the first when the hold is empty
+        let current = known.queue.pop_front();
+        if current != Some(played.location.piece) {
+            if known.hold.is_none() {
+                known.queue.pop_front();
+            }
+            known.hold = current;
+        }
+        match place(&known.board, placement) {
+            Some(mut board) => {
+                collapse_full_rows(&mut board);
+                known.board = board;
+            }
+            None => self.known = None,
+        }
+        Ok(())
+    }
+
+    /// Times the bot was started on a game state it could not have followed from the moves it was told about
+    pub fn starts(&self) -> u32 {
+        self.starts
+    }
+
+    /// None of the suggested moves could be made; the bot is started afresh for the next piece
+    pub fn lost_track(&mut self) {
+        self.known = None;
+    }
+}
+
+impl Drop for TbpBot {
+    fn drop(&mut self) {
+        let _ = self.send(&FrontendMessage::Quit);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The first of `moves` the game can make, with the plan that makes it: its piece has to be `current`, or
+/// `alternative` (what holding brings in, `None` when holding is not possible), and its cells reachable
+pub fn choose(
+    moves: &[Move],
+    board: &Board,
+    current: &tetromino::Block,
+    alternative: Option<&tetromino::Block>,
+) -> Option<(Move, Plan)> {
+    moves.iter().find_map(|candidate| {
+        let cells = candidate.cells()?;
+        let (hold, piece) = match alternative {
+            _ if candidate.location.piece == current.letter() => (false, current),
+            Some(piece) if candidate.location.piece == piece.letter() => (true, piece),
+            _ => return None,
+        };
+        let mut reachable: Vec<_> = reachable_placements(board, piece)
+            .into_iter()
+            .filter(|reachable| placement_cells(&reachable.placement) == cells)
+            .collect();
+        // A spin the bot counts on needs the path that ends in a rotation
+        reachable.sort_by_key(|reachable| reachable.t_spin != (candidate.spin != Spin::None));
+        let target = reachable.into_iter().next()?;
+        let plan = Plan {
+            hold,
+            rows: path_rows(board, piece, &target.path),
+            target,
+        };
+        Some((candidate.clone(), plan))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{placements, GARBAGE_CELL};
+    use crate::tetromino::Block;
+
+    fn location(piece: char, orientation: Orientation, x: i8, y: i8) -> Move {
+        Move {
+            location: PieceLocation {
+                piece,
+                orientation,
+                x,
+                y,
+            },
+            spin: Spin::None,
+        }
+    }
+
+    #[test]
+    fn messages_match_the_protocol() {
+        let played = location('T', Orientation::East, 4, 1);
+        assert_eq!(
+            serde_json::to_string(&FrontendMessage::Play { played: &played }).unwrap(),
+            r#"{"type":"play","move":{"location":{"type":"T","orientation":"east","x":4,"y":1},"spin":"none"}}"#
+        );
+        assert_eq!(serde_json::to_string(&FrontendMessage::Rules {}).unwrap(), r#"{"type":"rules"}"#);
+        let reply = r#"{"type":"suggestion","moves":[{"location":{"type":"I","orientation":"north","x":4,"y":0},
+            "spin":"none"}],"move_info":{"nodes":1}}"#;
+        match serde_json::from_str(&reply.replace('\n', "")) {
+            Ok(BotMessage::Suggestion { moves }) => assert_eq!(moves, vec![location('I', Orientation::North, 4, 0)]),
+            _ => panic!("a suggestion"),
+        }
+        let mut snapshot = Snapshot {
+            board: [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS],
+            queue: vec!['T'],
+            hold: None,
+            combo: 0,
+            back_to_back: false,
+        };
+        snapshot.board[BOARD_ROWS - 1][3] = GARBAGE_CELL;
+        let board = snapshot.tbp_board();
+        assert_eq!(board.len(), TBP_ROWS);
+        assert_eq!(board[0][3], Some('G'));
+        assert_eq!(board[1][3], None);
+    }
+
+    #[test]
+    fn locations_become_the_cells_the_game_would_fill() {
+        let board = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+        // A flat I on the floor and an upright one against the left wall, a T pointing down into the floor
+        for (piece, orientation, x, y) in
+            [('I', Orientation::North, 4, 0), ('I', Orientation::East, 0, 2), ('T', Orientation::South, 1, 1)]
+        {
+            let block = Block::from_letter(piece).unwrap();
+            let (suggested, plan) =
+                choose(&[location(piece, orientation, x, y)], &board, &block, None).expect("reachable");
+            assert_eq!(Some(placement_cells(&plan.target.placement)), suggested.cells());
+            assert!(!plan.hold);
+        }
+        // Out of the board, or a piece neither in play nor behind the hold
+        let t = Block::new_t();
+        assert!(choose(&[location('T', Orientation::North, 9, 0)], &board, &t, None).is_none());
+        assert!(choose(&[location('S', Orientation::North, 4, 0)], &board, &t, Some(&Block::new_z())).is_none());
+        let (_, plan) = choose(&[location('Z', Orientation::North, 4, 0)], &board, &t, Some(&Block::new_z())).unwrap();
+        assert!(plan.hold);
+
+        // And back: every drop the game can make has a location that covers the same cells
+        for piece in ['I', 'O', 'T', 'S', 'Z', 'J', 'L'] {
+            for placement in placements(&board, &Block::from_letter(piece).unwrap()) {
+                assert_eq!(Move::from_placement(&placement).cells(), Some(placement_cells(&placement)));
+            }
+        }
+    }
+}
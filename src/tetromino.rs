@@ -115,6 +115,32 @@ impl Block {
         }
     }
 
+    /// Piece for a letter of "IOTSZJL" (case-insensitive), as used in puzzle files
+    pub fn from_letter(letter: char) -> Option<Self> {
+        match letter.to_ascii_uppercase() {
+            'I' => Some(Self::new_i()),
+            'O' => Some(Self::new_o()),
+            'T' => Some(Self::new_t()),
+            'S' => Some(Self::new_s()),
+            'Z' => Some(Self::new_z()),
+            'J' => Some(Self::new_j()),
+            'L' => Some(Self::new_l()),
+            _ => None,
+        }
+    }
+
+    pub fn letter(&self) -> char {
+        match self {
+            Self::I { .. } => 'I',
+            Self::O { .. } => 'O',
+            Self::T { .. } => 'T',
+            Self::S { .. } => 'S',
+            Self::Z { .. } => 'Z',
+            Self::J { .. } => 'J',
+            Self::L { .. } => 'L',
+        }
+    }
+
     pub fn new_i() -> Self {
         Self::I {
             dots: [
@@ -410,6 +436,15 @@ mod tests {
         ]
     }
 
+    #[test]
+    fn letters_round_trip() {
+        for block in all_blocks() {
+            assert_eq!(Block::from_letter(block.letter()).map(|b| b.letter()), Some(block.letter()));
+        }
+        assert_eq!(Block::from_letter('t').map(|b| b.letter()), Some('T'));
+        assert!(Block::from_letter('X').is_none());
+    }
+
     #[test]
     fn initial_state_is_zero() {
         for block in all_blocks() {
@@ -0,0 +1,164 @@
+use bevy::math::Vec3;
+use bevy::prelude::{
+    Commands, DespawnRecursiveExt, Entity, Query, Res, Transform, With, Without,
+};
+use bevy::sprite::Sprite;
+use bevy::text::Text2d;
+
+use crate::attack::exchange;
+use crate::bot::{Bot, BotTier};
+use crate::common_component::{GameData, DOT_SIZE};
+use crate::game_mode::{format_time, ModeSettings};
+use crate::player::{spawn_board, Controls, MainBoard, PlayerBoard};
+use crate::side_board::{
+    size_side_garbage_meter, spawn_side_board_view, SideBoardInfo, SideBoardLayout, SideBoardView, SideGarbageMeter,
+};
+use crate::spawn_block_system::Randomizer7Bag;
+
+/// Where player 2's board is drawn, in the space right of player 1's
+const SECOND_BOARD: SideBoardLayout = SideBoardLayout {
+    x: 302.5,
+    y: 30.0,
+    dot_size: 16.0,
+};
+
+/// Pieces listed in player 2's NEXT line
+const NEXT_SHOWN: usize = 5;
+
+/// Player 2's board: every board but the main one
+type SecondBoardQuery<'w, 's> =
+    Query<'w, 's, (&'static GameData, &'static Randomizer7Bag), (With<PlayerBoard>, Without<MainBoard>)>;
+
+/// Builds player 2's board at the start of every two-player game, replacing the last game's; with the
+/// shared queue option both players' randomizers get the same seed, and the bot can take player 2's seat
+pub(crate) fn two_player_setup_system(
+    mut commands: Commands,
+    mut main_board: Query<(&mut GameData, &mut Randomizer7Bag), With<MainBoard>>,
+    other_boards: Query<Entity, (With<PlayerBoard>, Without<MainBoard>)>,
+    settings: Res<ModeSettings>,
+    view: Query<Entity, With<SideBoardView>>,
+) {
+    let Ok((mut game_data, mut randomizer)) = main_board.get_single_mut() else {
+        return;
+    };
+    if game_data.initial_board_placed {
+        return;
+    }
+    game_data.initial_board_placed = true;
+
+    let title = if settings.bot_second_player {
+        format!("BOT ({})", settings.cpu_difficulty.name())
+    } else {
+        "PLAYER 2".to_string()
+    };
+    let second = replace_second_board(&mut commands, &other_boards, &view, title);
+    if settings.shared_queue {
+        let seed = rand::random();
+        *randomizer = Randomizer7Bag::seeded(seed);
+        commands.entity(second).insert(Randomizer7Bag::seeded(seed));
+    }
+    if settings.bot_second_player {
+        let tier = BotTier::preset(settings.cpu_difficulty);
+        commands.entity(second).insert(Bot::for_settings(&settings).with_tier(tier));
+    }
+}
+
+/// Spawns the second board right of player 1's, with its status view titled `title`, in place of the last
+/// game's (Versus seats the CPU there too)
+pub(crate) fn replace_second_board(
+    commands: &mut Commands,
+    other_boards: &Query<Entity, (With<PlayerBoard>, Without<MainBoard>)>,
+    view: &Query<Entity, With<SideBoardView>>,
+    title: String,
+) -> Entity {
+    for entity in other_boards.iter().chain(view.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+    let transform = Transform::from_xyz(SECOND_BOARD.x, SECOND_BOARD.y, 0.0)
+        .with_scale(Vec3::new(SECOND_BOARD.dot_size / DOT_SIZE, SECOND_BOARD.dot_size / DOT_SIZE, 1.0));
+    let second = spawn_board(commands, 2, transform, Controls::player_two());
+    spawn_side_board_view(commands, SECOND_BOARD, title);
+    second
+}
+
+/// Hands each player's outgoing attack to the other's incoming queue
+pub(crate) fn two_player_exchange_system(mut boards: Query<&mut GameData, With<PlayerBoard>>) {
+    let mut boards: Vec<_> = boards.iter_mut().collect();
+    if let [first, second] = &mut boards[..] {
+        exchange(&mut first.attack, &mut second.attack);
+    }
+}
+
+/// Player 2's hold, queue and score, shown under their board
+fn second_board_info(game_data: &GameData, randomizer: &Randomizer7Bag) -> String {
+    let next: String = randomizer.peek(NEXT_SHOWN).iter().map(|block| block.letter()).collect();
+    let held = game_data.held_block.as_ref().map_or('-', |block| block.letter());
+    format!(
+        "Hold: {held}  Next: {next}\nScore: {}  Lines: {}",
+        game_data.score, game_data.lines_cleared
+    )
+}
+
+/// Keeps player 2's garbage meter and status text up to date
+pub(crate) fn update_second_board_view(
+    second: SecondBoardQuery,
+    mut meter: Query<(&mut Sprite, &mut Transform), With<SideGarbageMeter>>,
+    mut info: Query<&mut Text2d, With<SideBoardInfo>>,
+) {
+    let Ok((game_data, randomizer)) = second.get_single() else {
+        return;
+    };
+    size_side_garbage_meter(SECOND_BOARD, game_data.attack.incoming_lines(), &mut meter);
+    let Ok(mut text) = info.get_single_mut() else {
+        return;
+    };
+    let content = second_board_info(game_data, randomizer);
+    if text.0 != content {
+        text.0 = content;
+    }
+}
+
+/// Score, attack and lines of player 1 against player 2
+pub(crate) fn two_player_result_lines(game_data: &GameData, other_boards: &[&GameData]) -> Vec<String> {
+    let Some(second) = other_boards.first() else {
+        return vec![format!("Score: {}", game_data.score)];
+    };
+    vec![
+        format!("Score: {} - {}", game_data.score, second.score),
+        format!("Lines sent: {} - {}", game_data.attack.lines_sent, second.attack.lines_sent),
+        format!("Lines: {} - {}", game_data.lines_cleared, second.lines_cleared),
+        format!("Time: {}", format_time(game_data.play_time.elapsed())),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetromino;
+
+    #[test]
+    fn info_lists_hold_queue_and_score() {
+        let game_data = GameData {
+            held_block: Some(tetromino::Block::new_t()),
+            score: 1200,
+            lines_cleared: 7,
+            ..Default::default()
+        };
+        let blocks = "IOSZJL".chars().filter_map(tetromino::Block::from_letter).collect();
+        let info = second_board_info(&game_data, &Randomizer7Bag::from_sequence(blocks));
+        assert_eq!(info, "Hold: T  Next: IOSZJ\nScore: 1200  Lines: 7");
+    }
+
+    #[test]
+    fn results_put_player_1_first() {
+        let first = GameData {
+            score: 500,
+            ..Default::default()
+        };
+        let second = GameData {
+            score: 300,
+            ..Default::default()
+        };
+        assert_eq!(two_player_result_lines(&first, &[&second])[0], "Score: 500 - 300");
+    }
+}
@@ -0,0 +1,66 @@
+use bevy::prelude::{Has, NextState, Query, Res, ResMut, Resource};
+
+use crate::bot::Bot;
+use crate::common_component::{GameData, GameOutcome, GameState};
+use crate::game_mode::ModeSettings;
+use crate::records::Records;
+
+/// Best Ultra score for the played time limit, taken before the last game was recorded
+#[derive(Resource, Default)]
+pub(crate) struct UltraResult {
+    pub previous_best: Option<u32>,
+}
+
+/// Ends the game with a "time up" once the time limit is reached; the score at that moment is final.
+/// A bot's score is not recorded.
+pub(crate) fn ultra_countdown_system(
+    mut boards: Query<(&mut GameData, Has<Bot>)>,
+    settings: Res<ModeSettings>,
+    mut records: ResMut<Records>,
+    mut result: ResMut<UltraResult>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (mut game_data, bot) in boards.iter_mut() {
+        if game_data.play_time.elapsed() < settings.ultra_duration() {
+            continue;
+        }
+
+        game_data.outcome = GameOutcome::TimeUp;
+        result.previous_best = if bot {
+            records.ultra_best.get(&settings.ultra_duration_secs).copied()
+        } else {
+            records.submit_ultra(settings.ultra_duration_secs, game_data.score)
+        };
+        next_state.set(GameState::GameOver);
+    }
+}
+
+pub(crate) fn ultra_result_lines(result: &UltraResult, score: u32, lines_cleared: u32) -> Vec<String> {
+    let mut lines = vec![format!("Score: {score}"), format!("Lines: {lines_cleared}")];
+    match result.previous_best {
+        Some(best) if score <= best => lines.push(format!("Best: {best}")),
+        Some(best) => lines.push(format!("New best! (+{})", score - best)),
+        None => lines.push("New best!".to_string()),
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn result_lines_compare_against_best() {
+        let result = UltraResult {
+            previous_best: Some(1000),
+        };
+        assert_eq!(ultra_result_lines(&result, 800, 4)[2], "Best: 1000");
+        assert_eq!(ultra_result_lines(&result, 1500, 9)[2], "New best! (+500)");
+    }
+
+    #[test]
+    fn result_lines_first_game_is_best() {
+        let lines = ultra_result_lines(&UltraResult::default(), 300, 1);
+        assert_eq!(lines, vec!["Score: 300", "Lines: 1", "New best!"]);
+    }
+}
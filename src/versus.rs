@@ -0,0 +1,72 @@
+use bevy::prelude::{Commands, Entity, Query, Res, With, Without};
+
+use crate::bot::{Bot, BotTier};
+use crate::common_component::GameData;
+use crate::game_mode::{format_time, CpuDifficulty, ModeSettings};
+use crate::player::{MainBoard, PlayerBoard};
+use crate::side_board::SideBoardView;
+use crate::two_player::replace_second_board;
+
+/// Seats the CPU at the start of every Versus game: a board of its own beside the player's, played by the bot
+/// at the chosen level
+pub(crate) fn versus_setup_system(
+    mut commands: Commands,
+    mut main_board: Query<&mut GameData, With<MainBoard>>,
+    other_boards: Query<Entity, (With<PlayerBoard>, Without<MainBoard>)>,
+    settings: Res<ModeSettings>,
+    view: Query<Entity, With<SideBoardView>>,
+) {
+    let Ok(mut game_data) = main_board.get_single_mut() else {
+        return;
+    };
+    if game_data.initial_board_placed {
+        return;
+    }
+    game_data.initial_board_placed = true;
+    let title = format!("CPU ({})", settings.cpu_difficulty.name());
+    let cpu = replace_second_board(&mut commands, &other_boards, &view, title);
+    let tier = BotTier::preset(settings.cpu_difficulty);
+    commands.entity(cpu).insert(Bot::for_settings(&settings).with_tier(tier));
+}
+
+pub(crate) fn versus_result_lines(game_data: &GameData, other_boards: &[&GameData], cpu: CpuDifficulty) -> Vec<String> {
+    let cpu_lines = other_boards.first().map_or(0, |cpu| cpu.lines_cleared);
+    vec![
+        format!("vs CPU ({})", cpu.name()),
+        format!("Lines sent: {}", game_data.attack.lines_sent),
+        format!("Lines: {} (CPU {cpu_lines})", game_data.lines_cleared),
+        format!("Time: {}", format_time(game_data.play_time.elapsed())),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::prelude::World;
+
+    use crate::player::spawn_main_board;
+
+    #[test]
+    fn the_cpu_plays_a_board_of_its_own_through_the_bot() {
+        let mut world = World::new();
+        let mut settings = ModeSettings::default();
+        settings.cpu_difficulty = CpuDifficulty::Hard;
+        world.insert_resource(settings);
+        world.run_system_once(spawn_main_board).expect("the system runs");
+        for _ in 0..2 {
+            world.run_system_once(versus_setup_system).expect("the system runs");
+            // A restart seats a new CPU in place of the last one
+            let mut boards = world.query::<&mut GameData>();
+            for mut game_data in boards.iter_mut(&mut world) {
+                game_data.initial_board_placed = false;
+            }
+        }
+        let mut cpus = world.query_filtered::<(&PlayerBoard, &Bot), Without<MainBoard>>();
+        let cpus: Vec<_> = cpus.iter(&world).collect();
+        assert_eq!(cpus.len(), 1);
+        let (board, bot) = cpus[0];
+        assert_eq!(board.player, 2);
+        assert_eq!(bot.tier, Some(BotTier::preset(CpuDifficulty::Hard)));
+    }
+}
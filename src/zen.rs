@@ -0,0 +1,34 @@
+use bevy::prelude::{Commands, DespawnRecursiveExt, Entity, Parent, Query, With};
+
+use crate::board::BoardDot;
+use crate::common_component::{ActiveBlock, GameData, BOARD_COLS, BOARD_ROWS};
+use crate::spawn_block_system::{can_spawn, Randomizer7Bag};
+
+/// Zen (and Practice) never tops out: when the next piece would not fit, the board is wiped so play can continue
+pub(crate) fn zen_top_out_system(
+    mut commands: Commands,
+    mut boards: Query<(Entity, &mut GameData, &Randomizer7Bag)>,
+    active_blocks: Query<&Parent, With<ActiveBlock>>,
+    board_dots: Query<(Entity, &Parent), With<BoardDot>>,
+) {
+    for (board, mut game_data, randomizer) in boards.iter_mut() {
+        // Rows still animating out may free enough room once they are gone
+        let piece_out = active_blocks.iter().any(|parent| parent.get() == board);
+        if piece_out || !game_data.clearing_rows.is_empty() {
+            continue;
+        }
+        let Some(next) = randomizer.peek(1).first().copied() else {
+            continue;
+        };
+        if can_spawn(&game_data.board_matrix, next) {
+            continue;
+        }
+
+        game_data.board_matrix = [[0; BOARD_COLS]; BOARD_ROWS];
+        for (entity, parent) in board_dots.iter() {
+            if parent.get() == board {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! The game's side of the Tetris Bot Protocol, played against the stand-in bot from `src/bin/tbp_stand_in.rs`
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use bevy_tetris::board::{EMPTY_CELL, GARBAGE_CELL};
+use bevy_tetris::common_component::{BOARD_COLS, BOARD_ROWS};
+use bevy_tetris::line_clear::collapse_full_rows;
+use bevy_tetris::spawn_block_system::{Randomizer7Bag, PREVIEW_SLOTS};
+use bevy_tetris::tbp::{choose, Move, Snapshot, TbpBot};
+use bevy_tetris::board::place;
+use serde_json::Value;
+
+#[test]
+fn the_stand_in_answers_like_a_bot() {
+    let mut bot = Command::new(env!("CARGO_BIN_EXE_tbp_stand_in"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("the stand-in starts");
+    let mut stdin = bot.stdin.take().unwrap();
+    let mut replies = BufReader::new(bot.stdout.take().unwrap()).lines();
+    let mut next_reply = move || -> Value { serde_json::from_str(&replies.next().unwrap().unwrap()).unwrap() };
+
+    assert_eq!(next_reply()["type"], "info");
+    writeln!(stdin, r#"{{"type":"rules"}}"#).unwrap();
+    assert_eq!(next_reply()["type"], "ready");
+    let board = vec![vec![Value::Null; 10]; 40];
+    let start = serde_json::json!({"type": "start", "hold": null, "queue": ["O", "T"], "combo": 0,
+        "back_to_back": false, "board": board});
+    writeln!(stdin, "{start}").unwrap();
+    writeln!(stdin, r#"{{"type":"suggest"}}"#).unwrap();
+    let suggestion = next_reply();
+    assert_eq!(suggestion["type"], "suggestion");
+    let best = &suggestion["moves"][0]["location"];
+    assert_eq!(best["type"], "O");
+    assert_eq!(best["y"], 0);
+
+    writeln!(stdin, r#"{{"type":"quit"}}"#).unwrap();
+    assert!(bot.wait().unwrap().success());
+}
+
+fn wait_for_moves(bot: &mut TbpBot, snapshot: &Snapshot) -> Vec<Move> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(moves) = bot.suggest(snapshot).expect("the bot keeps talking") {
+            return moves;
+        }
+        assert!(Instant::now() < deadline, "no suggestion");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn plays_a_game_with_the_stand_in_bot() {
+    let mut bot = TbpBot::spawn(env!("CARGO_BIN_EXE_tbp_stand_in")).expect("the stand-in bot starts");
+    let mut board = [[EMPTY_CELL; BOARD_COLS]; BOARD_ROWS];
+    let mut queue = Randomizer7Bag::seeded(3);
+    let mut lines = 0;
+    for piece in 0..40 {
+        if piece == 20 {
+            // Garbage the bot was not told about
+            board[BOARD_ROWS - 1] = [GARBAGE_CELL; BOARD_COLS];
+            board[BOARD_ROWS - 1][0] = EMPTY_CELL;
+        }
+        let current = queue.pop_next().unwrap();
+        let snapshot = Snapshot {
+            board,
+            queue: std::iter::once(&current).chain(queue.peek(PREVIEW_SLOTS)).map(|b| b.letter()).collect(),
+            hold: None,
+            combo: 0,
+            back_to_back: false,
+        };
+        let moves = wait_for_moves(&mut bot, &snapshot);
+        let (played, plan) = choose(&moves, &board, &current, None).expect("a move the game can make");
+        bot.play(&played, &plan.target.placement).unwrap();
+        board = place(&board, &plan.target.placement).unwrap();
+        lines += collapse_full_rows(&mut board);
+    }
+    assert!(lines > 0);
+    // Told about every piece as it came, and started again only after the garbage
+    assert_eq!(bot.starts(), 2);
+}